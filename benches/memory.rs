@@ -19,8 +19,8 @@ fn load_acid() -> Gbc {
     let filename = "./tests/dmg-acid2.gb";
     let data = std::fs::read(filename).unwrap();
 
-    let rom_size = RomSize::from_byte(data[0x0148]);
-    let ram_size = RamSize::from_byte(data[0x0149]);
+    let rom_size = RomSize::from_byte(data[0x0148]).unwrap();
+    let ram_size = RamSize::from_byte(data[0x0149]).unwrap();
 
     let mbc = match data[0x0147] {
         0x00 => MbcSelector::NoMbc,
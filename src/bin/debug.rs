@@ -7,8 +7,8 @@ fn main() {
     let filename = std::env::args().nth(1).unwrap();
     let data = std::fs::read(filename).unwrap();
 
-    let rom_size = RomSize::from_byte(data[0x0148]);
-    let ram_size = RamSize::from_byte(data[0x0149]);
+    let rom_size = RomSize::from_byte(data[0x0148]).unwrap();
+    let ram_size = RamSize::from_byte(data[0x0149]).unwrap();
 
     let mbc = match data[MBC_ADDR] {
         0x00 => MbcSelector::NoMbc,
@@ -20,7 +20,6 @@ fn main() {
 
     let mut unlocked = false;
     let mut stepping = true;
-    let mut vram_window = false;
     let mut skip: u64 = 0;
     let mut serial_buf = String::new();
 
@@ -134,12 +133,23 @@ fn main() {
                     // opcodes must be space separated 8 bit integers, and can be in hexadecimal
 
                 } else if input == "show vram" {
-                    if !vram_window {
-                        emu.cpu.ppu.init_debug();
-                        vram_window = true;
+                    // Usage: show vram
+                    // dumps the VRAM tile data to vram.ppm, 16 tiles wide and 24 tall
+                    const SIZE: [usize; 2] = [16, 24];
+                    let (width, height) = (SIZE[0] * 8, SIZE[1] * 8);
+                    let mut fb = vec![0; width * height * 3];
+
+                    emu.cpu.ppu.debug_show(&emu.cpu.memory, SIZE, None, &mut fb);
+
+                    let mut out = format!("P6\n{width} {height}\n255\n").into_bytes();
+                    out.extend_from_slice(&fb);
+
+                    match std::fs::write("vram.ppm", out) {
+                        Ok(()) => println!("VRAM written to vram.ppm"),
+                        Err(e) => println!("Failed to write vram.ppm: {e}"),
                     }
 
-                    emu.cpu.ppu.debug_show(&emu.cpu.memory)
+                    continue;
                 } else if input.starts_with("show ") {
                     // Usage: show <ADDR:int>
                     // Prints the value stored in memory at ADDR
@@ -180,7 +190,7 @@ fn main() {
                         println!("Serial buffer: {}", serial_buf);
                         return;
                     }
-                    CpuStatus::Run => {
+                    CpuStatus::Run(_) => {
                         let serial = emu.read_serial();
                 
                         if serial != 0xFF {
@@ -188,14 +198,15 @@ fn main() {
                             serial_buf += &format!("{}", serial as char);
                         }
                     }
-                    CpuStatus::Break => {}
+                    CpuStatus::Break(_, _) | CpuStatus::Halt | CpuStatus::BlockedByDma => {}
                 }
             }
             (Err(e), _) => {
                 stepping = true;
 
                 match e {
-                    CpuError::MemoryLoadFail(addr) => println!("[ERR] Accessed uninitialized memory at {addr:#04X}")
+                    CpuError::MemoryLoadFail(addr) => println!("[ERR] Accessed uninitialized memory at {addr:#04X}"),
+                    CpuError::InvalidAccess(access) => println!("[ERR] {access}"),
                 }
             }
         }
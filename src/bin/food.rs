@@ -7,8 +7,8 @@ fn main() {
     let data = std::fs::read(filename).unwrap();
     let mut serial_buf = String::new();
 
-    let rom_size = RomSize::from_byte(data[0x0148]);
-    let ram_size = RamSize::from_byte(data[0x0149]);
+    let rom_size = RomSize::from_byte(data[0x0148]).unwrap();
+    let ram_size = RamSize::from_byte(data[0x0149]).unwrap();
 
     let mbc = match data[0x0147] {
         0x00 => MbcSelector::NoMbc,
@@ -16,7 +16,7 @@ fn main() {
         _ => panic!("Unsupported MBC"),
     };
 
-    let mut emu = Gbc::new(mbc, None, None, false, true);
+    let mut emu = Gbc::new(mbc, false, true);
     emu.load_rom(&data);
 
     let mut file = std::fs::OpenOptions::new()
@@ -35,7 +35,7 @@ fn main() {
                         println!("Serial buffer: {}", serial_buf);
                         break;
                     }
-                    CpuStatus::Run => {
+                    CpuStatus::Run(_) => {
                         let pc = emu.cpu.regs.pc;
                         let pcmem = emu.cpu.memory.load_block(pc, pc + 3);
                         let out = format!("A: {:02X} F: {:02X} B: {:02X} C: {:02X} D: {:02X} E: {:02X} H: {:02X} L: {:02X} SP: {:04X} PC: 00:{:04X} ({:02X} {:02X} {:02X} {:02X})\n",
@@ -49,12 +49,13 @@ fn main() {
                             serial_buf += &format!("{}", serial as char);
                         }
                     }
-                    CpuStatus::Break => {}
+                    CpuStatus::Break(_, _) | CpuStatus::Halt | CpuStatus::BlockedByDma => {}
                 }
             }
             (Err(e), _) => {
                 match e {
-                    CpuError::MemoryLoadFail(addr) => println!("[ERR] Accessed uninitialized memory at {addr:#04X}")
+                    CpuError::MemoryLoadFail(addr) => println!("[ERR] Accessed uninitialized memory at {addr:#04X}"),
+                    CpuError::InvalidAccess(access) => println!("[ERR] {access}"),
                 }
             }
         }
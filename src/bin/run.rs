@@ -20,8 +20,8 @@ fn main() {
     let data = std::fs::read(filename).unwrap();
     let mut serial_buf = String::new();
 
-    let rom_size = RomSize::from_byte(data[0x0148]);
-    let ram_size = RamSize::from_byte(data[0x0149]);
+    let rom_size = RomSize::from_byte(data[0x0148]).unwrap();
+    let ram_size = RamSize::from_byte(data[0x0149]).unwrap();
 
     let mbc = match data[MBC_ADDR] {
         0x00 => MbcSelector::NoMbc,
@@ -48,7 +48,7 @@ fn main() {
                         println!("Serial buffer: {serial_buf}");
                         break;
                     }
-                    CpuStatus::Run => {
+                    CpuStatus::Run(_) => {
                         let serial = emu.read_serial();
     
                         if serial != 0xFF {
@@ -60,12 +60,13 @@ fn main() {
                             }
                         }
                     }
-                    CpuStatus::Break => {}
+                    CpuStatus::Break(_, _) | CpuStatus::Halt | CpuStatus::BlockedByDma => {}
                 }
             }
             (Err(e), _) => {
                 match e {
-                    CpuError::MemoryLoadFail(addr) => println!("[ERR] Accessed uninitialized memory at {addr:#04X}")
+                    CpuError::MemoryLoadFail(addr) => println!("[ERR] Accessed uninitialized memory at {addr:#04X}"),
+                    CpuError::InvalidAccess(access) => println!("[ERR] {access}"),
                 }
             }
         }
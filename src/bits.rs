@@ -0,0 +1,38 @@
+//! Helpers for single-bit tests, so they all read the same way
+//!
+//! Comparing against a mask by hand (`reg & 1 << 4 == 1 << 4`) works for one bit, but it's easy to get the mask and the
+//! comparison out of sync, or write `& 1 == 1` when a different bit was meant. Use these instead
+
+/// Mask with only bit `n` set
+pub(crate) const fn bit(n: u8) -> u8 {
+    1 << n
+}
+
+/// Checks if bit `n` of `value` is 1
+pub(crate) const fn bit_set(value: u8, n: u8) -> bool {
+    value & bit(n) != 0
+}
+
+/// Checks if bit `n` of a 16 bit `value` is 1, for things like the internal divider
+pub(crate) const fn word_bit_set(value: u16, n: u8) -> bool {
+    value & (1 << n) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bit, bit_set, word_bit_set};
+
+    #[test]
+    fn bits() {
+        assert_eq!(bit(0), 0x01);
+        assert_eq!(bit(7), 0x80);
+
+        assert!(bit_set(0b0001_0000, 4));
+        assert!(!bit_set(0b1110_1111, 4));
+        // bit 0 being set says nothing about the others
+        assert!(!bit_set(0b0000_0001, 1));
+
+        assert!(word_bit_set(0x0200, 9));
+        assert!(!word_bit_set(0x01FF, 9));
+    }
+}
@@ -2,24 +2,39 @@ use core::fmt;
 use std::{fmt::Display, time::Instant};
 
 use crate::{
-    memory::{self, Mmu},
-    ppu::{Lcdc, Ppu}, PpuStatus,
+    bits::{bit_set, word_bit_set},
+    memory::{self, AccessKind, Interrupt, InterruptFlags, InvalidAccess, Mmu, ResetKind},
+    ppu::{Lcdc, Ppu, PpuMode}, PpuStatus,
 };
 
 use self::instructions::{
-    ArithmeticTarget, StackTarget,
-    WordArithmeticTarget,
+    ArithmeticTarget, JumpTest, StackTarget,
 };
 
+pub use self::cycles::{branch_cycles, opcode_cycles};
+pub use self::disasm::{disassemble, disassemble_range};
 pub use self::instructions::Instruction;
 pub use self::registers::{CpuReg, CpuFlag, Registers};
 
 
+mod cycles;
+mod disasm;
 mod instructions;
 mod registers;
 
 const EXT_PREFIX: u8 = 0xCB;
 
+/// Clock rate in normal speed, in T-cycles per second
+///
+/// The PPU always runs at this rate, even in CGB double speed, where everything else is twice as fast
+pub const CLOCK_HZ: u32 = 4_194_304;
+
+// most ticks to skip in one step while idle, so the frontend still gets control back regularly
+const IDLE_SKIP_LIMIT: usize = 1024;
+
+// what LY reads as in doctor mode, the first line of vblank
+const DOCTOR_LY: u8 = 0x90;
+
 #[derive(Clone, Copy, Debug)]
 pub struct IoRegs {
     pub lcdc: u8,
@@ -33,9 +48,12 @@ pub enum CpuEvent {
     Pc(u16),
     MemoryRead(u16),
     MemoryWrite(u16),
-    Interrupt(u8),
+    Interrupt(Interrupt),
     Flag(CpuFlag),
     Reg(CpuReg),
+    /// Pushed after every instruction with the new count from `Cpu::instructions`, so a breakpoint can stop at a
+    /// reference trace's line number
+    InstructionCount(u64),
 }
 
 impl PartialEq for CpuEvent {
@@ -63,12 +81,15 @@ impl PartialEq for CpuEvent {
             (Reg(lhs), Reg(rhs)) => {
                 lhs == rhs
             },
+            (InstructionCount(lhs), InstructionCount(rhs)) => {
+                lhs == rhs
+            },
             (_, _) => false,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct EnabledBreakpoints {
     pub opcode: bool,
     pub prefix_code: bool,
@@ -79,6 +100,7 @@ pub struct EnabledBreakpoints {
     pub interrupt: bool,
     pub flag_change: bool,
     pub reg_change: bool,
+    pub instruction_count: bool,
 }
 
 impl EnabledBreakpoints {
@@ -93,6 +115,7 @@ impl EnabledBreakpoints {
             interrupt: true,
             flag_change: true,
             reg_change: true,
+            instruction_count: true,
         }
     }
     
@@ -108,11 +131,12 @@ impl EnabledBreakpoints {
             Interrupt(_) => self.interrupt,
             Flag(_) => self.flag_change,
             Reg(_) => self.reg_change,
+            InstructionCount(_) => self.instruction_count,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Breakpoints {
     pub breakpoints: Vec<CpuEvent>,
     pub enabled_kinds: EnabledBreakpoints,
@@ -168,12 +192,31 @@ pub enum CpuStatus {
     BlockedByDma,
 }
 
+/// Snapshot of what decides whether the CPU is running and taking interrupts, see `Cpu::state`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CpuState {
+    /// Interrupt master enable, the same as `Registers::ime`
+    pub ime: bool,
+    /// EI just ran, and IME gets set after the next instruction
+    pub ei_pending: bool,
+    /// Waiting in HALT until an interrupt is requested
+    pub halted: bool,
+    /// Stopped by STOP
+    pub stopped: bool,
+    /// Running at CGB double speed
+    pub double_speed: bool,
+    /// Interrupts that are both enabled and requested, which wake the CPU from HALT and get serviced once IME is set
+    pub pending_interrupts: InterruptFlags,
+}
+
+#[derive(Clone, Copy)]
 pub struct Dma {
     pub cycles_remaining: u8,
     pub source: u16,
     pub oam: bool,
 }
 
+#[derive(Clone)]
 pub struct Cpu {
     pub regs: Registers,
     pub memory: Box<Mmu>,
@@ -183,16 +226,35 @@ pub struct Cpu {
     pub debug: bool,
     pub allow_uninit: bool,
     pub breakpoint_controls: Breakpoints,
+    /// Repeat `JR $-2` loops until an interrupt inside one step, instead of returning from `step` every iteration
+    ///
+    /// This only skips fetching and decoding the jump each time. Every M-cycle is still ticked,
+    /// so it's not a fast-forward to the next interrupt
+    pub skip_idle: bool,
+    /// Block the CPU from VRAM during pixel transfer and from OAM during OAM scan and pixel transfer, like the real PPU does
+    ///
+    /// Blocked reads give `0xFF` and blocked writes are dropped. Debuggers can turn this off to see memory while the PPU is using it
+    pub restrict_ppu_memory: bool,
+    /// Make LY always read 0x90, the way Gameboy Doctor logs are made
+    ///
+    /// Only what the CPU reads changes, the PPU still counts lines and draws as normal
+    pub doctor_mode: bool,
     ei_called: u8,
     div: u16,
     div_last: bool,
     tima_overflow: bool,
     stop: bool,
     tick: usize,
+    /// Instructions run since the CPU was created or reset
+    instructions: u64,
     dma: Option<Dma>,
     /// Breakpoints are put here during execution
     /// When the instruction is finished, the system goes through this list and checks if any breakpoints were hit
     pending_breakpoints: Vec<CpuEvent>,
+    /// Interrupt that was serviced at the end of the last step, if any
+    serviced: Option<Interrupt>,
+    /// Invalid access caught in strict mode with no callback to report it to, returned once the instruction finishes
+    invalid_access: Option<InvalidAccess>,
 }
 
 impl Cpu {
@@ -206,14 +268,20 @@ impl Cpu {
             debug,
             allow_uninit,
             breakpoint_controls: Breakpoints::new(),
+            skip_idle: false,
+            restrict_ppu_memory: true,
+            doctor_mode: false,
             ei_called: 0,
             div: 0,
             div_last: false,
             tima_overflow: false,
             stop: false,
             tick: 0,
+            instructions: 0,
             dma: None,
             pending_breakpoints: Vec::new(),
+            serviced: None,
+            invalid_access: None,
         }
     }
 
@@ -221,19 +289,34 @@ impl Cpu {
         self.memory.load_rom(data);
     }
 
+    /// Puts the CPU, PPU and memory back how they are at power on, see `ResetKind` for what's kept
+    ///
+    /// Settings like `debug`, `skip_idle` and breakpoints stay as they are, and the tick count starts over from 0
+    pub(crate) fn reset(&mut self, kind: ResetKind) {
+        self.memory.reset(kind);
+        self.regs = Registers::new();
+        self.ppu = Ppu::new();
+        self.double_speed = false;
+        self.halted = false;
+        self.ei_called = 0;
+        self.div = 0;
+        self.div_last = false;
+        self.tima_overflow = false;
+        self.stop = false;
+        self.tick = 0;
+        self.instructions = 0;
+        self.dma = None;
+        self.pending_breakpoints.clear();
+        self.serviced = None;
+        self.invalid_access = None;
+    }
+
     /// Ticks the system by 1 M-cycle, stepping the PPU and DIV
     pub(crate) fn tick(&mut self) {
         // there is a single tick delay between TIMA overflowing and IF.2 being set
         self.tick += 1;
         if self.tima_overflow {
-            let mut if_reg = self
-                .memory
-                .load(memory::IF)
-                .expect("Error reading IF register: Uninitialized");
-
-            if_reg |= 1 << 2;
-
-            self.memory.set(memory::IF, if_reg);
+            self.memory.request_interrupt(Interrupt::Timer);
             self.tima_overflow = false;
         }
 
@@ -248,17 +331,20 @@ impl Cpu {
             }
         }
 
-        self.ppu.tick(&self.memory);
+        self.ppu.tick_at(&self.memory, self.clock_hz());
         if self.ppu.status == PpuStatus::EnterVBlank {
-            let mut if_reg = self
-                .memory
-                .load(memory::IF)
-                .expect("Error reading IF register: Uninitialized");
+            self.memory.request_interrupt(Interrupt::VBlank);
 
-            if_reg |= 1 << 0;
-            self.memory.set(memory::IF, if_reg);
+            // gameshark codes are applied once per frame
+            self.memory.apply_cheats();
         }
 
+        let lyc = self.memory.load(memory::LYC).unwrap_or(0);
+        if self.ppu.update_stat_line(lyc) {
+            self.memory.request_interrupt(Interrupt::Stat);
+        }
+
+        self.memory.tick_serial();
         self.tick_div();
     }
 
@@ -267,6 +353,14 @@ impl Cpu {
         self.div = self.div.wrapping_add(4);
         self.memory.set(memory::DIV, (self.div >> 8) as u8);
 
+        self.update_timer();
+    }
+
+    /// Increments TIMA if the divider bit selected by TAC just fell, or the timer was just disabled while it was high
+    ///
+    /// This is checked every time the divider changes, including when it's reset by writing to DIV, so a reset while the
+    /// bit is high ticks TIMA right away
+    fn update_timer(&mut self) {
         let tac = self
             .memory
             .load(memory::TAC)
@@ -274,15 +368,14 @@ impl Cpu {
 
         // numbers from here https://pixelbits.16-b.it/GBEDG/timers/#timer-operation
         let div_bit = match tac & 0b11 {
-            0b00 => self.div >> 9 & 1,
-            0b01 => self.div >> 3 & 1,
-            0b10 => self.div >> 5 & 1,
-            0b11 => self.div >> 7 & 1,
+            0b00 => 9,
+            0b01 => 3,
+            0b10 => 5,
+            0b11 => 7,
             _ => unreachable!(),
-        } as u8;
+        };
 
-        let tac_bit = tac >> 2 & 1;
-        let div_and = div_bit & tac_bit == 1;
+        let div_and = word_bit_set(self.div, div_bit) && bit_set(tac, 2);
 
         if self.div_last == true && div_and == false {
             let (tima, overflowed) = self
@@ -307,9 +400,33 @@ impl Cpu {
     /// - `Ok(true)` if operation should continue
     /// - `Ok(false)` if STOP was called and execution should stop
     /// - `Err(addr)` if there was an attempt to read from uninitialized memory
+    // `Gbc` always has a deadline for idle loops, so this is only for tests running the cpu on its own
+    #[cfg(test)]
     pub(crate) fn step(&mut self) -> Result<CpuStatus, CpuError> {
+        self.step_until(usize::MAX)
+    }
+
+    /// Same as `step`, but an idle loop run by `skip_idle` stops repeating once the tick count reaches `deadline`
+    ///
+    /// Like any other instruction, the last iteration can finish a couple of M-cycles past it
+    pub(crate) fn step_until(&mut self, deadline: usize) -> Result<CpuStatus, CpuError> {
         self.dbg("Loading instruction\n");
 
+        let start_pc = self.regs.pc;
+        let start_tick = self.tick;
+        self.serviced = None;
+
+        if self.stop {
+            // a selected button going low starts the clock back up
+            if self.memory.joypad.read() & 0x0F == 0x0F {
+                // nothing runs while the clock is stopped, but the count still goes up so frames keep ending
+                self.tick += 1;
+                return Ok(CpuStatus::Stop);
+            }
+
+            self.stop = false;
+        }
+
         if self.halted {
             let Some(ie) = self
                 .memory
@@ -337,7 +454,7 @@ impl Cpu {
         //     return Ok(CpuStatus::BlockedByDma)
         // }
 
-        let instruction_byte = self.mem_load(self.regs.pc)?;
+        let instruction_byte = self.mem_load_as(self.regs.pc, AccessKind::Execute)?;
         let (instruction_byte, prefixed) = if instruction_byte == EXT_PREFIX {
             (self.load_d8()?, true)
         } else {
@@ -360,13 +477,16 @@ impl Cpu {
         self.push_event(CpuEvent::Instruction(instruction));
         let next_pc = self.execute(instruction)?;
 
+        self.regs.pc = next_pc;
+        self.push_event(CpuEvent::Pc(self.regs.pc));
+
+        self.instructions += 1;
+        self.push_event(CpuEvent::InstructionCount(self.instructions));
+
         if self.stop {
             return Ok(CpuStatus::Stop);
         }
 
-        self.regs.pc = next_pc;
-        self.push_event(CpuEvent::Pc(self.regs.pc));
-
         // the effects of ei are delayed by one instruction
         if self.ei_called == 1 {
             self.ei_called = 2;
@@ -377,8 +497,21 @@ impl Cpu {
 
         self.handle_interrupts();
 
+        // a jump to itself can only be left through an interrupt
+        let idle = self.regs.ime
+            && self.regs.pc == start_pc
+            && matches!(instruction, Instruction::JR(JumpTest::Always));
+
+        if self.skip_idle && idle {
+            self.repeat_idle_loop(self.tick - start_tick, deadline);
+        }
+
         let breakpoints = self.pending_breakpoints.clone();
         self.pending_breakpoints = Vec::with_capacity(8);
+
+        if let Some(access) = self.invalid_access.take() {
+            return Err(CpuError::InvalidAccess(access));
+        }
         
         if let Some(breakpoint) = breakpoints.iter().find_map(|&b| self.breakpoint_controls.check(b)) {
             Ok(CpuStatus::Break(instruction, breakpoint))
@@ -387,52 +520,71 @@ impl Cpu {
         }
     }
 
-    // TODO: clean this up (enum probably)
-    fn handle_interrupts(&mut self) {
-        if self.regs.ime {
-            let ie = self
-                .mem_load(memory::IE)
-                .expect("Error reading IE register: Uninitialized");
-            let if_reg = self
-                .mem_load(memory::IF)
-                .expect("Error reading IF register: Uninitialized");
-
-            if ie & if_reg == 0 {
-                return;
+    /// Repeats an idle loop without decoding it again until an interrupt is requested, `cost` ticks per iteration
+    ///
+    /// The timer and PPU still get ticked every M-cycle, so it only skips the fetch and decode. It stops at `deadline`,
+    /// so it doesn't run past the end of a frame or a `Gbc::run_for`.
+    /// This gives the same timing and instruction count as stepping through every iteration, but no events are pushed
+    /// for the skipped iterations, except for an instruction count that has a breakpoint on it, which stops the skipping
+    fn repeat_idle_loop(&mut self, cost: usize, deadline: usize) {
+        let limit = deadline.min(self.tick + IDLE_SKIP_LIMIT);
+        let pc = self.regs.pc;
+
+        while self.regs.pc == pc && self.tick < limit {
+            for _ in 0..cost {
+                self.tick();
             }
 
-            let mut same = [false; 5];
+            self.instructions += 1;
+            let count = CpuEvent::InstructionCount(self.instructions);
 
-            for i in 0..5 {
-                let ie_bit = ie & (1 << i);
+            self.handle_interrupts();
 
-                same[i] = ie_bit > 0 && ie_bit == if_reg & (1 << i);
+            if self.breakpoint_controls.check(count).is_some() {
+                self.pending_breakpoints.push(count);
+                break;
             }
+        }
+    }
+
+    /// Services the highest priority pending interrupt, if interrupts are enabled
+    fn handle_interrupts(&mut self) {
+        if !self.regs.ime {
+            return;
+        }
 
-            for i in 0..5 {
-                if same[i] {
-                    // TODO: Push interrupt events when i make this use an enum
-                    // self.push_event(CpuEvent::Interrupt(i));
+        // checking for interrupts happens alongside the last instruction, so it doesn't take any cycles
+        let ie = self
+            .memory
+            .load(memory::IE)
+            .expect("Error reading IE register: Uninitialized");
+        let if_reg = self
+            .memory
+            .load(memory::IF)
+            .expect("Error reading IF register: Uninitialized");
 
-                    // acknowledge the interrupt and prevent further interrupts
-                    self.mem_set(memory::IF, if_reg - (1 << i));
-                    self.regs.ime = false;
+        let Some(interrupt) = InterruptFlags::from_bits(ie & if_reg).highest_priority() else {
+            return;
+        };
 
-                    // 2 wait cycles are executed
-                    self.tick();
-                    self.tick();
+        self.push_event(CpuEvent::Interrupt(interrupt));
+        self.serviced = Some(interrupt);
 
-                    // pc is pushed to the stack
-                    self.push_word(self.regs.pc);
+        // acknowledge the interrupt and prevent further interrupts
+        self.memory.set(memory::IF, if_reg & !interrupt.bit());
+        self.regs.ime = false;
 
-                    // the 16 bit ISR address is loaded into pc, taking another cycle
-                    self.regs.pc = 0x40 + 0x08 * i as u16;
+        // 2 wait cycles are executed
+        self.tick();
+        self.tick();
 
-                    self.tick();
-                    return;
-                }
-            }
-        }
+        // pc is pushed to the stack
+        self.push_word(self.regs.pc);
+
+        // the 16 bit ISR address is loaded into pc, taking another cycle
+        self.regs.pc = interrupt.vector();
+
+        self.tick();
     }
 
     /// Executes a single instruction
@@ -494,8 +646,8 @@ impl Cpu {
                         self.load_d8()?
                     }
                 };
-                
-                self.sub(value);
+
+                self.cp(value);
             }
             Instruction::INC(target)
             | Instruction::DEC(target)
@@ -557,13 +709,7 @@ impl Cpu {
             Instruction::RLCA => self.rlca(),
             Instruction::CPL => self.cpl(),
             Instruction::ADDHL(target) => {
-                let value = match target {
-                    WordArithmeticTarget::BC => self.regs.get_bc(),
-                    WordArithmeticTarget::DE => self.regs.get_de(),
-                    WordArithmeticTarget::HL => self.regs.get_hl(),
-                    WordArithmeticTarget::SP => self.regs.sp,
-                };
-
+                let value = self.regs.read16(target);
                 let new_value = self.add_hl(value);
                 self.regs.set_hl(new_value);
             }
@@ -571,19 +717,24 @@ impl Cpu {
                 let value = self.load_s8()?;
                 self.regs.sp = self.add_sp(value);
                 size = 2;
+
+                // 2 internal cycles, one for each byte of sp
+                self.tick();
+                self.tick();
+            }
+            // 16 bit inc and dec don't touch the flags, and take an extra cycle to carry into the high byte
+            // on a dmg these corrupt OAM when the register points into it during mode 2, but this is a cgb, where that got fixed.
+            // if a dmg mode ever gets added, the OAM bug goes here and on the other 16 bit pointer changes (LD A,(HL+), PUSH, POP, ...)
+            Instruction::INCW(target) => {
+                let value = self.regs.read16(target);
+                self.regs.write16(target, value.wrapping_add(1));
+                self.tick();
+            }
+            Instruction::DECW(target) => {
+                let value = self.regs.read16(target);
+                self.regs.write16(target, value.wrapping_sub(1));
+                self.tick();
             }
-            Instruction::INCW(target) => match target {
-                WordArithmeticTarget::BC => self.regs.set_bc(self.regs.get_bc().wrapping_add(1)),
-                WordArithmeticTarget::DE => self.regs.set_de(self.regs.get_de().wrapping_add(1)),
-                WordArithmeticTarget::HL => self.regs.set_hl(self.regs.get_hl().wrapping_add(1)),
-                WordArithmeticTarget::SP => self.regs.sp = self.regs.sp.wrapping_add(1),
-            },
-            Instruction::DECW(target) => match target {
-                WordArithmeticTarget::BC => self.regs.set_bc(self.regs.get_bc().wrapping_sub(1)),
-                WordArithmeticTarget::DE => self.regs.set_de(self.regs.get_de().wrapping_sub(1)),
-                WordArithmeticTarget::HL => self.regs.set_hl(self.regs.get_hl().wrapping_sub(1)),
-                WordArithmeticTarget::SP => self.regs.sp = self.regs.sp.wrapping_sub(1),
-            },
             Instruction::BIT(target, bit) => {
                 let byte = match target {
                     ArithmeticTarget::A => self.regs.a,
@@ -649,6 +800,8 @@ impl Cpu {
                     StackTarget::AF => self.regs.get_af(),
                 };
 
+                // internal delay before pushing
+                self.tick();
                 self.push_word(value)
             }
             Instruction::POP(target) => {
@@ -662,10 +815,7 @@ impl Cpu {
                 }
             }
             Instruction::DAA => self.regs.a = self.daa(),
-            Instruction::STOP => {
-                self.stop = true;
-                return Ok(self.regs.pc);
-            }
+            Instruction::STOP => return Ok(self.stop()),
             Instruction::HALT => self.halted = true,
             Instruction::NOP => {}
             Instruction::RET(test) => return self.ret(test),
@@ -716,6 +866,10 @@ impl Cpu {
             self.push_event(CpuEvent::Reg(CpuReg::D));
         }
 
+        if self.regs.e != old_regs.e {
+            self.push_event(CpuEvent::Reg(CpuReg::E));
+        }
+
         if self.regs.h != old_regs.h {
             self.push_event(CpuEvent::Reg(CpuReg::H));
         }
@@ -731,18 +885,33 @@ impl Cpu {
     /// - `Ok(value)` if a byte was read successfully
     /// - `Err(addr)` if the byte at the address was uninitialized, and `Self::allow_uninit` is false
     fn mem_load(&mut self, addr: u16) -> Result<u8, CpuError> {
+        self.mem_load_as(addr, AccessKind::Read)
+    }
+
+    /// Same as `mem_load`, but strict mode checks it as a `kind` access instead of a read
+    ///
+    /// Opcode fetches go through here as `AccessKind::Execute`, so they're only reported once
+    fn mem_load_as(&mut self, addr: u16, kind: AccessKind) -> Result<u8, CpuError> {
         self.dbg(format!("[LOAD] {:#06X}", addr));
         self.push_event(CpuEvent::MemoryRead(addr));
+        self.check_access(addr, kind);
         self.tick();
 
         // if self.oam_dma_running() && addr < memory::HRAM {
         //     return Ok(0);
         // }
 
+        if self.ppu_locked(addr) {
+            return Ok(0xFF);
+        }
+
         match addr {
-            memory::LY => Ok(self.ppu.coords.y),
+            memory::LY if self.doctor_mode => Ok(DOCTOR_LY),
+            memory::LY => Ok(self.ppu.ly()),
+            memory::STAT => Ok(self.ppu.read_stat(self.memory.load(memory::LYC).unwrap_or(0))),
             _ => {
                 if let Some(out) = self.memory.load(addr) {
+                    let out = out | memory::unused_bits(addr);
                     self.dbg(" -> {out:#04X}\n");
         
                     Ok(out)
@@ -760,21 +929,42 @@ impl Cpu {
 
     }
 
+    /// Reports the access if it's one strict mode catches, see `Mmu::set_strict`
+    ///
+    /// Only the first one without a callback is kept, since that's the one that stops the CPU
+    fn check_access(&mut self, addr: u16, kind: AccessKind) {
+        if !self.memory.is_strict() || !self.memory.is_invalid_access(addr, kind) {
+            return;
+        }
+
+        let access = InvalidAccess { addr, kind, pc: self.regs.pc };
+
+        if let Err(access) = self.memory.report_invalid_access(access) {
+            self.invalid_access.get_or_insert(access);
+        }
+    }
+
     /// Sets a byte in memory and ticks an M-cycle
     fn mem_set(&mut self, addr: u16, value: u8) {
         self.dbg("[SET] {addr:#06X} <- {value:#04X}");
         self.push_event(CpuEvent::MemoryWrite(addr));
+        self.check_access(addr, AccessKind::Write);
         self.tick();
 
         // if self.oam_dma_running() && addr < memory::HRAM {
         //     return;
         // }
 
+        if self.ppu_locked(addr) {
+            return;
+        }
+
         match addr {
-            memory::DIV => {
-                self.div = 0;
-                self.memory.set(addr, 0);
-                return;
+            memory::DIV => return self.reset_div(),
+            memory::KEY1 => {
+                // only the switch can be armed, bit 7 is the current speed
+                let speed = if self.double_speed { 0x80 } else { 0x00 };
+                return self.memory.set(addr, speed | (value & 1));
             }
             memory::LCDC => {
                 self.ppu.set_lcdc(value);
@@ -785,6 +975,12 @@ impl Cpu {
             memory::BGP => {
                 self.ppu.set_palette(value);
             }
+            memory::OBP0 => {
+                self.ppu.set_obj_palette(0, value);
+            }
+            memory::OBP1 => {
+                self.ppu.set_obj_palette(1, value);
+            }
             memory::DMA => {
                 if self.dma.is_none() {
                     self.dma = Some(Dma {
@@ -800,6 +996,42 @@ impl Cpu {
         self.memory.set(addr, value);
     }
 
+    /// Gets the interrupt that was serviced at the end of the last step, if one was
+    pub(crate) fn serviced_interrupt(&self) -> Option<Interrupt> {
+        self.serviced
+    }
+
+    /// Clock rate the CPU is currently running at in T-cycles per second, `CLOCK_HZ` or twice that in double speed
+    pub fn clock_hz(&self) -> u32 {
+        if self.double_speed {
+            CLOCK_HZ * 2
+        } else {
+            CLOCK_HZ
+        }
+    }
+
+    /// Sets the internal divider back to 0, which also clears DIV
+    ///
+    /// If the bit TIMA is watching was high, clearing it counts as a falling edge, so TIMA goes up by 1
+    pub(crate) fn reset_div(&mut self) {
+        self.div = 0;
+        self.memory.set(memory::DIV, 0);
+        self.update_timer();
+    }
+
+    /// Checks if the PPU is using the memory at `addr`, which keeps the CPU out of it
+    fn ppu_locked(&self, addr: u16) -> bool {
+        if !self.restrict_ppu_memory || !self.ppu.lcdc.lcd_enable {
+            return false;
+        }
+
+        match addr {
+            0x8000..=0x9FFF => self.ppu.mode() == PpuMode::Transfer,
+            memory::OAM..=memory::OAM_END => matches!(self.ppu.mode(), PpuMode::OamScan | PpuMode::Transfer),
+            _ => false,
+        }
+    }
+
     fn load_from_hl(&mut self) -> Result<u8, CpuError> {
         self.mem_load(self.regs.get_hl())
     }
@@ -872,6 +1104,60 @@ impl Cpu {
         self.dma.as_ref().map_or(false, |dma| dma.oam)
     }
 
+    /// Gets the interrupt and sleep state, for debuggers to show why an interrupt is or isn't being taken
+    pub fn state(&self) -> CpuState {
+        CpuState {
+            ime: self.regs.ime,
+            ei_pending: self.ei_called > 0,
+            halted: self.halted,
+            stopped: self.stop,
+            double_speed: self.double_speed,
+            pending_interrupts: self.memory.pending_interrupts(),
+        }
+    }
+
+    /// Number of M-cycles that have passed since the CPU was created
+    pub fn ticks(&self) -> usize {
+        self.tick
+    }
+
+    /// Number of instructions that have run since the CPU was created or reset
+    ///
+    /// Only instructions count, not M-cycles spent halted or stopped, or servicing interrupts. Skipping idle loops
+    /// with `skip_idle` counts the same as running them
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Formats the registers and the 4 bytes at PC the same way as Gameboy Doctor logs, without a newline
+    ///
+    /// Reading memory here doesn't take any cycles, and uninitialized bytes show up as `00`
+    pub fn trace_line(&self) -> String {
+        let regs = &self.regs;
+        let pc = regs.pc;
+        let pcmem: Vec<u8> = (0..4)
+            .map(|i| self.memory.load(pc.wrapping_add(i)).unwrap_or(0))
+            .collect();
+
+        format!(
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            regs.a,
+            regs.f.as_byte(),
+            regs.b,
+            regs.c,
+            regs.d,
+            regs.e,
+            regs.h,
+            regs.l,
+            regs.sp,
+            pc,
+            pcmem[0],
+            pcmem[1],
+            pcmem[2],
+            pcmem[3],
+        )
+    }
+
     pub fn dump_io_regs(&self) -> IoRegs {
         IoRegs {
             lcdc: self.memory.load(memory::LCDC).unwrap_or(0),
@@ -896,10 +1182,343 @@ impl Cpu {
 #[derive(Clone, Copy, Debug)]
 pub enum CpuError {
     MemoryLoadFail(u16),
+    /// Strict mode caught an access with no diagnostics callback to report it to, see `Mmu::set_strict`
+    InvalidAccess(InvalidAccess),
 }
 
 impl fmt::Display for CpuError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Emulated CPU encountered an error: {:#?}", self)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{
+        joypad::Button,
+        memory::{self, mbc::MbcSelector, AccessKind, InvalidAccess, Mmu},
+        ppu::{Ppu, PpuMode},
+    };
+
+    use super::{Cpu, CpuError, CpuEvent, CpuStatus};
+
+    fn init() -> Cpu {
+        let mut cpu = Cpu::new(Mmu::new(MbcSelector::NoMbc), Ppu::new(), false, true);
+
+        // jr -2 with the timer interrupt enabled, ticking every 16 cycles
+        cpu.memory.splice(0x100, &[0x18, 0xFE]);
+        cpu.memory.set(memory::IF, 0);
+        cpu.memory.set(memory::IE, 1 << 2);
+        cpu.memory.set(memory::TIMA, 0xF0);
+        cpu.memory.set(memory::TAC, 0b101);
+        cpu.regs.ime = true;
+
+        cpu
+    }
+
+    /// Steps until the timer interrupt is serviced, returning the number of steps it took
+    fn run_to_interrupt(cpu: &mut Cpu) -> usize {
+        let mut steps = 0;
+
+        while cpu.regs.pc != 0x50 {
+            cpu.step().unwrap();
+            steps += 1;
+        }
+
+        steps
+    }
+
+    #[test]
+    fn skip_idle() {
+        let mut stepped = init();
+        let mut skipped = init();
+        skipped.skip_idle = true;
+
+        let steps = run_to_interrupt(&mut stepped);
+        let skipped_steps = run_to_interrupt(&mut skipped);
+
+        // the interrupt happens at the same time, in fewer steps
+        assert_eq!(stepped.tick, skipped.tick);
+        assert_eq!(stepped.instructions(), skipped.instructions());
+        assert!(skipped_steps < steps);
+    }
+
+    #[test]
+    fn instruction_count_breakpoint() {
+        let mut cpu = init();
+        cpu.skip_idle = true;
+        cpu.breakpoint_controls.set(CpuEvent::InstructionCount(50));
+
+        // skipping the idle loop still stops at the right instruction
+        loop {
+            if let CpuStatus::Break(_, event) = cpu.step().unwrap() {
+                assert_eq!(event, CpuEvent::InstructionCount(50));
+                break;
+            }
+        }
+
+        assert_eq!(cpu.instructions(), 50);
+    }
+
+    #[test]
+    fn trace_line() {
+        let mut cpu = init();
+        cpu.regs.a = 0x12;
+        cpu.regs.f.set_bits(0b1011_0000);
+
+        assert_eq!(
+            cpu.trace_line(),
+            "A:12 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:18,FE,00,00"
+        );
+    }
+
+    #[test]
+    fn state() {
+        let mut cpu = init();
+        cpu.regs.ime = false;
+        // ei, halt
+        cpu.memory.splice(0x100, &[0xFB, 0x76]);
+
+        cpu.step().unwrap();
+        let state = cpu.state();
+        assert!(state.ei_pending && !state.ime && !state.halted);
+
+        cpu.step().unwrap();
+        let state = cpu.state();
+        assert!(!state.ei_pending && state.ime && state.halted);
+        assert_eq!(state.pending_interrupts.bits(), 0);
+
+        cpu.memory.set(memory::IF, 1 << 2);
+        assert_eq!(cpu.state().pending_interrupts.bits(), 1 << 2);
+        assert!(!cpu.state().stopped);
+    }
+
+    #[test]
+    fn stop() {
+        let mut cpu = init();
+        cpu.memory.set(memory::IE, 0);
+        cpu.memory.set(memory::TAC, 0);
+        // nop, stop, then a nop after the skipped byte
+        cpu.memory.splice(0x100, &[0x00, 0x10, 0xFF, 0x00]);
+
+        for _ in 0..200 {
+            cpu.tick();
+        }
+
+        assert_ne!(cpu.memory.load(memory::DIV), Some(0));
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.mem_load(memory::DIV).unwrap(), 0);
+        assert_eq!(cpu.regs.pc, 0x103);
+        assert!(cpu.state().stopped);
+
+        // nothing runs until a selected button is pressed
+        cpu.memory.joypad.write(0x20);
+        let tick = cpu.tick;
+        assert!(matches!(cpu.step(), Ok(CpuStatus::Stop)));
+        assert_eq!((cpu.tick - tick, cpu.regs.pc), (1, 0x103));
+
+        cpu.memory.set_button(Button::Right, true);
+        cpu.step().unwrap();
+        assert!(!cpu.state().stopped);
+        assert_eq!(cpu.regs.pc, 0x104);
+
+        // with the button still held, the cpu halts instead and div keeps going
+        cpu.memory.splice(0x104, &[0x10, 0x00]);
+        cpu.div = 0x1234;
+        cpu.step().unwrap();
+        assert!(cpu.state().halted && !cpu.state().stopped);
+        assert_ne!(cpu.div, 0);
+        cpu.memory.set_button(Button::Right, false);
+
+        // a pending interrupt makes it 1 byte long
+        cpu.halted = false;
+        cpu.regs.pc = 0x104;
+        cpu.memory.set(memory::IE, 1 << 2);
+        cpu.memory.set(memory::IF, 1 << 2);
+        cpu.regs.ime = false;
+        cpu.step().unwrap();
+        assert_eq!(cpu.regs.pc, 0x105);
+        assert!(cpu.state().stopped);
+    }
+
+    #[test]
+    fn speed_switch() {
+        let mut cpu = init();
+        cpu.memory.set(memory::IE, 0);
+        cpu.ppu.cgb = true;
+        cpu.memory.splice(0x100, &[0x10, 0x00]);
+
+        assert_eq!(cpu.mem_load(memory::KEY1).unwrap(), 0x7E);
+        cpu.mem_set(memory::KEY1, 0x81);
+        assert_eq!(cpu.mem_load(memory::KEY1).unwrap(), 0x7F);
+
+        cpu.step().unwrap();
+        assert!(cpu.state().double_speed && !cpu.state().stopped);
+        assert_eq!(cpu.mem_load(memory::KEY1).unwrap(), 0xFE);
+        assert_eq!(cpu.regs.pc, 0x102);
+    }
+
+    #[test]
+    fn div_write_ticks_tima() {
+        let mut cpu = init();
+        cpu.reset_div();
+
+        // bit 3 of the divider is selected, and goes high after 2 M-cycles
+        cpu.tick();
+        cpu.tick();
+        assert_eq!(cpu.memory.load(memory::TIMA), Some(0xF0));
+
+        cpu.mem_set(memory::DIV, 0x12);
+        assert_eq!(cpu.memory.load(memory::TIMA), Some(0xF1));
+        assert_eq!(cpu.memory.load(memory::DIV), Some(0));
+
+        // the bit is low now, so neither the next tick nor another reset counts
+        cpu.tick();
+        cpu.reset_div();
+        assert_eq!(cpu.memory.load(memory::TIMA), Some(0xF1));
+    }
+
+    #[test]
+    fn unused_bits_read_high() {
+        let mut cpu = init();
+
+        cpu.mem_set(memory::STAT, 0x00);
+        assert_eq!(cpu.mem_load(memory::STAT).unwrap() & 0x80, 0x80);
+
+        cpu.mem_set(memory::IF, 0x00);
+        assert_eq!(cpu.mem_load(memory::IF).unwrap(), 0xE0);
+
+        cpu.mem_set(memory::TAC, 0x05);
+        assert_eq!(cpu.mem_load(memory::TAC).unwrap(), 0xFD);
+
+        // nothing selected, nothing pressed
+        cpu.mem_set(memory::JOYP, 0x30);
+        assert_eq!(cpu.mem_load(memory::JOYP).unwrap(), 0xFF);
+    }
+
+    #[test]
+    fn undocumented_registers() {
+        let mut cpu = init();
+
+        assert_eq!(cpu.mem_load(memory::UNDOC_FF72).unwrap(), 0x00);
+        assert_eq!(cpu.mem_load(memory::UNDOC_FF75).unwrap(), 0x8F);
+
+        for addr in [memory::UNDOC_FF72, memory::UNDOC_FF73, memory::UNDOC_FF74] {
+            cpu.mem_set(addr, 0xA5);
+            assert_eq!(cpu.mem_load(addr).unwrap(), 0xA5, "{addr:#06X}");
+        }
+
+        // only bits 4-6 stick, the rest read 1
+        cpu.mem_set(memory::UNDOC_FF75, 0x00);
+        assert_eq!(cpu.mem_load(memory::UNDOC_FF75).unwrap(), 0x8F);
+        cpu.mem_set(memory::UNDOC_FF75, 0xFF);
+        assert_eq!(cpu.mem_load(memory::UNDOC_FF75).unwrap(), 0xFF);
+        cpu.mem_set(memory::UNDOC_FF75, 0x50);
+        assert_eq!(cpu.mem_load(memory::UNDOC_FF75).unwrap(), 0xDF);
+
+        // PCM registers can't be written
+        cpu.mem_set(memory::PCM12, 0x12);
+        cpu.mem_set(memory::PCM34, 0x34);
+        assert_eq!(cpu.mem_load(memory::PCM12).unwrap(), 0x00);
+        assert_eq!(cpu.mem_load(memory::PCM34).unwrap(), 0x00);
+    }
+
+    #[test]
+    fn ppu_memory_locked() {
+        let mut cpu = init();
+        cpu.memory.set(0x8000, 0x12);
+        cpu.memory.set(memory::OAM, 0x34);
+
+        while cpu.ppu.mode() != PpuMode::Transfer {
+            cpu.tick();
+        }
+
+        // the access itself takes a cycle, which is still in pixel transfer
+        assert_eq!(cpu.mem_load(0x8000).unwrap(), 0xFF);
+        assert_eq!(cpu.mem_load(memory::OAM).unwrap(), 0xFF);
+
+        cpu.mem_set(0x8000, 0x56);
+        assert_eq!(cpu.memory.load(0x8000), Some(0x12));
+
+        // debuggers can look anyway
+        cpu.restrict_ppu_memory = false;
+        assert_eq!(cpu.mem_load(0x8000).unwrap(), 0x12);
+        cpu.restrict_ppu_memory = true;
+
+        while cpu.ppu.mode() != PpuMode::HBlank {
+            cpu.tick();
+        }
+
+        assert_eq!(cpu.mem_load(0x8000).unwrap(), 0x12);
+        assert_eq!(cpu.mem_load(memory::OAM).unwrap(), 0x34);
+    }
+
+    #[test]
+    fn strict_mode() {
+        let mut cpu = init();
+        // ld a, [$FEA0], then jp $FF00
+        cpu.memory.splice(0x100, &[0xFA, 0xA0, 0xFE, 0xC3, 0x00, 0xFF]);
+
+        assert!(cpu.step().is_ok());
+
+        // the instruction still finishes before stopping
+        cpu.regs.pc = 0x100;
+        cpu.memory.set_strict(true);
+        let Err(CpuError::InvalidAccess(access)) = cpu.step() else {
+            panic!("Reading the prohibited area should stop the CPU");
+        };
+        assert_eq!(access, InvalidAccess { addr: 0xFEA0, kind: AccessKind::Read, pc: 0x100 });
+        assert_eq!(cpu.regs.pc, 0x103);
+
+        let caught = Arc::new(Mutex::new(Vec::new()));
+        let callback_caught = Arc::clone(&caught);
+        cpu.memory.set_diagnostics(Some(Arc::new(move |access| callback_caught.lock().unwrap().push(*access))));
+
+        // with a callback, it carries on
+        assert!(cpu.step().is_ok());
+        assert!(cpu.step().is_ok());
+        // nothing is selected in JOYP, so it reads as 0xFF, which is rst $38
+        assert_eq!(cpu.regs.pc, 0x38);
+        assert_eq!(
+            *caught.lock().unwrap(),
+            [InvalidAccess { addr: 0xFF00, kind: AccessKind::Execute, pc: 0xFF00 }]
+        );
+    }
+
+    #[test]
+    fn strict_execute_reported_once() {
+        let mut cpu = init();
+        cpu.memory.set_strict(true);
+
+        let caught = Arc::new(Mutex::new(Vec::new()));
+        let callback_caught = Arc::clone(&caught);
+        cpu.memory.set_diagnostics(Some(Arc::new(move |access| callback_caught.lock().unwrap().push(*access))));
+
+        // the prohibited area is invalid to read too, but the fetch only counts as executing
+        cpu.regs.pc = 0xFEA0;
+        assert!(cpu.step().is_ok());
+        assert_eq!(
+            *caught.lock().unwrap(),
+            [InvalidAccess { addr: 0xFEA0, kind: AccessKind::Execute, pc: 0xFEA0 }]
+        );
+    }
+
+    #[test]
+    fn doctor_mode() {
+        let mut cpu = init();
+        cpu.doctor_mode = true;
+
+        for _ in 0..1000 {
+            assert_eq!(cpu.mem_load(memory::LY).unwrap(), 0x90);
+        }
+
+        // the ppu kept going underneath
+        assert_ne!(cpu.ppu.ly(), 0);
+        cpu.doctor_mode = false;
+        assert_eq!(cpu.mem_load(memory::LY).unwrap(), cpu.ppu.ly());
+    }
+}
@@ -0,0 +1,129 @@
+//! How many M-cycles each instruction takes, without running it
+//!
+//! Conditional jumps, calls and returns are listed at their not-taken cost, `branch_cycles` has the taken one
+
+/// M-cycles for unprefixed opcodes, 0 for the ones that don't exist and the `0xCB` prefix
+#[rustfmt::skip]
+const CYCLES: [u8; 0x100] = [
+//  x0 x1 x2 x3 x4 x5 x6 x7 x8 x9 xA xB xC xD xE xF
+    1, 3, 2, 2, 1, 1, 2, 1, 5, 2, 2, 2, 1, 1, 2, 1, // 0x
+    1, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1, // 1x
+    2, 3, 2, 2, 1, 1, 2, 1, 2, 2, 2, 2, 1, 1, 2, 1, // 2x
+    2, 3, 2, 2, 3, 3, 3, 1, 2, 2, 2, 2, 1, 1, 2, 1, // 3x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 4x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 5x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 6x
+    2, 2, 2, 2, 2, 2, 1, 2, 1, 1, 1, 1, 1, 1, 2, 1, // 7x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 8x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 9x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // Ax
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // Bx
+    2, 3, 3, 4, 3, 4, 2, 4, 2, 4, 3, 0, 3, 6, 2, 4, // Cx
+    2, 3, 3, 0, 3, 4, 2, 4, 2, 4, 3, 0, 3, 0, 2, 4, // Dx
+    3, 3, 2, 0, 0, 4, 2, 4, 4, 1, 4, 0, 0, 0, 2, 4, // Ex
+    3, 3, 2, 1, 0, 4, 2, 4, 3, 2, 4, 1, 0, 0, 2, 4, // Fx
+];
+
+/// Gets how many M-cycles the instruction with `opcode` takes, including fetching it and its operands
+///
+/// `cb` is for opcodes that come after the `0xCB` prefix, and their count includes the prefix.
+/// Conditional instructions give the cost when the condition fails, see `branch_cycles` for when it passes
+///
+/// ### Return Variants
+/// - `0` if `opcode` isn't an instruction, or is the `0xCB` prefix without `cb` set
+/// - The number of M-cycles otherwise
+pub fn opcode_cycles(opcode: u8, cb: bool) -> u8 {
+    if !cb {
+        return CYCLES[opcode as usize];
+    }
+
+    match opcode {
+        // BIT only reads (HL), everything else writes it back too
+        0x40..=0x7F if opcode & 0x07 == 6 => 3,
+        _ if opcode & 0x07 == 6 => 4,
+        _ => 2,
+    }
+}
+
+/// Gets how many M-cycles a conditional instruction takes when its condition passes
+///
+/// ### Return Variants
+/// - `Some(cycles)` for conditional `JR`, `JP`, `CALL` and `RET`
+/// - `None` for anything else, which always takes `opcode_cycles`
+pub fn branch_cycles(opcode: u8) -> Option<u8> {
+    match opcode {
+        // JR cc
+        0x20 | 0x28 | 0x30 | 0x38 => Some(3),
+        // RET cc
+        0xC0 | 0xC8 | 0xD0 | 0xD8 => Some(5),
+        // JP cc
+        0xC2 | 0xCA | 0xD2 | 0xDA => Some(4),
+        // CALL cc
+        0xC4 | 0xCC | 0xD4 | 0xDC => Some(6),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cpu::Cpu,
+        memory::{mbc::MbcSelector, Mmu},
+        ppu::Ppu,
+    };
+
+    use super::{branch_cycles, opcode_cycles};
+
+    fn init() -> Cpu {
+        let mut cpu = Cpu::new(Mmu::new(MbcSelector::NoMbc), Ppu::new(), false, true);
+
+        // keep everything that touches memory in WRAM, and interrupts out of the way
+        cpu.regs.set_bc(0xC000);
+        cpu.regs.set_de(0xC000);
+        cpu.regs.set_hl(0xC000);
+        cpu.regs.sp = 0xCFF0;
+        cpu.regs.ime = false;
+
+        cpu
+    }
+
+    /// Runs `program` and returns how many M-cycles it took
+    fn run(cpu: &mut Cpu, program: &[u8]) -> u8 {
+        cpu.memory.splice(cpu.regs.pc, program);
+
+        let tick = cpu.tick;
+        cpu.step().unwrap();
+
+        (cpu.tick - tick) as u8
+    }
+
+    #[test]
+    fn matches_execution() {
+        for opcode in 0..=0xFF {
+            // HALT and STOP wait for something to happen
+            if matches!(opcode, 0x10 | 0x76) || opcode_cycles(opcode, false) == 0 {
+                continue;
+            }
+
+            // every condition fails with the flags cleared except NZ and NC, and those fail with them set
+            let mut cpu = init();
+            let flags = if matches!(opcode, 0x20 | 0x30 | 0xC0 | 0xC2 | 0xC4 | 0xD0 | 0xD2 | 0xD4) { 0xF0 } else { 0x00 };
+            cpu.regs.f.set_bits(flags);
+
+            assert_eq!(run(&mut cpu, &[opcode, 0x00, 0xC0]), opcode_cycles(opcode, false), "opcode {opcode:#04X}");
+
+            if let Some(taken) = branch_cycles(opcode) {
+                let mut cpu = init();
+                cpu.regs.f.set_bits(flags ^ 0xF0);
+
+                assert_eq!(run(&mut cpu, &[opcode, 0x00, 0xC0]), taken, "opcode {opcode:#04X} taken");
+            }
+        }
+
+        for opcode in 0..=0xFF {
+            let mut cpu = init();
+
+            assert_eq!(run(&mut cpu, &[0xCB, opcode]), opcode_cycles(opcode, true), "opcode 0xCB {opcode:#04X}");
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Turns machine code back into assembly, for debuggers and traces
+//!
+//! Operands are written as `$` hex. Relative jumps show the address they land on, and addresses in `LDH` have the
+//! `$FF00` added in
+
+use crate::memory::Mmu;
+
+/// Unprefixed opcodes, with the operand written as a placeholder that `disassemble` fills in
+///
+/// `d8`/`d16` are immediates, `a8`/`a16` are addresses, `r8` is a relative jump, and `s8` is a signed offset.
+/// The register loads (0x40-0x7F) and arithmetic (0x80-0xBF) are left empty since they're worked out from the opcode.
+/// Opcodes that don't exist are `-`
+#[rustfmt::skip]
+const MNEMONICS: [&str; 0x100] = [
+    // 0x
+    "NOP", "LD BC,d16", "LD (BC),A", "INC BC", "INC B", "DEC B", "LD B,d8", "RLCA",
+    "LD (a16),SP", "ADD HL,BC", "LD A,(BC)", "DEC BC", "INC C", "DEC C", "LD C,d8", "RRCA",
+    // 1x
+    "STOP d8", "LD DE,d16", "LD (DE),A", "INC DE", "INC D", "DEC D", "LD D,d8", "RLA",
+    "JR r8", "ADD HL,DE", "LD A,(DE)", "DEC DE", "INC E", "DEC E", "LD E,d8", "RRA",
+    // 2x
+    "JR NZ,r8", "LD HL,d16", "LD (HL+),A", "INC HL", "INC H", "DEC H", "LD H,d8", "DAA",
+    "JR Z,r8", "ADD HL,HL", "LD A,(HL+)", "DEC HL", "INC L", "DEC L", "LD L,d8", "CPL",
+    // 3x
+    "JR NC,r8", "LD SP,d16", "LD (HL-),A", "INC SP", "INC (HL)", "DEC (HL)", "LD (HL),d8", "SCF",
+    "JR C,r8", "ADD HL,SP", "LD A,(HL-)", "DEC SP", "INC A", "DEC A", "LD A,d8", "CCF",
+    // 4x - Bx
+    "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
+    // Cx
+    "RET NZ", "POP BC", "JP NZ,a16", "JP a16", "CALL NZ,a16", "PUSH BC", "ADD A,d8", "RST $00",
+    "RET Z", "RET", "JP Z,a16", "", "CALL Z,a16", "CALL a16", "ADC A,d8", "RST $08",
+    // Dx
+    "RET NC", "POP DE", "JP NC,a16", "-", "CALL NC,a16", "PUSH DE", "SUB d8", "RST $10",
+    "RET C", "RETI", "JP C,a16", "-", "CALL C,a16", "-", "SBC A,d8", "RST $18",
+    // Ex
+    "LDH (a8),A", "POP HL", "LD ($FF00+C),A", "-", "-", "PUSH HL", "AND d8", "RST $20",
+    "ADD SP,s8", "JP HL", "LD (a16),A", "-", "-", "-", "XOR d8", "RST $28",
+    // Fx
+    "LDH A,(a8)", "POP AF", "LD A,($FF00+C)", "DI", "-", "PUSH AF", "OR d8", "RST $30",
+    "LD HL,SP+s8", "LD SP,HL", "LD A,(a16)", "EI", "-", "-", "CP d8", "RST $38",
+];
+
+/// Registers in the order they're encoded in opcodes
+const REGISTERS: [&str; 8] = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+
+/// Arithmetic for 0x80-0xBF, in opcode order
+const ARITHMETIC: [&str; 8] = ["ADD A,", "ADC A,", "SUB ", "SBC A,", "AND ", "XOR ", "OR ", "CP "];
+
+/// Rotates and shifts for 0xCB00-0xCB3F, in opcode order
+const SHIFTS: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"];
+
+/// Disassembles the instruction at `addr`, giving its text and how many bytes long it is
+///
+/// Uninitialized bytes are read as 0, the same as `Mmu::load_block`. Opcodes that don't exist, and instructions
+/// that would run past 0xFFFF, come out as a single data byte like `DB $D3`
+pub fn disassemble(memory: &Mmu, addr: u16) -> (String, u16) {
+    let byte = |offset: u16| memory.load(addr.wrapping_add(offset)).unwrap_or(0);
+    let opcode = byte(0);
+    let data = (format!("DB ${opcode:02X}"), 1);
+
+    match opcode {
+        0x76 => ("HALT".to_owned(), 1),
+        0x40..=0x7F => (format!("LD {},{}", register(opcode >> 3), register(opcode)), 1),
+        0x80..=0xBF => (format!("{}{}", ARITHMETIC[(opcode >> 3 & 7) as usize], register(opcode)), 1),
+        0xCB if addr == 0xFFFF => data,
+        0xCB => (prefixed(byte(1)), 2),
+        _ if MNEMONICS[opcode as usize] == "-" => data,
+        _ => {
+            let mnemonic = MNEMONICS[opcode as usize];
+            let length = if ["d16", "a16"].iter().any(|p| mnemonic.contains(p)) {
+                3
+            } else if ["d8", "a8", "r8", "s8"].iter().any(|p| mnemonic.contains(p)) {
+                2
+            } else {
+                1
+            };
+
+            if addr.checked_add(length - 1).is_none() {
+                return data;
+            }
+
+            let d8 = byte(1);
+            let d16 = u16::from_le_bytes([byte(1), byte(2)]);
+            let target = addr.wrapping_add(2).wrapping_add(d8 as i8 as u16);
+            let signed = if (d8 as i8) < 0 { format!("-${:02X}", (d8 as i8).unsigned_abs()) } else { format!("+${d8:02X}") };
+
+            let text = mnemonic
+                .replace("d16", &format!("${d16:04X}"))
+                .replace("a16", &format!("${d16:04X}"))
+                .replace("d8", &format!("${d8:02X}"))
+                .replace("a8", &format!("$FF{d8:02X}"))
+                .replace("r8", &format!("${target:04X}"))
+                .replace("+s8", &signed)
+                .replace("s8", &signed);
+
+            (text, length)
+        }
+    }
+}
+
+/// Disassembles `count` instructions one after another, starting at `start`, giving the address of each one
+///
+/// The listing stops early at the end of the address space instead of wrapping back around to 0
+pub fn disassemble_range(memory: &Mmu, start: u16, count: usize) -> Vec<(u16, String)> {
+    let mut lines = Vec::with_capacity(count);
+    let mut addr = Some(start);
+
+    while let Some(current) = addr {
+        if lines.len() == count {
+            break;
+        }
+
+        let (text, length) = disassemble(memory, current);
+        lines.push((current, text));
+        addr = current.checked_add(length);
+    }
+
+    lines
+}
+
+/// Gets the register encoded in the low 3 bits of `bits`
+fn register(bits: u8) -> &'static str {
+    REGISTERS[(bits & 7) as usize]
+}
+
+/// Disassembles the opcode after a `0xCB` prefix
+fn prefixed(opcode: u8) -> String {
+    let target = register(opcode);
+    let bit = opcode >> 3 & 7;
+
+    match opcode >> 6 {
+        0 => format!("{} {target}", SHIFTS[bit as usize]),
+        1 => format!("BIT {bit},{target}"),
+        2 => format!("RES {bit},{target}"),
+        _ => format!("SET {bit},{target}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::{mbc::MbcSelector, Mmu};
+
+    use super::{disassemble, disassemble_range};
+
+    #[test]
+    fn listing() {
+        let mut memory = Mmu::new(MbcSelector::NoMbc);
+        #[rustfmt::skip]
+        let program = [
+            0x00,
+            0x3E, 0x45,
+            0xC3, 0x34, 0x12,
+            0xCB, 0x7C,
+            0x18, 0xFE,
+            0xE0, 0x40,
+            0xF8, 0xFE,
+            0x77,
+            0xA8,
+            0xFF,
+            0xD3,
+        ];
+        memory.splice(0xC000, &program);
+
+        let lines = disassemble_range(&memory, 0xC000, 12);
+        let expected = [
+            (0xC000, "NOP"),
+            (0xC001, "LD A,$45"),
+            (0xC003, "JP $1234"),
+            (0xC006, "BIT 7,H"),
+            (0xC008, "JR $C008"),
+            (0xC00A, "LDH ($FF40),A"),
+            (0xC00C, "LD HL,SP-$02"),
+            (0xC00E, "LD (HL),A"),
+            (0xC00F, "XOR B"),
+            (0xC010, "RST $38"),
+            (0xC011, "DB $D3"),
+            (0xC012, "NOP"),
+        ];
+
+        assert_eq!(lines.len(), expected.len());
+        for ((addr, text), (expected_addr, expected_text)) in lines.iter().zip(expected) {
+            assert_eq!((*addr, text.as_str()), (expected_addr, expected_text));
+        }
+    }
+
+    #[test]
+    fn end_of_memory() {
+        let mut memory = Mmu::new(MbcSelector::NoMbc);
+
+        // jp a16 with its operand cut off by the end of the address space
+        memory.set(0xFFFE, 0xC3);
+        memory.set(0xFFFF, 0x00);
+
+        assert_eq!(disassemble(&memory, 0xFFFE), ("DB $C3".to_owned(), 1));
+        assert_eq!(
+            disassemble_range(&memory, 0xFFFE, 5),
+            [(0xFFFE, "DB $C3".to_owned()), (0xFFFF, "NOP".to_owned())]
+        );
+    }
+}
@@ -50,7 +50,7 @@ impl Cpu {
     /// ### Flag States
     /// - The `zero` flag is set if the output is `0`
     /// - The `subtract` flag is set to `1`
-    /// - The `half carry` flag is reset to `0`
+    /// - The `half carry` flag is set if a bit was borrowed from bit 4
     /// - The `carry` flag is set if the output wraps around `0` to `255`
     pub fn sub(&mut self, value: u8) -> u8 {
         let (out, carry) = self.regs.a.overflowing_sub(value);
@@ -71,7 +71,7 @@ impl Cpu {
     /// ### Flag States
     /// - The `zero` flag is set if the output is `0`
     /// - The `subtract` flag is set to `1`
-    /// - The `half carry` flag is reset to `0`
+    /// - The `half carry` flag is set if a bit was borrowed from bit 4
     /// - The `carry` flag is set if the output wraps around `0` to `255`
     pub fn sub_carry(&mut self, value: u8) -> u8 {
         let carry = if self.regs.f.carry { 1 } else { 0 };
@@ -92,6 +92,17 @@ impl Cpu {
         out
     }
 
+    /// Compares a u8 with register A by subtracting it, without storing the result
+    ///
+    /// ### Flag States
+    /// - The `zero` flag is set if the values are equal
+    /// - The `subtract` flag is set to `1`
+    /// - The `half carry` flag is set if a bit was borrowed from bit 4
+    /// - The `carry` flag is set if `value` is greater than A
+    pub fn cp(&mut self, value: u8) {
+        self.sub(value);
+    }
+
     /// ANDs a u8 together with register A
     ///
     /// ### Flag States
@@ -154,9 +165,9 @@ impl Cpu {
     /// - The `half carry` flag is set if a bit was carried from bit 3 to bit 4
     /// - The `carry` flag is unaffected
     pub fn inc(&mut self, value: u8) -> u8 {
-        let (out, carry) = value.overflowing_add(1);
+        let out = value.wrapping_add(1);
 
-        self.set_flag(CpuFlag::Zero, carry);
+        self.set_flag(CpuFlag::Zero, out == 0);
         self.set_flag(CpuFlag::Subtract, false);
         self.set_flag(CpuFlag::HalfCarry, (value & 0xF) + 1 > 0x0F);
 
@@ -240,24 +251,26 @@ impl Cpu {
         out
     }
 
-    /// Adds an i8 to the stack pointer
+    /// Adds an i8 to the stack pointer, without storing the result
+    ///
+    /// Used by both `ADD SP, r8` and `LD HL, SP+r8`, which spend different amounts of time on it,
+    /// so the caller is responsible for ticking
     ///
     /// ### Flag States
+    /// The flags come from adding `value` to the low byte of SP as if it were unsigned, even when it's negative
     /// - The `zero` flag is reset to `0`
     /// - The `subtract` flag is reset to `0`
     /// - The `half carry` flag is set if bit 3 overflows into bit 4
     /// - The `carry` flag is set if bit 7 overflows into bit 8
     pub fn add_sp(&mut self, value: i8) -> u16 {
-        let out = self.regs.sp.wrapping_add(value as u16);
-
-        // this instruction takes 4 ticks, i think cause it needs to zero extend `value`
-        self.tick();
-        self.tick();
+        let sp = self.regs.sp;
+        let out = sp.wrapping_add(value as u16);
+        let low = value as u8;
 
         self.set_flag(CpuFlag::Zero, false);
         self.set_flag(CpuFlag::Subtract, false);
-        self.set_flag(CpuFlag::HalfCarry, ((self.regs.sp as u8 & 0x0F) + ((value) as u8 & 0x0F)) > 0x0F);
-        self.set_flag(CpuFlag::Carry, (self.regs.sp & 0xFF) + (value as u16 & 0xFF) & 0x0100 > 0);
+        self.set_flag(CpuFlag::HalfCarry, (sp as u8 & 0x0F) + (low & 0x0F) > 0x0F);
+        self.set_flag(CpuFlag::Carry, (sp & 0xFF) + low as u16 > 0xFF);
 
         out
     }
@@ -487,7 +500,94 @@ mod tests {
         assert_eq!(cpu.regs.f.as_byte(), 0b0110_0000);
     }
 
+    #[test]
+    fn inc_dec_keep_carry() {
+        let mut cpu = init();
+        cpu.regs.b = 0x0F;
+        cpu.regs.f.set_bits(0b0001_0000);
+
+        cpu.execute(Instruction::INC(ArithmeticTarget::B));
+
+        assert_eq!(cpu.regs.b, 0x10);
+        assert_eq!(cpu.regs.f.as_byte(), 0b0011_0000);
+
+        cpu.execute(Instruction::DEC(ArithmeticTarget::B));
+
+        assert_eq!(cpu.regs.b, 0x0F);
+        assert_eq!(cpu.regs.f.as_byte(), 0b0111_0000);
+    }
+
+    #[test]
+    fn logic_flags() {
+        // (instruction, a, b, result, flags)
+        let cases = [
+            (Instruction::AND(ArithmeticTarget::B), 0b1100, 0b1010, 0b1000, 0b0010_0000),
+            (Instruction::AND(ArithmeticTarget::B), 0xF0, 0x0F, 0, 0b1010_0000),
+            (Instruction::OR(ArithmeticTarget::B), 0b1100, 0b1010, 0b1110, 0),
+            (Instruction::OR(ArithmeticTarget::B), 0, 0, 0, 0b1000_0000),
+            (Instruction::XOR(ArithmeticTarget::B), 0b1100, 0b1010, 0b0110, 0),
+            (Instruction::XOR(ArithmeticTarget::B), 0x5A, 0x5A, 0, 0b1000_0000),
+            // cp leaves a alone
+            (Instruction::CP(ArithmeticTarget::B), 0x42, 0x42, 0x42, 0b1100_0000),
+            (Instruction::CP(ArithmeticTarget::B), 0x10, 0x01, 0x10, 0b0110_0000),
+            (Instruction::CP(ArithmeticTarget::B), 0x10, 0x20, 0x10, 0b0101_0000),
+        ];
+
+        for (instruction, a, b, result, flags) in cases {
+            let mut cpu = init();
+            cpu.regs.a = a;
+            cpu.regs.b = b;
+            // every flag starts set so the ops have to clear them
+            cpu.regs.f.set_bits(0xF0);
+
+            cpu.execute(instruction);
+
+            assert_eq!(cpu.regs.a, result, "{instruction:?} {a:#04x} {b:#04x}");
+            assert_eq!(cpu.regs.f.as_byte(), flags, "{instruction:?} {a:#04x} {b:#04x}");
+        }
+    }
+
+    #[test]
+    fn cp_targets() {
+        // cp n, cp r, and cp (hl) all go through `sub`, so they should all borrow from bit 4 the same way
+        for target in [ArithmeticTarget::Immediate, ArithmeticTarget::B, ArithmeticTarget::HL] {
+            let mut cpu = init();
+            cpu.regs.a = 0x10;
+            cpu.regs.b = 0x01;
+            cpu.regs.set_hl(0xC000);
+            cpu.regs.f.set_bits(0);
+            cpu.memory.splice(cpu.regs.pc + 1, &[0x01]);
+            cpu.memory.set(0xC000, 0x01);
+
+            cpu.execute(Instruction::CP(target)).unwrap();
+
+            assert_eq!(cpu.regs.a, 0x10, "{target:?}");
+            assert_eq!(cpu.regs.f.as_byte(), 0b0110_0000, "{target:?}");
+        }
+    }
+
     // ---------- 16 bit ----------
+    #[test]
+    fn incw_decw_flags() {
+        let mut cpu = init();
+        cpu.regs.set_bc(0xFFFF);
+        cpu.regs.sp = 0;
+        cpu.regs.f.set_bits(0xF0);
+
+        cpu.execute(Instruction::INCW(WordArithmeticTarget::BC));
+        cpu.execute(Instruction::DECW(WordArithmeticTarget::SP));
+
+        assert_eq!(cpu.regs.get_bc(), 0);
+        assert_eq!(cpu.regs.sp, 0xFFFF);
+        assert_eq!(cpu.regs.f.as_byte(), 0xF0);
+
+        cpu.regs.f.set_bits(0);
+        cpu.execute(Instruction::INCW(WordArithmeticTarget::SP));
+
+        assert_eq!(cpu.regs.sp, 0);
+        assert_eq!(cpu.regs.f.as_byte(), 0);
+    }
+
     #[test]
     fn add_hl() {
         let mut cpu = init();
@@ -511,4 +611,26 @@ mod tests {
         assert_eq!(cpu.regs.get_hl(), 16);
         assert_eq!(cpu.regs.f.as_byte(), 0b0010_0000);
     }
+
+    #[test]
+    fn add_sp_negative() {
+        // (sp, r8, result, flags)
+        let cases = [
+            (0x0005, -1, 0x0004, 0b0011_0000),
+            (0x0000, -1, 0xFFFF, 0),
+            (0x00F8, 8, 0x0100, 0b0011_0000),
+            (0x000F, 1, 0x0010, 0b0010_0000),
+        ];
+
+        for (sp, value, result, flags) in cases {
+            let mut cpu = init();
+            cpu.regs.sp = sp;
+            cpu.regs.f.set_bits(0xF0);
+
+            cpu.regs.sp = cpu.add_sp(value);
+
+            assert_eq!(cpu.regs.sp, result, "{sp:#06x} {value}");
+            assert_eq!(cpu.regs.f.as_byte(), flags, "{sp:#06x} {value}");
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use crate::{cpu::Cpu, CpuFlag};
+use crate::{bits::{self, bit_set}, cpu::Cpu, CpuFlag};
 
 impl Cpu {
     /// Flips the carry flag
@@ -54,7 +54,7 @@ impl Cpu {
     /// - The `half carry` flag is reset to `0`
     /// - The `carry` flag is set to the previous value of bit 7
     pub(crate) fn rla(&mut self) {
-        let carry = self.regs.a & (1 << 7) > 0;
+        let carry = bit_set(self.regs.a, 7);
 
         self.regs.a <<= 1;
         self.regs.a |= self.regs.get_cf() as u8;
@@ -92,7 +92,7 @@ impl Cpu {
     /// - The `half carry` flag is reset to `0`
     /// - The `carry` flag is set to the previous value of bit 7
     pub(crate) fn rlca(&mut self) {
-        let carry = self.regs.a & (1 << 7) > 0;
+        let carry = bit_set(self.regs.a, 7);
 
         self.regs.a <<= 1;
         self.regs.a |= carry as u8;
@@ -129,7 +129,7 @@ impl Cpu {
             panic!("[BIT] Bit target `{idx}` out of range");
         }
 
-        self.set_flag(CpuFlag::Zero, (byte & (1 << idx)) == 0);
+        self.set_flag(CpuFlag::Zero, !bit_set(byte, idx));
         self.set_flag(CpuFlag::Subtract, false);
         self.set_flag(CpuFlag::HalfCarry, true);
     }
@@ -140,7 +140,7 @@ impl Cpu {
             panic!("[RES] Bit target `{idx}` out of range");
         }
 
-        byte & !(1 << idx)
+        byte & !bits::bit(idx)
     }
 
     /// Sets the selected bit to `1`
@@ -149,7 +149,7 @@ impl Cpu {
             panic!("[SET] Bit target `{idx}` out of range");
         }
 
-        byte | (1 << idx)
+        byte | bits::bit(idx)
     }
 
     /// Shifts the selected byte right, putting bit 0 in the carry flag and resetting bit 7 to `0`
@@ -198,13 +198,13 @@ impl Cpu {
     /// - The `half carry` flag is reset to `0`
     /// - The `carry` flag is set to the previous value of bit 7
     pub(crate) fn rl(&mut self, value: u8) -> u8 {
-        let carry = value & 1 << 7;
+        let carry = bit_set(value, 7);
         let out = (value << 1) | (self.regs.get_cf() as u8);
 
         self.set_flag(CpuFlag::Zero, out == 0);
         self.set_flag(CpuFlag::Subtract, false);
         self.set_flag(CpuFlag::HalfCarry, false);
-        self.set_flag(CpuFlag::Carry, carry > 0);
+        self.set_flag(CpuFlag::Carry, carry);
 
         out
     }
@@ -237,7 +237,7 @@ impl Cpu {
     /// - The `half carry` flag is reset to `0`
     /// - The `carry` flag is set to the previous value of bit 7
     pub(crate) fn rlc(&mut self, value: u8) -> u8 {
-        let carry = value & (1 << 7) > 0;
+        let carry = bit_set(value, 7);
 
         let out = (value << 1) | (carry as u8);
 
@@ -258,7 +258,7 @@ impl Cpu {
     /// - The `carry` flag is set to the previous value of bit 0
     pub(crate) fn sra(&mut self, value: u8) -> u8 {
         let carry = value & 1 > 0;
-        let old7 = value & (1 << 7);
+        let old7 = value & bits::bit(7);
 
         let out = (value >> 1) | old7;
 
@@ -278,7 +278,7 @@ impl Cpu {
     /// - The `half carry` flag is reset to `0`
     /// - The `carry` flag is set to the previous value of bit 7
     pub(crate) fn sla(&mut self, value: u8) -> u8 {
-        let carry = value & (1 << 7) > 0;
+        let carry = bit_set(value, 7);
 
         let out = value << 1;
 
@@ -326,8 +326,11 @@ mod tests {
     fn init() -> Cpu {
         let mmu = Mmu::new(MbcSelector::NoMbc);
         let ppu = Ppu::new();
+        let mut cpu = Cpu::new(mmu, ppu, false, true);
 
-        Cpu::new(mmu, ppu, false, true)
+        // only the zero flag set, so the carry doesn't leak into rotates
+        cpu.regs.f.set_bits(0b1000_0000);
+        cpu
     }
 
     #[test]
@@ -354,6 +357,22 @@ mod tests {
         assert_eq!(cpu.regs.f.as_byte(), 0b1001_0000);
     }
 
+    #[test]
+    fn ccf_scf_opcodes() {
+        let mut cpu = init();
+        // ccf, scf
+        cpu.memory.splice(cpu.regs.pc, &[0x3F, 0x37]);
+
+        // zero clear has to stay clear too, and subtract and half carry always go
+        cpu.regs.f.set_bits(0b0111_0000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.regs.f.as_byte(), 0b0000_0000);
+
+        cpu.regs.f.set_bits(0b1110_0000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.regs.f.as_byte(), 0b1001_0000);
+    }
+
     #[test]
     fn rra() {
         let mut cpu = init();
@@ -410,6 +429,20 @@ mod tests {
         assert_eq!(cpu.regs.f.as_byte(), 0);
     }
 
+    #[test]
+    fn rlca_zero() {
+        let mut cpu = init();
+        cpu.regs.a = 0;
+
+        // unlike rlc a, the accumulator rotates never set the zero flag
+        cpu.execute(Instruction::RLCA).unwrap();
+        assert_eq!(cpu.regs.a, 0);
+        assert_eq!(cpu.regs.f.as_byte(), 0);
+
+        cpu.execute(Instruction::RLC(ArithmeticTarget::A)).unwrap();
+        assert_eq!(cpu.regs.f.as_byte(), 0b1000_0000);
+    }
+
     #[test]
     fn cpl() {
         let mut cpu = init();
@@ -548,4 +581,25 @@ mod tests {
         assert_eq!(cpu.regs.b, 0b0011_1010);
         assert_eq!(cpu.regs.f.as_byte(), 0b0000_0000);
     }
+
+    #[test]
+    fn cb_hl_timing() {
+        let mut cpu = init();
+        cpu.regs.set_hl(0xC000);
+        cpu.memory.set(0xC000, 0x00);
+
+        // (opcode, M-cycles): BIT only reads (HL), SET and RES write it back too
+        for (opcode, cycles) in [(0x46, 3), (0xC6, 4), (0x86, 4), (0x40, 2)] {
+            cpu.regs.pc = 0x100;
+            cpu.memory.splice(0x100, &[0xCB, opcode]);
+
+            let tick = cpu.tick;
+            cpu.step().unwrap();
+
+            assert_eq!(cpu.tick - tick, cycles, "CB {opcode:#04X}");
+        }
+
+        // SET 0,(HL) then RES 0,(HL)
+        assert_eq!(cpu.memory.load(0xC000), Some(0x00));
+    }
 }
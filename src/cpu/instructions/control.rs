@@ -1,9 +1,15 @@
-use crate::cpu::{Cpu, CpuError};
+use crate::{
+    bits::bit_set,
+    cpu::{Cpu, CpuError},
+    memory,
+};
 
 use super::JumpTest;
 
 impl Cpu {
     /// Jumps to the address contained in the next two bytes if JumpTest succeeds
+    ///
+    /// Takes 4 M-cycles if it jumps, or 3 if it doesn't
     pub(crate) fn jp(&mut self, test: JumpTest) -> Result<u16, CpuError> {
         let jump = match test {
             JumpTest::NotZero => !self.regs.get_zf(),
@@ -13,16 +19,20 @@ impl Cpu {
             JumpTest::Always => true,
         };
 
-        let out = self.load_a16();
+        let out = self.load_a16()?;
 
         if jump {
-            out
+            // loading the address into PC takes a cycle
+            self.tick();
+            Ok(out)
         } else {
             Ok(self.regs.pc.wrapping_add(3))
         }
     }
 
     /// Jumps by a number of addresses as specified by the next byte
+    ///
+    /// Takes 3 M-cycles if it jumps, or 2 if it doesn't
     pub(crate) fn jr(&mut self, test: JumpTest) -> Result<u16, CpuError> {
         let jump = match test {
             JumpTest::NotZero => !self.regs.get_zf(),
@@ -33,9 +43,9 @@ impl Cpu {
         };
 
         let rel = self.load_s8()?;
-        self.tick();
 
         if jump {
+            self.tick();
             Ok(self.regs.pc.wrapping_add(2).wrapping_add(rel as u16))
         } else {
             Ok(self.regs.pc.wrapping_add(2))
@@ -48,6 +58,8 @@ impl Cpu {
     }
 
     /// Jumps to the address stored at the head of the stack
+    ///
+    /// Takes 4 M-cycles unconditionally, or 5 if a condition passes and 2 if it fails
     pub(crate) fn ret(&mut self, test: JumpTest) -> Result<u16, CpuError> {
         let jump = match test {
             JumpTest::NotZero => !self.regs.get_zf(),
@@ -57,22 +69,36 @@ impl Cpu {
             JumpTest::Always => true,
         };
 
+        // checking the condition takes a cycle
+        if !matches!(test, JumpTest::Always) {
+            self.tick();
+        }
+
         if jump {
-            self.pop_word()
-        } else {
+            let out = self.pop_word();
+
+            // setting pc takes another
             self.tick();
+            out
+        } else {
             Ok(self.regs.pc.wrapping_add(1))
         }
     }
 
     /// Jumps to the address stored in the stack, and sets IME to 1
     pub(crate) fn reti(&mut self) -> Result<u16, CpuError> {
+        // unlike ei, this takes effect right away
         self.regs.ime = true;
 
-        self.pop_word()
+        let out = self.pop_word();
+        self.tick();
+
+        out
     }
 
     /// Pushes PC to the stack and jumps to an immediate address
+    ///
+    /// Takes 6 M-cycles if the jump is taken, or 3 if not
     pub(crate) fn call(&mut self, test: JumpTest) -> Result<u16, CpuError> {
         let jump = match test {
             JumpTest::NotZero => !self.regs.get_zf(),
@@ -85,6 +111,8 @@ impl Cpu {
         let out = self.load_a16();
 
         if jump {
+            // internal delay before pushing
+            self.tick();
             self.push_word(self.regs.pc.wrapping_add(3));
             out
         } else {
@@ -101,12 +129,51 @@ impl Cpu {
             panic!("RST operand out of range: `{to}`. Valid range is 0..=7");
         }
 
+        // internal delay before pushing
+        self.tick();
         self.push_word(self.regs.pc.wrapping_add(1));
 
         // We're jumping to the nth byte, so we can just use it as an address directly
         to as u16 * 0x8
     }
 
+    /// Stops the CPU, or does one of the other things STOP can do depending on the buttons, interrupts and KEY1
+    ///
+    /// This follows the chart at https://gbdev.io/pandocs/Reducing_Power_Consumption.html#using-the-stop-instruction
+    ///
+    /// ### Return Variants
+    /// - The address after STOP and the byte after it, or just after STOP if an enabled interrupt is requested
+    ///
+    /// ### Side Effects
+    /// - With a selected button held, DIV is left alone, and the CPU halts if no interrupt is pending
+    /// - Otherwise DIV is reset, then:
+    ///     - In CGB mode with a speed switch armed through KEY1, the speed switches right away, without the pause
+    ///       real hardware takes. The case where IME is set and an interrupt is pending, which glitches on hardware,
+    ///       is treated the same as without IME
+    ///     - Without one, the CPU is stopped until a selected button is pressed
+    pub(crate) fn stop(&mut self) -> u16 {
+        let pending = !self.memory.pending_interrupts().is_empty();
+        let button_held = self.memory.joypad.read() & 0x0F != 0x0F;
+        let length = if pending { 1 } else { 2 };
+
+        if button_held {
+            self.halted = !pending;
+        } else {
+            self.reset_div();
+
+            let key1 = self.memory.load(memory::KEY1).unwrap_or(0);
+
+            if self.ppu.cgb && bit_set(key1, 0) {
+                self.double_speed = !self.double_speed;
+                self.memory.set(memory::KEY1, if self.double_speed { 0x80 } else { 0x00 });
+            } else {
+                self.stop = true;
+            }
+        }
+
+        self.regs.pc.wrapping_add(length)
+    }
+
     /// Reset IME to `0`
     pub(crate) fn di(&mut self) {
         self.regs.ime = false;
@@ -122,7 +189,7 @@ impl Cpu {
 mod tests {
     use crate::{
         cpu::Cpu,
-        memory::{mbc::MbcSelector, Mmu},
+        memory::{self, mbc::MbcSelector, Mmu},
         ppu::Ppu,
     };
 
@@ -137,9 +204,10 @@ mod tests {
     fn jp() {
         let mut cpu = init();
         let start = &[0xC3, 0x00, 0x10];
-        let instruction = &[0xC8, 0x30];
+        let instruction = &[0xCB, 0x30];
 
         cpu.regs.b = 0b1111_0101;
+        cpu.regs.pc = 0;
 
         cpu.memory.splice(0, start);
         cpu.memory.splice(0x1000, instruction);
@@ -156,11 +224,12 @@ mod tests {
     fn jp_a_equals_b() {
         let mut cpu = init();
         let start = &[0x90, 0xCA, 0x23, 0x45];
-        let instruction = &[0xC8, 0x31];
+        let instruction = &[0xCB, 0x31];
 
         cpu.regs.a = 140;
         cpu.regs.b = 140;
         cpu.regs.c = 0b1111_0101;
+        cpu.regs.pc = 0;
 
         cpu.memory.splice(0, start);
         cpu.memory.splice(0x4523, instruction);
@@ -182,41 +251,205 @@ mod tests {
         let mut cpu = init();
         let low = &[0x18, 0x7F];
         let high = &[0x18, 0xE0];
-        let instruction = &[0xC8, 0x30];
+        let instruction = &[0xCB, 0x30];
 
         cpu.regs.b = 0b1111_0101;
+        cpu.regs.pc = 0;
 
+        // offsets are from the end of the instruction
         cpu.memory.splice(0, low);
-        cpu.memory.splice(0x7F, high);
-        cpu.memory.splice(0x5F, instruction);
+        cpu.memory.splice(0x81, high);
+        cpu.memory.splice(0x63, instruction);
 
         cpu.step();
-        assert_eq!(cpu.regs.pc, 0x7F);
+        assert_eq!(cpu.regs.pc, 0x81);
 
         cpu.step();
-        assert_eq!(cpu.regs.pc, 0x5F);
+        assert_eq!(cpu.regs.pc, 0x63);
 
         cpu.step();
         assert_eq!(cpu.regs.b, 0b0101_1111);
     }
 
+    #[test]
+    fn conditional_timing() {
+        let mut cpu = init();
+        // JR NZ,+0 / JP NZ,a16 to the next instruction
+        let start = &[0x20, 0x00, 0xC2, 0x05, 0x01];
+
+        let run = |cpu: &mut Cpu, zero: bool| {
+            cpu.regs.pc = 0x100;
+            cpu.regs.f.set_bits(if zero { 0x80 } else { 0 });
+            cpu.memory.splice(cpu.regs.pc, start);
+
+            let tick = cpu.tick;
+            cpu.step().unwrap();
+            let jr = cpu.tick - tick;
+
+            let tick = cpu.tick;
+            cpu.step().unwrap();
+            let jp = cpu.tick - tick;
+
+            (jr, jp)
+        };
+
+        // 8 and 12 T-cycles when they don't jump, 12 and 16 when they do
+        assert_eq!(run(&mut cpu, true), (2, 3));
+        assert_eq!(run(&mut cpu, false), (3, 4));
+    }
+
     #[test]
     fn jphl() {
         let mut cpu = init();
         let start = &[0xE9];
-        let instruction = &[0xC8, 0x30];
+        let instruction = &[0xCB, 0x30];
 
         cpu.regs.b = 0b1111_0101;
-        cpu.regs.h = 0xA0;
+        cpu.regs.h = 0xC0;
         cpu.regs.l = 0x00;
 
-        cpu.memory.splice(0, start);
-        cpu.memory.splice(0xA000, instruction);
+        cpu.memory.splice(cpu.regs.pc, start);
+        cpu.memory.splice(0xC000, instruction);
 
-        cpu.step();
-        assert_eq!(cpu.regs.pc, 0xA000);
+        let tick = cpu.tick;
+        cpu.step().unwrap();
+        assert_eq!(cpu.regs.pc, 0xC000);
+        assert_eq!(cpu.tick - tick, 1);
 
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.regs.b, 0b0101_1111);
     }
+
+    #[test]
+    fn jphl_no_indirection() {
+        let mut cpu = init();
+
+        cpu.regs.set_hl(0x1234);
+        // a jump table entry that JP (HL) must not follow
+        cpu.memory.splice(0x1234, &[0x78, 0x56]);
+        cpu.memory.splice(cpu.regs.pc, &[0xE9]);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.regs.pc, 0x1234);
+        assert_eq!(cpu.regs.get_hl(), 0x1234);
+    }
+
+    #[test]
+    fn call_ret() {
+        let mut cpu = init();
+        let start = cpu.regs.pc;
+
+        cpu.regs.sp = 0xFFFE;
+        cpu.memory.splice(start, &[0xCD, 0x34, 0x12]);
+        cpu.memory.splice(0x1234, &[0xC9]);
+
+        let tick = cpu.tick;
+        cpu.step().unwrap();
+        assert_eq!(cpu.tick - tick, 6);
+        assert_eq!(cpu.regs.pc, 0x1234);
+        assert_eq!(cpu.regs.sp, 0xFFFC);
+
+        // return address is pushed low byte first
+        let ret = start + 3;
+        assert_eq!(cpu.memory.load(0xFFFC), Some(ret as u8));
+        assert_eq!(cpu.memory.load(0xFFFD), Some((ret >> 8) as u8));
+
+        let tick = cpu.tick;
+        cpu.step().unwrap();
+        assert_eq!(cpu.tick - tick, 4);
+        assert_eq!(cpu.regs.pc, ret);
+        assert_eq!(cpu.regs.sp, 0xFFFE);
+    }
+
+    #[test]
+    fn call_conditional() {
+        let mut cpu = init();
+        let start = cpu.regs.pc;
+
+        cpu.regs.sp = 0xFFFE;
+        cpu.regs.f.zero = false;
+        // call z, then call nz
+        cpu.memory.splice(start, &[0xCC, 0x34, 0x12, 0xC4, 0x34, 0x12]);
+
+        let tick = cpu.tick;
+        cpu.step().unwrap();
+        assert_eq!(cpu.tick - tick, 3);
+        assert_eq!(cpu.regs.pc, start + 3);
+        assert_eq!(cpu.regs.sp, 0xFFFE);
+
+        let tick = cpu.tick;
+        cpu.step().unwrap();
+        assert_eq!(cpu.tick - tick, 6);
+        assert_eq!(cpu.regs.pc, 0x1234);
+    }
+
+    #[test]
+    fn ret_conditional() {
+        let mut cpu = init();
+        let start = cpu.regs.pc;
+
+        cpu.regs.sp = 0xFFFC;
+        cpu.memory.splice(0xFFFC, &[0x00, 0x20]);
+        cpu.regs.f.carry = true;
+        // ret nc, then ret c
+        cpu.memory.splice(start, &[0xD0, 0xD8]);
+
+        let tick = cpu.tick;
+        cpu.step().unwrap();
+        assert_eq!(cpu.tick - tick, 2);
+        assert_eq!(cpu.regs.pc, start + 1);
+
+        let tick = cpu.tick;
+        cpu.step().unwrap();
+        assert_eq!(cpu.tick - tick, 5);
+        assert_eq!(cpu.regs.pc, 0x2000);
+    }
+
+    #[test]
+    fn rst_reti() {
+        let mut cpu = init();
+        let start = cpu.regs.pc;
+
+        cpu.regs.sp = 0xFFFE;
+        cpu.regs.ime = false;
+        // rst 0x28, and reti there
+        cpu.memory.splice(start, &[0xEF]);
+        cpu.memory.splice(0x28, &[0xD9]);
+
+        let tick = cpu.tick;
+        cpu.step().unwrap();
+        assert_eq!(cpu.tick - tick, 4);
+        assert_eq!(cpu.regs.pc, 0x28);
+
+        let tick = cpu.tick;
+        cpu.step().unwrap();
+        assert_eq!(cpu.tick - tick, 4);
+        assert_eq!(cpu.regs.pc, start + 1);
+
+        // ime is set without ei's delay
+        assert!(cpu.regs.ime);
+    }
+
+    #[test]
+    fn reti_interrupt_delay() {
+        let pending = |opcodes: &[u8]| {
+            let mut cpu = init();
+            let start = cpu.regs.pc;
+
+            cpu.regs.sp = 0xFFFC;
+            cpu.regs.ime = false;
+            cpu.memory.splice(0xFFFC, &(start + 1).to_le_bytes());
+            cpu.memory.splice(start, opcodes);
+            cpu.memory.set(memory::IE, 1);
+            cpu.memory.set(memory::IF, 1);
+
+            cpu.step().unwrap();
+            cpu.regs.pc
+        };
+
+        // vblank is serviced right after reti
+        assert_eq!(pending(&[0xD9]), 0x40);
+        // but ei waits for the next instruction
+        assert_ne!(pending(&[0xFB, 0x00]), 0x40);
+    }
 }
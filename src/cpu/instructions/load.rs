@@ -45,7 +45,9 @@ impl Cpu {
                         return Ok(2);
                     }
                     WordTarget::SPFromHL => {
+                        // moving a whole word into SP takes an extra cycle
                         self.regs.sp = self.regs.get_hl();
+                        self.tick();
                         return Ok(1);
                     }
                     _ => {}
@@ -65,9 +67,9 @@ impl Cpu {
                         unreachable!("Returned before the 16 bit immediate was read")
                     }
                     WordTarget::Immediate => {
+                        // low byte first, each write takes its own cycle
                         self.mem_set(source, (self.regs.sp & 0xFF) as u8);
-                        self.memory
-                            .set(source.wrapping_add(1), ((self.regs.sp & 0xFF00) >> 8) as u8)
+                        self.mem_set(source.wrapping_add(1), ((self.regs.sp & 0xFF00) >> 8) as u8)
                     }
                 };
 
@@ -240,6 +242,24 @@ mod tests {
         assert_eq!(cpu.regs.get_hl(), 0x3FEF);
     }
 
+    #[test]
+    fn ld_hl_sp_flags() {
+        let mut cpu = init();
+        let start = &[0xF8, 0xFF];
+
+        cpu.regs.sp = 0x0005;
+        cpu.regs.f.set_bits(0xC0);
+        cpu.memory.splice(cpu.regs.pc, start);
+
+        let tick = cpu.tick;
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.tick - tick, 3);
+        assert_eq!(cpu.regs.get_hl(), 0x0004);
+        assert_eq!(cpu.regs.sp, 0x0005);
+        assert_eq!(cpu.regs.f.as_byte(), 0b0011_0000);
+    }
+
     #[test]
     fn ld_sp_hl() {
         let mut cpu = init();
@@ -255,14 +275,18 @@ mod tests {
     #[test]
     fn ld_a16_sp() {
         let mut cpu = init();
-        let start = &[0x08, 0x00, 0x01];
+        let start = &[0x08, 0x00, 0xC0];
 
-        cpu.regs.sp = 0x4567;
+        cpu.regs.sp = 0xBEEF;
         cpu.memory.splice(cpu.regs.pc, start);
 
-        cpu.step();
-        assert_eq!(cpu.memory.load(0x0100), Some(0x67));
-        assert_eq!(cpu.memory.load(0x0101), Some(0x45));
+        let tick = cpu.tick;
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.tick - tick, 5);
+        assert_eq!(cpu.memory.load(0xC000), Some(0xEF));
+        assert_eq!(cpu.memory.load(0xC001), Some(0xBE));
+        assert_eq!(cpu.regs.sp, 0xBEEF);
     }
 
     #[test]
@@ -312,12 +336,12 @@ mod tests {
         let start = &[0x02];
 
         cpu.regs.a = 0x45;
-        cpu.regs.set_bc(0x100);
-        cpu.memory.set(0x100, 0);
+        cpu.regs.set_bc(0xC000);
+        cpu.memory.set(0xC000, 0);
         cpu.memory.splice(cpu.regs.pc, start);
 
         cpu.step();
-        assert_eq!(cpu.memory.load(0x100), Some(0x45));
+        assert_eq!(cpu.memory.load(0xC000), Some(0x45));
     }
 
     #[test]
@@ -326,13 +350,13 @@ mod tests {
         let start = &[0x22];
 
         cpu.regs.a = 0x45;
-        cpu.regs.set_hl(0x101);
-        cpu.memory.set(0x101, 0);
+        cpu.regs.set_hl(0xC001);
+        cpu.memory.set(0xC001, 0);
         cpu.memory.splice(cpu.regs.pc, start);
 
         cpu.step();
-        assert_eq!(cpu.memory.load(0x101), Some(0x45));
-        assert_eq!(cpu.regs.get_hl(), 0x102);
+        assert_eq!(cpu.memory.load(0xC001), Some(0x45));
+        assert_eq!(cpu.regs.get_hl(), 0xC002);
     }
 
     #[test]
@@ -341,14 +365,55 @@ mod tests {
         let start = &[0x32];
 
         cpu.regs.a = 0x45;
-        cpu.regs.set_hl(0x101);
-        cpu.memory.set(0x101, 0);
+        cpu.regs.set_hl(0xC001);
+        cpu.memory.set(0xC001, 0);
         cpu.memory.splice(cpu.regs.pc, start);
 
-        // load A into 0x0101 and decrement HL
+        // load A into 0xC001 and decrement HL
         cpu.step();
-        assert_eq!(cpu.memory.load(0x101), Some(0x45));
-        assert_eq!(cpu.regs.get_hl(), 0x100);
+        assert_eq!(cpu.memory.load(0xC001), Some(0x45));
+        assert_eq!(cpu.regs.get_hl(), 0xC000);
+    }
+
+    #[test]
+    fn copy_hlup() {
+        let mut cpu = init();
+        let data = [0x12, 0x34, 0x56, 0x78];
+
+        // LD A,(DE) / LD (HL+),A / INC DE, like a copy loop without the loop
+        let start: Vec<u8> = [0x1A, 0x22, 0x13].repeat(data.len());
+
+        cpu.regs.set_de(0xD000);
+        cpu.regs.set_hl(0xC000);
+        cpu.regs.f.set_bits(0xF0);
+        cpu.memory.splice(0xD000, &data);
+        cpu.memory.splice(cpu.regs.pc, &start);
+
+        for _ in 0..data.len() {
+            cpu.step().unwrap();
+
+            let tick = cpu.tick;
+            cpu.step().unwrap();
+            assert_eq!(cpu.tick - tick, 2);
+
+            cpu.step().unwrap();
+        }
+
+        assert_eq!(cpu.memory.load_block(0xC000, 0xC003), data);
+        assert_eq!(cpu.regs.get_hl(), 0xC004);
+        assert_eq!(cpu.regs.f.as_byte(), 0xF0);
+
+        // and back again, from the end
+        cpu.regs.set_hl(0xC003);
+        cpu.memory.splice(cpu.regs.pc, &[0x3A; 4]);
+
+        for &expected in data.iter().rev() {
+            cpu.step().unwrap();
+            assert_eq!(cpu.regs.a, expected);
+        }
+
+        assert_eq!(cpu.regs.get_hl(), 0xBFFF);
+        assert_eq!(cpu.regs.f.as_byte(), 0xF0);
     }
 
     #[test]
@@ -404,4 +469,108 @@ mod tests {
         cpu.step();
         assert_eq!(cpu.memory.load(0xFF80), Some(0x45));
     }
+
+    #[test]
+    fn ldh_joypad() {
+        let mut cpu = init();
+        // LDH (0x00),A / LDH A,(0x00) / LD (C),A / LD A,(C)
+        let start = &[0xE0, 0x00, 0xF0, 0x00, 0xE2, 0xF2];
+
+        // select the d-pad with nothing pressed
+        cpu.regs.a = 0x20;
+        cpu.regs.c = 0x00;
+        cpu.memory.splice(cpu.regs.pc, start);
+
+        let tick = cpu.tick;
+        cpu.step().unwrap();
+        assert_eq!(cpu.tick - tick, 3);
+
+        // only the select bits stick, the rest read back as 1s
+        cpu.regs.a = 0;
+        cpu.step().unwrap();
+        assert_eq!(cpu.regs.a, 0xEF);
+
+        // select the action buttons through C instead
+        cpu.regs.a = 0x10;
+
+        let tick = cpu.tick;
+        cpu.step().unwrap();
+        assert_eq!(cpu.tick - tick, 2);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.regs.a, 0xDF);
+    }
+
+    /// Reads register `index` in opcode order: B, C, D, E, H, L, (HL), A
+    fn reg(cpu: &Cpu, index: u8) -> u8 {
+        match index {
+            0 => cpu.regs.b,
+            1 => cpu.regs.c,
+            2 => cpu.regs.d,
+            3 => cpu.regs.e,
+            4 => cpu.regs.h,
+            5 => cpu.regs.l,
+            6 => cpu.memory.load(cpu.regs.get_hl()).unwrap(),
+            7 => cpu.regs.a,
+            _ => unreachable!(),
+        }
+    }
+
+    fn reset_regs(cpu: &mut Cpu) {
+        cpu.regs.b = 0x11;
+        cpu.regs.c = 0x22;
+        cpu.regs.d = 0x33;
+        cpu.regs.e = 0x44;
+        cpu.regs.set_hl(0xC000);
+        cpu.regs.a = 0x77;
+        cpu.memory.set(0xC000, 0x66);
+    }
+
+    #[test]
+    fn ld_matrix() {
+        let mut cpu = init();
+
+        // register to register, (HL) to register, register to (HL)
+        for opcode in 0x40..=0x7F {
+            if opcode == 0x76 {
+                continue;
+            }
+
+            let (target, source) = ((opcode >> 3) & 7, opcode & 7);
+
+            reset_regs(&mut cpu);
+            let expected = reg(&cpu, source);
+            cpu.regs.pc = 0x100;
+            cpu.memory.splice(0x100, &[opcode]);
+
+            let tick = cpu.tick;
+            cpu.step().unwrap();
+
+            let cycles = if target == 6 || source == 6 { 2 } else { 1 };
+            assert_eq!(cpu.tick - tick, cycles, "LD opcode {opcode:#04X}");
+            assert_eq!(reg(&cpu, target), expected, "LD opcode {opcode:#04X}");
+        }
+
+        // immediate to register, and to (HL) with 0x36
+        for target in 0..8 {
+            let opcode = 0x06 | target << 3;
+
+            reset_regs(&mut cpu);
+            cpu.regs.pc = 0x100;
+            cpu.memory.splice(0x100, &[opcode, 0xA5]);
+
+            let tick = cpu.tick;
+            cpu.step().unwrap();
+
+            let cycles = if target == 6 { 3 } else { 2 };
+            assert_eq!(cpu.tick - tick, cycles, "LD opcode {opcode:#04X}");
+            assert_eq!(reg(&cpu, target), 0xA5, "LD opcode {opcode:#04X}");
+        }
+
+        // LD (HL),(HL) would be 0x76, which is HALT instead
+        cpu.regs.pc = 0x100;
+        cpu.memory.splice(0x100, &[0x76]);
+        cpu.step().unwrap();
+        assert!(cpu.halted);
+    }
 }
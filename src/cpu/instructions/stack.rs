@@ -56,7 +56,7 @@ mod tests {
         // Push them onto the stack in the order [BC, DE, HL, AF], and pop off in the same order, effectively switching the values of the registers
         let start = &[0xC5, 0xD5, 0xE5, 0xF5, 0xC1, 0xD1, 0xE1, 0xF1];
 
-        cpu.regs.sp = 0x3FFF;
+        cpu.regs.sp = 0xCFFF;
         cpu.regs.set_bc(0x1234);
         cpu.regs.set_de(0x2345);
         cpu.regs.set_hl(0x3456);
@@ -65,20 +65,20 @@ mod tests {
 
         // Push them bad boyes onto the stack
         cpu.step();
-        assert_eq!(cpu.memory.load(0x3FFE), Some(0x12));
-        assert_eq!(cpu.memory.load(0x3FFD), Some(0x34));
+        assert_eq!(cpu.memory.load(0xCFFE), Some(0x12));
+        assert_eq!(cpu.memory.load(0xCFFD), Some(0x34));
 
         cpu.step();
-        assert_eq!(cpu.memory.load(0x3FFC), Some(0x23));
-        assert_eq!(cpu.memory.load(0x3FFB), Some(0x45));
+        assert_eq!(cpu.memory.load(0xCFFC), Some(0x23));
+        assert_eq!(cpu.memory.load(0xCFFB), Some(0x45));
 
         cpu.step();
-        assert_eq!(cpu.memory.load(0x3FFA), Some(0x34));
-        assert_eq!(cpu.memory.load(0x3FF9), Some(0x56));
+        assert_eq!(cpu.memory.load(0xCFFA), Some(0x34));
+        assert_eq!(cpu.memory.load(0xCFF9), Some(0x56));
 
         cpu.step();
-        assert_eq!(cpu.memory.load(0x3FF8), Some(0x45));
-        assert_eq!(cpu.memory.load(0x3FF7), Some(0x60));
+        assert_eq!(cpu.memory.load(0xCFF8), Some(0x45));
+        assert_eq!(cpu.memory.load(0xCFF7), Some(0x60));
 
         // POP
         cpu.step();
@@ -94,4 +94,23 @@ mod tests {
         cpu.step();
         assert_eq!(cpu.regs.get_af(), 0x1230);
     }
+
+    #[test]
+    fn pop_af_masks_flags() {
+        let mut cpu = init();
+        // LD BC,0x12FF / PUSH BC / POP AF / PUSH AF / POP DE
+        let start = &[0x01, 0xFF, 0x12, 0xC5, 0xF1, 0xF5, 0xD1];
+
+        cpu.regs.sp = 0xCFFF;
+        cpu.memory.splice(cpu.regs.pc, start);
+
+        for _ in 0..5 {
+            cpu.step().unwrap();
+        }
+
+        // the low nibble of F doesn't exist, so it can't survive the trip
+        assert_eq!(cpu.regs.a, 0x12);
+        assert_eq!(cpu.regs.f.as_byte(), 0xF0);
+        assert_eq!(cpu.regs.get_de(), 0x12F0);
+    }
 }
@@ -1,5 +1,7 @@
 use std::fmt::Display;
 
+use super::instructions::WordArithmeticTarget;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CpuFlag {
     Zero,
@@ -14,6 +16,7 @@ pub enum CpuReg {
     B,
     C,
     D,
+    E,
     H,
     L,
 }
@@ -28,7 +31,7 @@ pub struct Flags {
 }
 
 impl Flags {
-    /// Creates a new instance with a beginning state of 0b1000_0000
+    /// Creates a new instance with a beginning state of 0b1011_0000
     pub fn new() -> Self {
         Self {
             zero: true,
@@ -90,6 +93,7 @@ impl Display for Registers {
 [REGISTER] b: {:#04X}
 [REGISTER] c: {:#04X}
 [REGISTER] d: {:#04X}
+[REGISTER] e: {:#04X}
 [REGISTER] h: {:#04X}
 [REGISTER] l: {:#04X}
 [REGISTER] sp: {:#06X}
@@ -100,6 +104,7 @@ impl Display for Registers {
             self.b,
             self.c,
             self.d,
+            self.e,
             self.h,
             self.l,
             self.sp,
@@ -172,6 +177,52 @@ impl Registers {
         self.f.set_bits((value & 0xFF) as u8);
     }
 
+    /// Gets the byte stored in register `reg`
+    pub fn read(&self, reg: CpuReg) -> u8 {
+        match reg {
+            CpuReg::A => self.a,
+            CpuReg::B => self.b,
+            CpuReg::C => self.c,
+            CpuReg::D => self.d,
+            CpuReg::E => self.e,
+            CpuReg::H => self.h,
+            CpuReg::L => self.l,
+        }
+    }
+
+    /// Sets the byte stored in register `reg`
+    pub fn write(&mut self, reg: CpuReg, value: u8) {
+        match reg {
+            CpuReg::A => self.a = value,
+            CpuReg::B => self.b = value,
+            CpuReg::C => self.c = value,
+            CpuReg::D => self.d = value,
+            CpuReg::E => self.e = value,
+            CpuReg::H => self.h = value,
+            CpuReg::L => self.l = value,
+        }
+    }
+
+    /// Gets the word stored in the register pair `target`
+    pub fn read16(&self, target: WordArithmeticTarget) -> u16 {
+        match target {
+            WordArithmeticTarget::BC => self.get_bc(),
+            WordArithmeticTarget::DE => self.get_de(),
+            WordArithmeticTarget::HL => self.get_hl(),
+            WordArithmeticTarget::SP => self.sp,
+        }
+    }
+
+    /// Sets the word stored in the register pair `target`
+    pub fn write16(&mut self, target: WordArithmeticTarget, value: u16) {
+        match target {
+            WordArithmeticTarget::BC => self.set_bc(value),
+            WordArithmeticTarget::DE => self.set_de(value),
+            WordArithmeticTarget::HL => self.set_hl(value),
+            WordArithmeticTarget::SP => self.sp = value,
+        }
+    }
+
     /// Gets the `zero` flag
     pub fn get_zf(&self) -> bool {
         self.f.zero
@@ -212,3 +263,30 @@ impl Registers {
         self.f.carry = value;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::instructions::WordArithmeticTarget;
+
+    use super::{CpuReg, Registers};
+
+    #[test]
+    fn pairs_and_halves() {
+        let pairs = [
+            (WordArithmeticTarget::BC, CpuReg::B, CpuReg::C),
+            (WordArithmeticTarget::DE, CpuReg::D, CpuReg::E),
+            (WordArithmeticTarget::HL, CpuReg::H, CpuReg::L),
+        ];
+
+        for (pair, high, low) in pairs {
+            let mut regs = Registers::new();
+
+            regs.write16(pair, 0x1234);
+            assert_eq!(regs.read(high), 0x12, "{pair:?}");
+            assert_eq!(regs.read(low), 0x34, "{pair:?}");
+
+            regs.write(low, 0xCD);
+            assert_eq!(regs.read16(pair), 0x12CD, "{pair:?}");
+        }
+    }
+}
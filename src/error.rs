@@ -0,0 +1,60 @@
+use std::{fmt, io};
+
+use crate::cpu::CpuError;
+
+/// Anything that can go wrong setting up or running the system
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed
+    Io(io::Error),
+    /// The ROM ends before the header does, with how long it was
+    RomTooShort(usize),
+    /// The ROM has more data than its header says fits in the cartridge, with how long it was
+    RomTooLarge(usize),
+    /// The cartridge type in the header (`0x147`) isn't an MBC that's supported
+    UnsupportedMbc(u8),
+    /// The ROM size in the header (`0x148`) isn't a real size, or is too big for the cartridge's MBC
+    UnsupportedRomSize(u8),
+    /// The RAM size in the header (`0x149`) isn't a real size, or is too big for the cartridge's MBC
+    UnsupportedRamSize(u8),
+    /// The emulated CPU stopped with an error
+    Cpu(CpuError),
+    /// A buffer passed in to draw a frame into isn't the size of the screen, with how long it was
+    BufferLength(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::RomTooShort(len) => write!(f, "ROM is too short to have a header ({len:#X} bytes)"),
+            Error::RomTooLarge(len) => write!(f, "ROM is bigger than its header says ({len:#X} bytes)"),
+            Error::UnsupportedMbc(kind) => write!(f, "Unsupported MBC: {kind:#04X}"),
+            Error::UnsupportedRomSize(size) => write!(f, "Unsupported ROM size: {size:#04X}"),
+            Error::UnsupportedRamSize(size) => write!(f, "Unsupported RAM size: {size:#04X}"),
+            Error::Cpu(e) => write!(f, "{e}"),
+            Error::BufferLength(len) => write!(f, "Frame buffer has {len} pixels instead of 160x144"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<CpuError> for Error {
+    fn from(e: CpuError) -> Self {
+        Error::Cpu(e)
+    }
+}
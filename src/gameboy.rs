@@ -1,13 +1,94 @@
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
 use crate::{
-    cpu::{Cpu, CpuError, CpuStatus},
-    memory::{mbc::MbcSelector, Mmu},
-    ppu::{Ppu, PpuStatus},
+    cpu::{disassemble, Cpu, CpuError, CpuStatus, CLOCK_HZ},
+    error::Error,
+    header::{Header, HEADER_END},
+    input_log::InputLog,
+    joypad::{Button, ButtonState, KeyMap},
+    limiter::{FpsCounter, FrameLimiter},
+    memory::{mbc::{MbcSelector, RomBankSource}, Interrupt, MemoryInit, Mmu, ResetKind, OPRI},
+    ppu::{CompatPalette, Ppu, PpuStatus, TICKS_PER_FRAME},
+    rewind::{Rewind, Snapshot},
 };
+#[cfg(feature = "sgb")]
+use crate::sgb::{SgbBorder, SgbCallback, SgbPacket, SGB_HEIGHT, SGB_WIDTH, TRANSFER_LEN};
 
 pub const MBC_ADDR: usize = 0x0147;
 
+/// Frontend function called with the PC and opcode of every instruction before it runs, see `Gbc::set_instruction_hook`
+pub type InstructionHook = Box<dyn FnMut(u16, u8) + Send>;
+
+/// What happened during a call to `Gbc::run_for`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RunOutcome {
+    /// A frame finished, and `Gbc::frame` has been updated
+    pub frame_completed: bool,
+    /// T-cycles that were actually run, which can be a bit more than asked for to finish the last instruction
+    pub cycles: u32,
+}
+
+/// What happened during a call to `Gbc::step_instruction`
+#[derive(Clone, Copy, Debug)]
+pub struct StepInfo {
+    /// Where the instruction started
+    pub pc: u16,
+    /// First byte of the instruction, `0xCB` for prefixed ones. While halted or stopped this is just the byte at `pc`
+    pub opcode: u8,
+    /// What the CPU did, the same as from `Gbc::step`
+    pub status: CpuStatus,
+    /// Interrupt that was serviced right after the instruction, if there was one
+    pub interrupt: Option<Interrupt>,
+    /// T-cycles taken, including servicing the interrupt
+    pub cycles: u32,
+    /// Where PC ended up, which is the interrupt's vector if one was serviced
+    pub next_pc: u16,
+    /// A frame finished, and `Gbc::frame` has been updated
+    pub frame_completed: bool,
+}
+
 pub struct Gbc {
     pub cpu: Cpu,
+    /// The last frame returned by `next_frame`, one `0RGB` pixel per element
+    frame: Vec<u32>,
+    /// Tick that the next frame ends on
+    frame_end: usize,
+    /// What WRAM, VRAM and OAM were filled with when the system was made, if anything
+    memory_init: Option<MemoryInit>,
+    /// Forced DMG colors, used instead of picking them from the header
+    palette_override: Option<CompatPalette>,
+    /// Recent snapshots, only kept while rewinding is enabled
+    rewind: Option<Rewind>,
+    /// Buttons held so far, while recording
+    recording: Option<InputLog>,
+    /// Log being played back, with the index of the frame that's running
+    replay: Option<(InputLog, usize)>,
+    /// Where battery RAM is saved when the system is dropped, set by `from_path`
+    save_path: Option<PathBuf>,
+    /// Where every instruction is logged, see `set_trace`
+    trace: Option<Box<dyn Write + Send>>,
+    /// Called before every instruction, see `set_instruction_hook`
+    instruction_hook: Option<InstructionHook>,
+    /// Holds frames back to a set rate, see `set_frame_limit`
+    frame_limit: Option<FrameLimiter>,
+    /// Frames finished since the system was made, see `frame_count`
+    frame_count: u64,
+    fps: FpsCounter,
+    /// Called with every SGB command the game sends, see `set_sgb_callback`
+    #[cfg(feature = "sgb")]
+    sgb_callback: Option<SgbCallback>,
+    #[cfg(feature = "sgb")]
+    sgb_border: SgbBorder,
+    /// A border transfer sent last frame, which gets its data from VRAM at the end of this one
+    #[cfg(feature = "sgb")]
+    sgb_transfer: Option<SgbPacket>,
+    /// The last finished frame with the border around it, empty until there's a border
+    #[cfg(feature = "sgb")]
+    sgb_frame: Vec<u32>,
 }
 
 impl Gbc {
@@ -15,12 +96,202 @@ impl Gbc {
         let memory = Mmu::new(mbc);
         let ppu = Ppu::new();
         let cpu = Cpu::new(memory, ppu, debug, allow_uninit);
+        let frame = vec![0; cpu.ppu.fb.len() / 3];
+
+        Self {
+            cpu,
+            frame,
+            frame_end: TICKS_PER_FRAME,
+            memory_init: None,
+            palette_override: None,
+            rewind: None,
+            recording: None,
+            replay: None,
+            save_path: None,
+            trace: None,
+            instruction_hook: None,
+            frame_limit: None,
+            frame_count: 0,
+            fps: FpsCounter::default(),
+            #[cfg(feature = "sgb")]
+            sgb_callback: None,
+            #[cfg(feature = "sgb")]
+            sgb_border: SgbBorder::new(),
+            #[cfg(feature = "sgb")]
+            sgb_transfer: None,
+            #[cfg(feature = "sgb")]
+            sgb_frame: Vec::new(),
+        }
+    }
+
+    /// Sets up a system with `rom` loaded and the MBC picked from its header, for tests and tools that just want to run a ROM
+    ///
+    /// Nothing here needs a window, frames are read with `next_frame` or `frame`.
+    /// Uninitialized memory reads as 0 and debug output is off
+    ///
+    /// ### Return Variants
+    /// - `Err` if the header doesn't describe a cartridge that can be emulated, see `get_mbc`
+    pub fn new_headless(rom: &[u8]) -> Result<Self, Error> {
+        let mut gbc = Self::new(crate::get_mbc(rom)?, false, true);
+        gbc.load_rom(rom);
+
+        Ok(gbc)
+    }
+
+    /// Same as `new_headless`, but with WRAM, VRAM and OAM filled according to `init` instead of left uninitialized
+    pub fn new_with_memory_init(rom: &[u8], init: MemoryInit) -> Result<Self, Error> {
+        let mut gbc = Self::new_headless(rom)?;
+        gbc.cpu.memory.init_memory(init);
+        gbc.memory_init = Some(init);
+
+        Ok(gbc)
+    }
 
-        Self { cpu }
+    /// Reads a ROM from `path` and loads it, picking the MBC from its header
+    ///
+    /// If the cartridge has a battery, RAM is loaded from a `.sav` file next to the ROM if there is one,
+    /// and saved back there when the system is dropped
+    ///
+    /// ### Return Variants
+    /// - `Err(Error::Io)` if the ROM or save file couldn't be read
+    /// - Any other `Err` if the header doesn't describe a cartridge that can be emulated, see `get_mbc`
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let rom = std::fs::read(path)?;
+        let mut gbc = Self::new_headless(&rom)?;
+
+        if Header::from_rom(&rom).has_battery() {
+            let save_path = path.with_extension("sav");
+
+            match std::fs::read(&save_path) {
+                Ok(save) => gbc.cpu.memory.load_ram(&save),
+                // no save yet, it'll be made on drop
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            gbc.save_path = Some(save_path);
+        }
+
+        Ok(gbc)
     }
 
+    /// Writes the cartridge RAM to `path`, in the same format as other emulators' `.sav` files
+    pub fn save_to_path(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        Ok(std::fs::write(path, self.cpu.memory.save_ram())?)
+    }
+
+    /// Loads a ROM and colorizes it if it's a DMG game, the same way the CGB boot ROM does
     pub fn load_rom(&mut self, data: &[u8]) {
         self.cpu.load_rom(data);
+        self.boot();
+    }
+
+    /// Same as `load_rom`, but with banks read from `source` as they're needed, see `Mmu::load_rom_streaming`
+    pub fn load_rom_streaming(&mut self, source: impl RomBankSource + 'static, cache_banks: usize) {
+        self.cpu.memory.load_rom_streaming(source, cache_banks);
+        self.boot();
+    }
+
+    /// Resets the system, keeping the cartridge in. See `ResetKind` for what each kind clears
+    ///
+    /// The game starts over from the top, the same as just after `load_rom`. A cold reset also fills memory
+    /// the same way as `new_with_memory_init` did, if that's how the system was made
+    pub fn reset(&mut self, kind: ResetKind) {
+        self.cpu.reset(kind);
+
+        if let (ResetKind::Cold, Some(init)) = (kind, self.memory_init) {
+            self.cpu.memory.init_memory(init);
+        }
+
+        self.frame_end = TICKS_PER_FRAME;
+
+        #[cfg(feature = "sgb")]
+        {
+            self.sgb_transfer = None;
+
+            if kind == ResetKind::Cold {
+                self.sgb_border = SgbBorder::new();
+                self.sgb_frame.clear();
+            }
+        }
+
+        self.boot();
+    }
+
+    /// Swaps in a different cartridge without making a new system, like a game launcher would
+    ///
+    /// The header is read first, and the old cartridge stays in if it can't be emulated. If the old cartridge has a battery,
+    /// `flush` is called with its RAM before it's taken out, and it's also saved to its `.sav` file if it came from `from_path`.
+    /// Then the new ROM gets a fresh MBC with `save` loaded into its RAM, and everything else starts over from a cold reset.
+    ///
+    /// Settings like the palette override, trace and rewind depth are kept, but rewind snapshots and any replay in progress
+    /// belong to the old game, so they're dropped. The new cartridge doesn't have a save path, use `save_to_path` for it
+    ///
+    /// ### Return Variants
+    /// - `Err(Error::Io)` if the old save couldn't be written to its save path
+    /// - Any other `Err` if the header doesn't describe a cartridge that can be emulated, see `get_mbc`
+    pub fn insert_cartridge(&mut self, rom: &[u8], save: Option<&[u8]>, flush: impl FnOnce(&[u8])) -> Result<(), Error> {
+        let mbc = crate::get_mbc(rom)?;
+        let old_header = Header::from_rom(&self.cpu.memory.load_block(0, HEADER_END as u16));
+
+        if old_header.has_battery() {
+            let ram = self.cpu.memory.save_ram();
+
+            if let Some(path) = self.save_path.take() {
+                if let Err(e) = std::fs::write(&path, &ram) {
+                    self.save_path = Some(path);
+                    return Err(e.into());
+                }
+            }
+
+            flush(&ram);
+        }
+
+        *self.cpu.memory = Mmu::new(mbc);
+        self.cpu.load_rom(rom);
+
+        if let Some(save) = save {
+            self.cpu.memory.load_ram(save);
+        }
+
+        if let Some(ref mut rewind) = self.rewind {
+            rewind.clear();
+        }
+
+        self.replay = None;
+        self.reset(ResetKind::Cold);
+
+        Ok(())
+    }
+
+    /// Applies what the CGB boot ROM sets up based on the header
+    fn boot(&mut self) {
+        self.apply_compat_palette();
+
+        // the boot rom also sorts objects by x for dmg games, and leaves them in OAM order for cgb ones
+        let header = Header::from_rom(&self.cpu.memory.load_block(0, HEADER_END as u16));
+        self.cpu.memory.set(OPRI, if header.is_cgb() { 0x00 } else { 0x01 });
+        self.cpu.ppu.cgb = header.is_cgb();
+    }
+
+    /// Forces the colors used for DMG games, or picks them from the header again if `palette` is `None`
+    ///
+    /// `CompatPalette::GRAYSCALE` gives the original look
+    pub fn set_palette_override(&mut self, palette: Option<CompatPalette>) {
+        self.palette_override = palette;
+        self.apply_compat_palette();
+    }
+
+    fn apply_compat_palette(&mut self) {
+        let header = Header::from_rom(&self.cpu.memory.load_block(0, HEADER_END as u16));
+        // cgb games don't get colorized, but there's no cgb palette support yet so they stay grey
+        let palette = self
+            .palette_override
+            .or_else(|| CompatPalette::from_header(&header))
+            .unwrap_or(CompatPalette::GRAYSCALE);
+
+        self.cpu.ppu.set_compat_palette(&palette);
     }
 
     /// Entry point for the emulator
@@ -32,7 +303,404 @@ impl Gbc {
     /// 
     /// `fb` must have a length of 4 * 160 * 144 (91,260)
     pub fn step(&mut self) -> (Result<CpuStatus, CpuError>, PpuStatus) {
-        (self.cpu.step(), self.cpu.ppu.status)
+        (self.step_cpu(self.frame_end), self.cpu.ppu.status)
+    }
+
+    /// Runs exactly one instruction, for stepping through a game in a debugger
+    ///
+    /// The PPU, timer and everything else move forward by however many cycles it took. If an interrupt is serviced
+    /// right after it, that's part of the same step, and shows up in `StepInfo::interrupt`. While halted or stopped
+    /// a step is a single M-cycle, and frames end at the same points as in `next_frame` and `run_for`
+    pub fn step_instruction(&mut self) -> Result<StepInfo, CpuError> {
+        let pc = self.cpu.regs.pc;
+        let opcode = self.cpu.memory.load(pc).unwrap_or(0);
+        let start = self.cpu.ticks();
+
+        let status = self.step_cpu(self.frame_end)?;
+        let frame_completed = self.cpu.ticks() >= self.frame_end;
+
+        if frame_completed {
+            self.finish_frame();
+        }
+
+        Ok(StepInfo {
+            pc,
+            opcode,
+            status,
+            interrupt: self.cpu.serviced_interrupt(),
+            cycles: ((self.cpu.ticks() - start) * 4) as u32,
+            next_pc: self.cpu.regs.pc,
+            frame_completed,
+        })
+    }
+
+    /// Number of instructions run since the system was made or last reset, see `Cpu::instructions`
+    ///
+    /// With `Cpu::skip_idle` off it goes up by one for every line of a trace from `set_trace`, so a trace that diverges
+    /// from a reference at line `n` can be run back to just before it with a `CpuEvent::InstructionCount` breakpoint.
+    /// Skipped idle loop iterations are counted but don't get trace lines, so the two drift apart with it on.
+    /// Rewinding puts it back too
+    pub fn instructions_executed(&self) -> u64 {
+        self.cpu.instructions()
+    }
+
+    /// Starts writing a line to `trace` for every instruction that's run, or stops if it's `None`
+    ///
+    /// Each line is `Cpu::trace_line` from just before the instruction runs, then ` ; ` and the instruction as `disassemble` gives it.
+    /// Everything before the ` ; ` matches Gameboy Doctor logs, so a trace can be diffed against theirs after cutting off the rest.
+    /// Nothing is written while halted or stopped, and tracing stops by itself if writing fails.
+    /// The writer has to be `Send` so the system can still be moved to another thread
+    pub fn set_trace(&mut self, trace: Option<Box<dyn Write + Send>>) {
+        self.trace = trace;
+    }
+
+    /// Sets a function to be called before every instruction runs, or stops calling it if `hook` is `None`
+    ///
+    /// It gets the PC the instruction is at, before fetching the opcode moves it on, and the opcode there. For prefixed
+    /// instructions that's `0xCB`, the second byte is at `pc + 1`. This is for things like coverage and profiling, which
+    /// need to see every instruction without stopping at any of them like a breakpoint would.
+    ///
+    /// Nothing is called while halted or stopped, or for the iterations skipped by `Cpu::skip_idle`. Without a hook,
+    /// stepping only checks that there isn't one
+    pub fn set_instruction_hook(&mut self, hook: Option<InstructionHook>) {
+        self.instruction_hook = hook;
+    }
+
+    /// Runs one CPU step, writing it to the trace and calling the instruction hook first if there are any
+    ///
+    /// A skipped idle loop stops at `deadline`, see `Cpu::step_until`
+    fn step_cpu(&mut self, deadline: usize) -> Result<CpuStatus, CpuError> {
+        let running = !self.cpu.halted && !self.cpu.state().stopped;
+
+        if self.trace.is_some() && running && self.write_trace_line().is_err() {
+            self.trace = None;
+        }
+
+        if let Some(ref mut hook) = self.instruction_hook {
+            if running {
+                let pc = self.cpu.regs.pc;
+                hook(pc, self.cpu.memory.load(pc).unwrap_or(0));
+            }
+        }
+
+        self.cpu.step_until(deadline)
+    }
+
+    fn write_trace_line(&mut self) -> io::Result<()> {
+        let Some(ref mut trace) = self.trace else {
+            return Ok(());
+        };
+
+        let (text, _) = disassemble(&self.cpu.memory, self.cpu.regs.pc);
+        writeln!(trace, "{} ; {text}", self.cpu.trace_line())
+    }
+
+    /// Runs the system for exactly one frame's worth of ticks and returns the framebuffer
+    ///
+    /// Every frame is `TICKS_PER_FRAME` M-cycles long, or twice that in CGB double speed, regardless of what the game is doing.
+    /// Instructions that run past the end of a frame take their extra cycles from the next one,
+    /// so the same ROM and inputs always produce the same frames.
+    /// Pixels are `0RGB`, so the buffer can be handed straight to most windowing libraries
+    ///
+    /// Breakpoints and STOP don't end the frame early
+    pub fn next_frame(&mut self) -> Result<&[u32], CpuError> {
+        while self.cpu.ticks() < self.frame_end {
+            self.step_cpu(self.frame_end)?;
+        }
+
+        self.finish_frame();
+
+        Ok(&self.frame)
+    }
+
+    /// Runs instructions for up to `t_cycles` T-cycles, for hosts that need to hand control back regularly
+    ///
+    /// Frames end at the same points as in `next_frame`, and the two can be mixed.
+    /// If a frame ends partway through, it's available from `Gbc::frame` and the rest of the cycles still run
+    pub fn run_for(&mut self, t_cycles: u32) -> Result<RunOutcome, CpuError> {
+        let start = self.cpu.ticks();
+        // instructions always take whole M-cycles
+        let end = start + (t_cycles as usize).div_ceil(4);
+        let mut frame_completed = false;
+
+        while self.cpu.ticks() < end {
+            self.step_cpu(end.min(self.frame_end))?;
+
+            if self.cpu.ticks() >= self.frame_end {
+                self.finish_frame();
+                frame_completed = true;
+            }
+        }
+
+        Ok(RunOutcome {
+            frame_completed,
+            cycles: ((self.cpu.ticks() - start) * 4) as u32,
+        })
+    }
+
+    /// Clock rate the CPU is running at in T-cycles per second, `CLOCK_HZ` (4194304) or twice that in CGB double speed
+    ///
+    /// This changes when a game switches speed with `KEY1` and STOP. The CPU, DIV and the timer, OAM DMA, and serial with
+    /// the internal clock all count in CPU cycles, so they speed up with it. The PPU counts dots, which always go at
+    /// `CLOCK_HZ`, so frames take the same real time at either speed. `run_for` counts CPU T-cycles too, so anything
+    /// paced in real time, like audio, should go by this
+    pub fn clock_hz(&self) -> u32 {
+        self.cpu.clock_hz()
+    }
+
+    /// The last finished frame, in the same format as `next_frame`
+    pub fn frame(&self) -> &[u32] {
+        &self.frame
+    }
+
+    /// Number of frames finished since the system was made, by either `next_frame` or `run_for`
+    ///
+    /// Resets, swapping cartridges and rewinding don't change it, it only ever goes up
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Frames finished per second of real time, over about the last second
+    ///
+    /// This is how fast the host is actually running frames, after any limit from `set_frame_limit`. Comparing it to
+    /// `FRAME_RATE` shows if the host is keeping up. It's `0.0` until 2 frames have finished
+    pub fn fps(&self) -> f32 {
+        self.fps.fps()
+    }
+
+    /// Keeps frames from finishing faster than `rate` frames per second, or lets them run as fast as they can with `None`
+    ///
+    /// `FRAME_RATE` matches real hardware. Frames are paced wherever they finish, in `next_frame` or `run_for`,
+    /// by sleeping until the next one is due. Turning the limit off, like for a fast forward button, and back on
+    /// starts the pacing over instead of making up for lost time
+    ///
+    /// ### Panic Conditions
+    /// - `rate` isn't a positive, finite number
+    pub fn set_frame_limit(&mut self, rate: Option<f64>) {
+        self.frame_limit = rate.map(FrameLimiter::new);
+    }
+
+    /// Moves on to the next frame, copying out the framebuffer and taking a rewind snapshot if needed
+    fn finish_frame(&mut self) {
+        // frames take the same real time in double speed, which is twice as many M-cycles
+        self.frame_end += TICKS_PER_FRAME * (self.cpu.clock_hz() / CLOCK_HZ) as usize;
+
+        #[cfg(feature = "sgb")]
+        if let Some(ref mut callback) = self.sgb_callback {
+            while let Some(packet) = self.cpu.memory.sgb.take() {
+                callback(&packet);
+            }
+        }
+
+        if let Some(ref mut log) = self.recording {
+            log.push(self.cpu.memory.joypad.state());
+        }
+
+        if let Some((ref log, ref mut index)) = self.replay {
+            *index += 1;
+
+            match log.frames().get(*index) {
+                Some(&buttons) => self.set_buttons(buttons),
+                None => self.replay = None,
+            }
+        }
+
+        if let Some(ref mut rewind) = self.rewind {
+            rewind.record(|| Snapshot {
+                cpu: self.cpu.clone(),
+                frame_end: self.frame_end,
+            });
+        }
+
+        for (pixel, rgb) in self.frame.iter_mut().zip(self.cpu.ppu.fb.chunks_exact(3)) {
+            *pixel = u32::from_be_bytes([0, rgb[0], rgb[1], rgb[2]]);
+        }
+
+        #[cfg(feature = "sgb")]
+        self.update_sgb_border();
+
+        if let Some(ref mut limiter) = self.frame_limit {
+            limiter.wait();
+        }
+
+        self.frame_count += 1;
+        self.fps.record(Instant::now());
+    }
+
+    /// Reads the data for a border transfer sent last frame, then draws the border around the frame that just finished
+    #[cfg(feature = "sgb")]
+    fn update_sgb_border(&mut self) {
+        // games show the data for a whole frame after sending the command, so it's read a frame late
+        if let Some(packet) = self.sgb_transfer.take() {
+            let data = self.sgb_vram();
+            self.sgb_border.transfer(&packet, &data);
+        }
+
+        self.sgb_transfer = self.cpu.memory.sgb.take_transfer();
+
+        if self.sgb_border.is_set() {
+            self.sgb_frame.resize(SGB_WIDTH * SGB_HEIGHT, 0);
+            self.sgb_border.render(&self.frame, self.cpu.memory.sgb.backdrop(), &mut self.sgb_frame);
+        }
+    }
+
+    /// The first 4KiB of tile data on the background, which is how the `_TRN` commands send their data
+    ///
+    /// Tiles are read in the order they're shown, 20 to a row, using the map and tile data areas selected in LCDC
+    #[cfg(feature = "sgb")]
+    fn sgb_vram(&self) -> [u8; TRANSFER_LEN] {
+        let lcdc = &self.cpu.ppu.lcdc;
+        let mut data = [0; TRANSFER_LEN];
+
+        for (n, tile) in data.chunks_exact_mut(16).enumerate() {
+            let map_addr = lcdc.bg_map_area + (n / 20 * 32 + n % 20) as u16;
+            let tile_addr = lcdc.bg_addressing.convert_offset(self.cpu.memory.load(map_addr).unwrap_or(0));
+
+            tile.copy_from_slice(&self.cpu.memory.load_block(tile_addr, tile_addr + 15));
+        }
+
+        data
+    }
+
+    /// Starts keeping a snapshot every `interval` frames, up to `depth` snapshots, replacing any previous ones
+    ///
+    /// Snapshots are taken at the end of frames from `next_frame` or `run_for`. At 60 frames per second, a depth of 20 with an interval of 30
+    /// covers the last 10 seconds.
+    ///
+    /// Each snapshot is a full copy of the system's state apart from the cartridge ROM, not a difference from the last one.
+    /// Rewinding only goes back to the snapshots themselves, the frames between them aren't replayed, so a longer interval
+    /// saves memory but rewinds in bigger steps
+    pub fn enable_rewind(&mut self, depth: usize, interval: usize) {
+        self.rewind = Some(Rewind::new(depth, interval));
+    }
+
+    /// Stops rewinding and throws away all snapshots
+    pub fn disable_rewind(&mut self) {
+        self.rewind = None;
+    }
+
+    /// Gets the rewind buffer, if rewinding is enabled
+    pub fn rewind_buffer(&self) -> Option<&Rewind> {
+        self.rewind.as_ref()
+    }
+
+    /// Goes back to the most recent snapshot, removing it from the buffer
+    ///
+    /// Calling this repeatedly steps further back, `interval` frames at a time.
+    /// The framebuffer is updated on the next call to `next_frame`
+    ///
+    /// ### Return Variants
+    /// - `true` if the system was rewound
+    /// - `false` if rewinding is disabled or there are no snapshots left
+    pub fn rewind(&mut self) -> bool {
+        let Some(snapshot) = self.rewind.as_mut().and_then(Rewind::pop) else {
+            return false;
+        };
+
+        self.cpu = snapshot.cpu;
+        self.frame_end = snapshot.frame_end;
+
+        true
+    }
+
+    /// Starts holding `button`, requesting the joypad interrupt if its group is selected
+    ///
+    /// Pressing a button that's already held does nothing
+    pub fn press(&mut self, button: Button) {
+        self.cpu.memory.set_button(button, true);
+    }
+
+    /// Lets go of `button`. Releasing a button that isn't held does nothing
+    pub fn release(&mut self, button: Button) {
+        self.cpu.memory.set_button(button, false);
+    }
+
+    /// Gets every button that's being held, for input displays and debugging
+    pub fn buttons(&self) -> ButtonState {
+        self.cpu.memory.joypad.state()
+    }
+
+    /// Starts recording the buttons held during each frame, throwing away any log that was already being recorded
+    ///
+    /// A frame is recorded when it ends, so the frontend should update the buttons before running each one
+    pub fn start_recording(&mut self) {
+        self.recording = Some(InputLog::new());
+    }
+
+    /// Stops recording and returns the frames recorded since `start_recording`, which is empty if it wasn't called
+    pub fn stop_recording(&mut self) -> InputLog {
+        self.recording.take().unwrap_or_default()
+    }
+
+    /// Plays back `log`, with the first frame's buttons held right away and the rest held at the start of each frame after
+    ///
+    /// Once the log runs out, the last frame's buttons stay held. Input from the frontend during playback
+    /// is overwritten at the start of the next frame, so frontends should stop polling while `is_replaying` is true
+    pub fn play(&mut self, log: InputLog) {
+        let Some(&first) = log.frames().first() else {
+            self.replay = None;
+            return;
+        };
+
+        self.set_buttons(first);
+        self.replay = Some((log, 0));
+    }
+
+    /// Checks if an input log from `play` is still being played back
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// Holds exactly the buttons in `buttons`, going through `Mmu::set_buttons` so interrupts still happen
+    fn set_buttons(&mut self, buttons: ButtonState) {
+        self.cpu.memory.set_buttons(buttons);
+    }
+
+    /// Updates the joypad from the frontend's input state, meant to be called once per frame
+    ///
+    /// `is_down` is called for every button, and should return whether the key bound to it is held.
+    /// The conventional layout is arrow keys for the d-pad, Z/X for B/A, and Enter/Backspace for Start/Select
+    ///
+    /// Frontends with their own input handling can ignore this and use `Mmu::set_button` directly
+    pub fn poll_input(&mut self, is_down: impl Fn(Button) -> bool) {
+        for button in Button::ALL {
+            self.cpu.memory.set_button(button, is_down(button));
+        }
+    }
+
+    /// Updates the joypad using `keymap`, where `is_key_down` reports whether a frontend key is held
+    pub fn poll_keymap<K: Copy + PartialEq>(&mut self, keymap: &KeyMap<K>, is_key_down: impl Fn(K) -> bool) {
+        self.poll_input(|button| keymap.is_down(button, &is_key_down));
+    }
+
+    /// Sets a function to be called with each SGB command the game sends, or stops calling it if `callback` is `None`
+    ///
+    /// Commands are handed over at the end of each frame from `next_frame` or `run_for`, in the order they were sent.
+    /// Without a callback, they pile up in `Mmu::sgb` until they're taken from there
+    #[cfg(feature = "sgb")]
+    pub fn set_sgb_callback(&mut self, callback: Option<SgbCallback>) {
+        self.sgb_callback = callback;
+    }
+
+    /// Checks if the game has sent an SGB border, so a frontend can size its window for `sgb_frame` instead of `frame`
+    ///
+    /// Cold resets and swapping cartridges clear the border
+    #[cfg(feature = "sgb")]
+    pub fn has_sgb_border(&self) -> bool {
+        self.sgb_border.is_set()
+    }
+
+    /// The last finished frame with the SGB border around it, `SGB_WIDTH` by `SGB_HEIGHT` in the same format as `frame`
+    ///
+    /// The border is read from VRAM at the end of the frame after the game sends `ChrTrn` or `PctTrn`, and redrawn
+    /// around every frame after that
+    ///
+    /// ### Return Variants
+    /// - `Some(frame)` once the game has sent a border
+    /// - `None` if there isn't one, so `frame` is all there is to show
+    #[cfg(feature = "sgb")]
+    pub fn sgb_frame(&self) -> Option<&[u32]> {
+        self.sgb_border.is_set().then_some(&self.sgb_frame[..])
     }
 
     /// Reads the serial buffer
@@ -46,3 +714,378 @@ impl Gbc {
     //     fb.swap_with_slice(&mut self.cpu.ppu.fb);
     // }
 }
+
+impl Drop for Gbc {
+    fn drop(&mut self) {
+        if let Some(ref path) = self.save_path {
+            // there's nowhere to report this, and panicking in drop is worse than losing the save
+            let _ = self.save_to_path(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cpu::CLOCK_HZ,
+        error::Error,
+        joypad::Button,
+        memory::{mbc::MbcSelector, Interrupt, MemoryInit, ResetKind, IE, JOYP, IF, TMA},
+        ppu::TICKS_PER_FRAME,
+    };
+
+    use std::{
+        io::{self, Write},
+        sync::{Arc, Mutex},
+    };
+
+    use super::Gbc;
+
+    fn init() -> Gbc {
+        let mut rom = vec![0; 0x8000];
+        // jr -2, spins forever
+        rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+
+        let mut gbc = Gbc::new(MbcSelector::NoMbc, false, true);
+        gbc.load_rom(&rom);
+
+        gbc
+    }
+
+    #[test]
+    fn new_headless() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+
+        let mut gbc = Gbc::new_headless(&rom).unwrap();
+        let frame = gbc.next_frame().unwrap();
+
+        assert_eq!(frame.len(), 160 * 144);
+        assert_eq!(gbc.cpu.regs.pc, 0x100);
+    }
+
+    #[test]
+    fn bad_roms() {
+        let mut rom = vec![0; 0x8000];
+
+        assert!(matches!(Gbc::new_headless(&rom[..0x100]), Err(Error::RomTooShort(0x100))));
+
+        // MBC3 isn't supported
+        rom[0x147] = 0x13;
+        assert!(matches!(Gbc::new_headless(&rom), Err(Error::UnsupportedMbc(0x13))));
+
+        // MBC2 tops out at 16 banks
+        rom[0x147] = 0x05;
+        rom[0x148] = 0x04;
+        assert!(matches!(Gbc::new_headless(&rom), Err(Error::UnsupportedRomSize(0x04))));
+
+        // a bank more than the header says
+        rom[0x147] = 0x00;
+        rom[0x148] = 0x00;
+        rom.extend([0; 0x4000]);
+        assert!(matches!(Gbc::new_headless(&rom), Err(Error::RomTooLarge(0xC000))));
+    }
+
+    #[test]
+    fn press_release() {
+        let mut gbc = init();
+
+        // select the action buttons
+        gbc.cpu.memory.set(JOYP, 0x10);
+        gbc.cpu.memory.set(IF, 0);
+
+        gbc.press(Button::Start);
+        assert_eq!(gbc.cpu.memory.load(IF), Some(1 << 4));
+        assert!(gbc.buttons().is_pressed(Button::Start));
+
+        // holding it down some more doesn't ask for another interrupt
+        gbc.cpu.memory.set(IF, 0);
+        gbc.press(Button::Start);
+        assert_eq!(gbc.cpu.memory.load(IF), Some(0));
+
+        gbc.press(Button::A);
+        assert_eq!(gbc.cpu.memory.load(IF), Some(1 << 4));
+        assert_eq!(gbc.buttons().pressed().collect::<Vec<_>>(), [Button::A, Button::Start]);
+
+        gbc.cpu.memory.set(IF, 0);
+        gbc.release(Button::Start);
+        gbc.release(Button::A);
+        assert_eq!(gbc.buttons().bits(), 0);
+        assert_eq!(gbc.cpu.memory.load(IF), Some(0));
+    }
+
+    #[test]
+    fn reset() {
+        let mut gbc = init();
+        gbc.next_frame().unwrap();
+
+        gbc.cpu.memory.set(0xC000, 0x12);
+        gbc.cpu.memory.set(0x8000, 0x34);
+        gbc.cpu.memory.set(TMA, 0x56);
+        gbc.cpu.regs.a = 0x78;
+
+        gbc.reset(ResetKind::Warm);
+        assert_eq!(gbc.cpu.regs.pc, 0x100);
+        assert_eq!(gbc.cpu.regs.a, 0x01);
+        assert_eq!(gbc.cpu.ticks(), 0);
+        assert_eq!(gbc.cpu.memory.load(TMA), Some(0x00));
+        assert_eq!(gbc.cpu.memory.load(0xC000), Some(0x12));
+        assert_eq!(gbc.cpu.memory.load(0x8000), Some(0x34));
+        // the game still runs from the top
+        assert_eq!(gbc.cpu.memory.load(0x100), Some(0x18));
+
+        gbc.reset(ResetKind::Cold);
+        assert_eq!(gbc.cpu.memory.load(0xC000), None);
+        assert_eq!(gbc.cpu.memory.load(0x8000), None);
+        assert_eq!(gbc.cpu.memory.load(0x100), Some(0x18));
+
+        // memory gets filled again if the system was made that way
+        let mut rom = vec![0; 0x8000];
+        rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+        let mut gbc = Gbc::new_with_memory_init(&rom, MemoryInit::Pattern(0xAB)).unwrap();
+        gbc.cpu.memory.set(0xC000, 0x12);
+
+        gbc.reset(ResetKind::Cold);
+        assert_eq!(gbc.cpu.memory.load(0xC000), Some(0xAB));
+        assert_eq!(gbc.next_frame().unwrap().len(), 160 * 144);
+    }
+
+    #[test]
+    fn trace() {
+        /// Hands out what's been written after the writer is moved into the system
+        #[derive(Clone, Default)]
+        struct Shared(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut gbc = init();
+        let out = Shared::default();
+        gbc.set_trace(Some(Box::new(out.clone())));
+
+        gbc.step().0.unwrap();
+        gbc.step().0.unwrap();
+        gbc.set_trace(None);
+        gbc.step().0.unwrap();
+
+        let log = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = log.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:18,FE,00,00 ; JR $0100");
+        assert_eq!(lines[0], lines[1]);
+
+        // only the stop itself is written, not every cycle spent stopped
+        let out = Shared::default();
+        gbc.cpu.memory.set(IE, 0);
+        gbc.cpu.memory.splice(0x100, &[0x10, 0x00]);
+        gbc.set_trace(Some(Box::new(out.clone())));
+
+        for _ in 0..10 {
+            gbc.step().0.unwrap();
+        }
+
+        assert!(gbc.cpu.state().stopped);
+        assert_eq!(out.0.lock().unwrap().iter().filter(|&&b| b == b'\n').count(), 1);
+    }
+
+    #[test]
+    fn record_replay() {
+        let mut gbc = init();
+        gbc.start_recording();
+
+        gbc.next_frame().unwrap();
+        gbc.press(Button::A);
+        gbc.next_frame().unwrap();
+        gbc.press(Button::Down);
+        gbc.release(Button::A);
+        gbc.next_frame().unwrap();
+
+        let log = gbc.stop_recording();
+        let expected: Vec<_> = log.frames().iter().map(|buttons| buttons.pressed().collect::<Vec<_>>()).collect();
+        assert_eq!(expected, [vec![], vec![Button::A], vec![Button::Down]]);
+
+        let mut replay = init();
+        replay.play(log.clone());
+
+        for frame in log.frames() {
+            assert!(replay.is_replaying());
+            assert_eq!(replay.buttons(), *frame);
+            replay.next_frame().unwrap();
+        }
+
+        assert!(!replay.is_replaying());
+        assert_eq!(replay.buttons().pressed().collect::<Vec<_>>(), [Button::Down]);
+    }
+
+    #[test]
+    fn run_for() {
+        let mut gbc = init();
+        let mut frames = 0;
+        let mut cycles = 0;
+
+        // a bit more than a frame, in slices that don't line up with it
+        while cycles < TICKS_PER_FRAME * 4 + 1000 {
+            let outcome = gbc.run_for(1000).unwrap();
+
+            // jr takes 3 M-cycles, so slices can overshoot by up to 2
+            assert!((1000..=1000 + 8).contains(&outcome.cycles));
+
+            frames += outcome.frame_completed as usize;
+            cycles += outcome.cycles as usize;
+        }
+
+        assert_eq!(frames, 1);
+        assert_eq!(gbc.frame_count(), 1);
+    }
+
+    #[test]
+    fn skip_idle_deadline() {
+        let mut gbc = init();
+        gbc.cpu.skip_idle = true;
+        // nothing is enabled in IE, so the loop never ends
+        gbc.cpu.regs.ime = true;
+
+        // skipping still stops at the end of the slice
+        for _ in 0..100 {
+            let outcome = gbc.run_for(1000).unwrap();
+            assert!((1000..=1000 + 8).contains(&outcome.cycles));
+        }
+
+        // and at the end of the frame
+        let end = gbc.frame_end;
+        gbc.next_frame().unwrap();
+        assert!(gbc.cpu.ticks() - end < 3);
+    }
+
+    #[test]
+    fn instruction_hook() {
+        let mut gbc = init();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let hook_seen = Arc::clone(&seen);
+        gbc.set_instruction_hook(Some(Box::new(move |pc, opcode| hook_seen.lock().unwrap().push((pc, opcode)))));
+
+        gbc.step().0.unwrap();
+        gbc.step().0.unwrap();
+        gbc.set_instruction_hook(None);
+        gbc.step().0.unwrap();
+
+        assert_eq!(*seen.lock().unwrap(), [(0x100, 0x18), (0x100, 0x18)]);
+    }
+
+    #[test]
+    fn step_instruction() {
+        let mut gbc = init();
+        gbc.cpu.regs.pc = 0x100;
+        gbc.cpu.regs.ime = true;
+        gbc.cpu.memory.set(IE, 1);
+        gbc.cpu.memory.set(IF, 0);
+
+        let info = gbc.step_instruction().unwrap();
+        assert_eq!((info.pc, info.opcode, info.cycles, info.next_pc), (0x100, 0x18, 12, 0x100));
+        assert!(info.interrupt.is_none());
+
+        // vblank is serviced straight after jr, as part of the same step
+        gbc.cpu.memory.set(IF, 1);
+        let info = gbc.step_instruction().unwrap();
+        assert_eq!(info.interrupt, Some(Interrupt::VBlank));
+        assert_eq!((info.cycles, info.next_pc), (12 + 20, 0x40));
+    }
+
+    #[test]
+    fn double_speed_frames() {
+        let mut gbc = init();
+        assert_eq!(gbc.clock_hz(), CLOCK_HZ);
+
+        gbc.cpu.double_speed = true;
+        assert_eq!(gbc.clock_hz(), CLOCK_HZ * 2);
+
+        // the first frame was already scheduled at normal speed
+        gbc.next_frame().unwrap();
+        let start = gbc.cpu.ticks();
+        let ly = gbc.cpu.ppu.coords.y;
+
+        gbc.next_frame().unwrap();
+
+        // twice the M-cycles, but the ppu still moves one frame
+        assert!((TICKS_PER_FRAME * 2..TICKS_PER_FRAME * 2 + 3).contains(&(gbc.cpu.ticks() - start)));
+        assert_eq!(gbc.cpu.ppu.coords.y, ly);
+    }
+
+    #[test]
+    fn insert_cartridge() {
+        // mbc1 + ram + battery, 32KiB rom, 8KiB ram
+        let mut first = vec![0; 0x8000];
+        first[0x147] = 0x03;
+        first[0x149] = 0x02;
+        // jr -2
+        first[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+
+        let mut second = first.clone();
+        second[0x100] = 0x00;
+
+        let mut gbc = Gbc::new_headless(&first).unwrap();
+        gbc.next_frame().unwrap();
+        gbc.cpu.memory.set(0x0000, 0x0A);
+        gbc.cpu.memory.set(0xA000, 0x42);
+
+        // a bad header leaves the old cartridge in, without flushing anything
+        let mut bad = second.clone();
+        bad[0x147] = 0xFF;
+        assert!(gbc.insert_cartridge(&bad, None, |_| panic!("flushed")).is_err());
+        assert_eq!(gbc.cpu.memory.load(0xA000), Some(0x42));
+
+        let mut flushed = Vec::new();
+        let mut save = vec![0; 0x2000];
+        save[0] = 0x24;
+
+        gbc.insert_cartridge(&second, Some(&save), |ram| flushed = ram.to_vec()).unwrap();
+        assert_eq!(flushed.len(), 0x2000);
+        assert_eq!(flushed[0], 0x42);
+
+        // the new game starts from the top, with its own save
+        assert_eq!(gbc.cpu.regs.pc, 0x100);
+        assert_eq!(gbc.cpu.memory.load(0x100), Some(0x00));
+        gbc.cpu.memory.set(0x0000, 0x0A);
+        assert_eq!(gbc.cpu.memory.load(0xA000), Some(0x24));
+    }
+
+    #[test]
+    fn battery_save() {
+        let dir = std::env::temp_dir().join(format!("gbc-battery-save-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let rom_path = dir.join("game.gb");
+        let save_path = dir.join("game.sav");
+
+        // mbc1 + ram + battery, 32KiB rom, 8KiB ram
+        let mut rom = vec![0; 0x8000];
+        rom[0x147] = 0x03;
+        rom[0x149] = 0x02;
+        std::fs::write(&rom_path, &rom).unwrap();
+
+        let mut save = vec![0; 0x2000];
+        save[0] = 0x42;
+        std::fs::write(&save_path, &save).unwrap();
+
+        let mut gbc = Gbc::from_path(&rom_path).unwrap();
+
+        // the save is loaded, and ram changes go back into it on drop
+        gbc.cpu.memory.set(0x0000, 0x0A);
+        assert_eq!(gbc.cpu.memory.load(0xA000), Some(0x42));
+        gbc.cpu.memory.set(0xA001, 0x24);
+        drop(gbc);
+
+        let save = std::fs::read(&save_path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(save.len(), 0x2000);
+        assert_eq!(&save[0..2], &[0x42, 0x24]);
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    error::Error,
+    gameboy::Gbc,
+    joypad::Button,
+    memory::ResetKind,
+    ppu::{SCREEN_HEIGHT, SCREEN_WIDTH},
+};
+
+/// Something for the emulation thread to do, sent with `EmulatorHandle::send`
+pub enum Command {
+    /// Swaps in a different system, like one made from a newly picked ROM. The old one is dropped on the emulation thread,
+    /// which saves its battery RAM if it has a save path
+    Load(Box<Gbc>),
+    Press(Button),
+    Release(Button),
+    /// Stops running frames until `Resume`. Commands are still handled while paused
+    Pause,
+    Resume,
+    Reset(ResetKind),
+    /// Runs a function on the system between frames, for anything there isn't a command for, like reading out save RAM
+    With(Box<dyn FnOnce(&mut Gbc) + Send>),
+    /// Stops the emulation thread, which also happens when the handle is dropped
+    Quit,
+}
+
+/// Frames shared between the emulation thread and the handle
+struct Shared {
+    /// The last finished frame, in the same format as `Gbc::frame`
+    frame: Mutex<Vec<u32>>,
+    /// Number of frames finished so far
+    frames: AtomicU64,
+}
+
+/// Runs a `Gbc` on its own thread, controlled through a command channel
+///
+/// The emulation thread owns the system, and only touches it between frames. Each frame it handles every command that's
+/// been sent, in order, then runs the frame with `Gbc::next_frame` and copies the result into a buffer shared with the handle.
+/// That copy is the only thing behind a lock, so the frame being drawn is never seen half finished, and reading the latest
+/// one with `copy_frame` only waits for a copy at worst.
+///
+/// Frames are run as fast as the thread can go unless the system has a limit from `Gbc::set_frame_limit`, which can also be
+/// changed while it's running with `Command::With`. The thread stops when the handle is dropped, when `Command::Quit` is sent, or if the CPU errors
+pub struct EmulatorHandle {
+    commands: Sender<Command>,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<Result<(), Error>>>,
+}
+
+impl EmulatorHandle {
+    /// Moves `gbc` onto a new thread and starts running it
+    pub fn spawn(gbc: Gbc) -> Self {
+        let (commands, receiver) = mpsc::channel();
+        let shared = Arc::new(Shared {
+            frame: Mutex::new(vec![0; SCREEN_WIDTH * SCREEN_HEIGHT]),
+            frames: AtomicU64::new(0),
+        });
+
+        let thread_shared = Arc::clone(&shared);
+        let thread = thread::spawn(move || run(gbc, receiver, &thread_shared));
+
+        Self {
+            commands,
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    /// Sends `command` to the emulation thread, which handles it before the next frame
+    ///
+    /// ### Return Variants
+    /// - `true` if the command was sent
+    /// - `false` if the emulation thread has already stopped
+    pub fn send(&self, command: Command) -> bool {
+        self.commands.send(command).is_ok()
+    }
+
+    /// Number of frames finished so far, which goes up by one every time a new frame is ready
+    pub fn frame_number(&self) -> u64 {
+        self.shared.frames.load(Ordering::Acquire)
+    }
+
+    /// Copies the last finished frame into `out` and returns its frame number
+    ///
+    /// ### Panic Conditions
+    /// - `out` isn't `SCREEN_WIDTH * SCREEN_HEIGHT` long
+    pub fn copy_frame(&self, out: &mut [u32]) -> u64 {
+        let frame = self.shared.frame.lock().unwrap_or_else(|e| e.into_inner());
+        out.copy_from_slice(&frame);
+
+        self.frame_number()
+    }
+
+    /// Checks if the emulation thread has stopped
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    /// Stops the emulation thread and waits for it to finish
+    ///
+    /// ### Return Variants
+    /// - `Ok(())` if the thread stopped because it was told to
+    /// - `Err(Error::Cpu)` if it stopped early because the CPU errored
+    pub fn join(mut self) -> Result<(), Error> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> Result<(), Error> {
+        let Some(thread) = self.thread.take() else {
+            return Ok(());
+        };
+
+        let _ = self.commands.send(Command::Quit);
+
+        match thread.join() {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+impl Drop for EmulatorHandle {
+    fn drop(&mut self) {
+        // there's nowhere to report an error from here, use `join` to see it
+        if !thread::panicking() {
+            let _ = self.stop();
+        }
+    }
+}
+
+/// The emulation thread's main loop
+fn run(mut gbc: Gbc, commands: Receiver<Command>, shared: &Shared) -> Result<(), Error> {
+    let mut paused = false;
+
+    loop {
+        // wait for something to do while paused, otherwise just take what's there
+        let command = if paused {
+            match commands.recv() {
+                Ok(command) => command,
+                Err(_) => return Ok(()),
+            }
+        } else {
+            match commands.try_recv() {
+                Ok(command) => command,
+                Err(TryRecvError::Empty) => {
+                    let frame = gbc.next_frame()?;
+                    // the count goes up while the lock is held, so it always matches the frame `copy_frame` sees
+                    let mut shared_frame = shared.frame.lock().unwrap_or_else(|e| e.into_inner());
+                    shared_frame.copy_from_slice(frame);
+                    shared.frames.fetch_add(1, Ordering::Release);
+
+                    continue;
+                }
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+        };
+
+        match command {
+            Command::Load(new) => gbc = *new,
+            Command::Press(button) => gbc.press(button),
+            Command::Release(button) => gbc.release(button),
+            Command::Pause => paused = true,
+            Command::Resume => paused = false,
+            Command::Reset(kind) => gbc.reset(kind),
+            Command::With(f) => f(&mut gbc),
+            Command::Quit => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use crate::{
+        gameboy::Gbc,
+        joypad::Button,
+        ppu::{SCREEN_HEIGHT, SCREEN_WIDTH},
+    };
+
+    use super::{Command, EmulatorHandle};
+
+    fn spin_rom() -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        // jr -2, spins forever
+        rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+
+        rom
+    }
+
+    #[test]
+    fn commands_and_frames() {
+        fn is_send<T: Send>() {}
+        is_send::<Gbc>();
+
+        let handle = EmulatorHandle::spawn(Gbc::new_headless(&spin_rom()).unwrap());
+        handle.send(Command::Press(Button::A));
+
+        let (sender, receiver) = mpsc::channel();
+        handle.send(Command::With(Box::new(move |gbc| sender.send(gbc.buttons()).unwrap())));
+        assert!(receiver.recv().unwrap().is_pressed(Button::A));
+
+        while handle.frame_number() < 2 {
+            std::thread::yield_now();
+        }
+
+        let mut frame = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT];
+        assert!(handle.copy_frame(&mut frame) >= 2);
+
+        // nothing gets run while paused
+        handle.send(Command::Pause);
+        let (sender, receiver) = mpsc::channel();
+        handle.send(Command::With(Box::new(move |_| sender.send(()).unwrap())));
+        receiver.recv().unwrap();
+
+        let paused_at = handle.frame_number();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!(handle.frame_number(), paused_at);
+
+        assert!(handle.join().is_ok());
+    }
+}
@@ -0,0 +1,154 @@
+/// End of the cartridge header (inclusive)
+pub const HEADER_END: usize = 0x014F;
+
+const TITLE: usize = 0x0134;
+const TITLE_END: usize = 0x0143;
+const NEW_LICENSEE: usize = 0x0144;
+const OLD_LICENSEE: usize = 0x014B;
+const CGB_FLAG: usize = 0x0143;
+const CARTRIDGE_TYPE: usize = 0x0147;
+const HEADER_CHECKSUM: usize = 0x014D;
+const GLOBAL_CHECKSUM: usize = 0x014E;
+
+// cartridge types that keep their ram powered with a battery
+const BATTERY_TYPES: [u8; 11] = [0x03, 0x06, 0x09, 0x0D, 0x0F, 0x10, 0x13, 0x1B, 0x1E, 0x22, 0xFF];
+
+/// The interesting parts of the cartridge header ($0100-$014F)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    /// Raw title bytes, including the CGB flag on newer cartridges
+    pub title: [u8; 16],
+    pub cgb_flag: u8,
+    pub old_licensee: u8,
+    pub new_licensee: [u8; 2],
+    /// Which MBC the cartridge uses, and whether it has RAM or a battery
+    pub cartridge_type: u8,
+}
+
+impl Header {
+    /// Reads the header out of `rom`
+    ///
+    /// ### Panic Conditions
+    /// - `rom` is shorter than the header (`0x150` bytes)
+    pub fn from_rom(rom: &[u8]) -> Self {
+        let mut title = [0; 16];
+        title.copy_from_slice(&rom[TITLE..=TITLE_END]);
+
+        Self {
+            title,
+            cgb_flag: rom[CGB_FLAG],
+            old_licensee: rom[OLD_LICENSEE],
+            new_licensee: [rom[NEW_LICENSEE], rom[NEW_LICENSEE + 1]],
+            cartridge_type: rom[CARTRIDGE_TYPE],
+        }
+    }
+
+    /// Checks if the game has CGB features, either as CGB-only or DMG compatible
+    pub fn is_cgb(&self) -> bool {
+        self.cgb_flag & 0x80 > 0
+    }
+
+    /// Checks if the game was published by Nintendo
+    ///
+    /// The CGB boot ROM only colorizes Nintendo games by title, everything else gets the default palette
+    pub fn is_nintendo(&self) -> bool {
+        match self.old_licensee {
+            0x01 => true,
+            // 0x33 means the new licensee code should be used instead
+            0x33 => &self.new_licensee == b"01",
+            _ => false,
+        }
+    }
+
+    /// Checks if the cartridge RAM is battery backed, meaning it should be saved between sessions
+    pub fn has_battery(&self) -> bool {
+        BATTERY_TYPES.contains(&self.cartridge_type)
+    }
+
+    /// Sum of all title bytes, used by the CGB boot ROM to pick a palette for DMG games
+    pub fn title_checksum(&self) -> u8 {
+        self.title.iter().fold(0, |acc, &byte| acc.wrapping_add(byte))
+    }
+
+    /// Checks the header checksum at $014D against the bytes from $0134-$014C
+    ///
+    /// This is the one the boot ROM checks, and it locks up instead of starting the game if it doesn't match.
+    /// A `rom` too short to have a header never matches
+    pub fn verify_header_checksum(rom: &[u8]) -> bool {
+        let Some(header) = rom.get(TITLE..=HEADER_CHECKSUM) else {
+            return false;
+        };
+
+        let (&expected, bytes) = header.split_last().unwrap();
+        let checksum = bytes.iter().fold(0u8, |acc, &byte| acc.wrapping_sub(byte).wrapping_sub(1));
+
+        checksum == expected
+    }
+
+    /// Checks the global checksum at $014E-$014F, stored big endian, against the sum of every other byte in `rom`
+    ///
+    /// Nothing on real hardware checks this one, so plenty of homebrew and patched ROMs get it wrong and still run.
+    /// A `rom` too short to have a header never matches
+    pub fn verify_global_checksum(rom: &[u8]) -> bool {
+        let Some(&[high, low]) = rom.get(GLOBAL_CHECKSUM..=HEADER_END) else {
+            return false;
+        };
+
+        let checksum = rom
+            .iter()
+            .enumerate()
+            .filter(|&(addr, _)| addr != GLOBAL_CHECKSUM && addr != HEADER_END)
+            .fold(0u16, |acc, (_, &byte)| acc.wrapping_add(byte as u16));
+
+        checksum == u16::from_be_bytes([high, low])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Header;
+
+    fn rom(title: &[u8], old_licensee: u8) -> Vec<u8> {
+        let mut rom = vec![0; 0x150];
+        rom[0x134..0x134 + title.len()].copy_from_slice(title);
+        rom[0x14B] = old_licensee;
+
+        rom
+    }
+
+    #[test]
+    fn title_checksum() {
+        let header = Header::from_rom(&rom(b"TETRIS", 0x01));
+
+        assert_eq!(header.title_checksum(), 0xDB);
+        assert!(header.is_nintendo());
+        assert!(!header.is_cgb());
+    }
+
+    #[test]
+    fn checksums() {
+        let mut data = rom(b"TETRIS", 0x01);
+        assert!(!Header::verify_header_checksum(&data));
+
+        data[0x14D] = 0x0B;
+        assert!(Header::verify_header_checksum(&data));
+
+        // every byte but the global checksum itself, so the title, licensee and header checksum
+        data[0x14E..0x150].copy_from_slice(&[0x01, 0xE7]);
+        assert!(Header::verify_global_checksum(&data));
+
+        // bytes past the header count too
+        data.push(0x01);
+        assert!(!Header::verify_global_checksum(&data));
+        assert!(!Header::verify_header_checksum(&data[..0x14D]));
+    }
+
+    #[test]
+    fn new_licensee() {
+        let mut data = rom(b"GAME", 0x33);
+        assert!(!Header::from_rom(&data).is_nintendo());
+
+        data[0x144..0x146].copy_from_slice(b"01");
+        assert!(Header::from_rom(&data).is_nintendo());
+    }
+}
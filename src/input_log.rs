@@ -0,0 +1,84 @@
+use crate::joypad::ButtonState;
+
+/// Bytes at the start of a saved input log
+const MAGIC: &[u8; 4] = b"GBIL";
+
+/// The buttons held during each frame of a run, for replaying it exactly
+///
+/// Recorded with `Gbc::start_recording` and played back with `Gbc::play`. Starting from the same ROM and memory init
+/// (see `Gbc::new_with_memory_init`), a replay goes through exactly the same frames as the original run
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InputLog {
+    frames: Vec<ButtonState>,
+}
+
+impl InputLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Buttons held during each frame, in order
+    pub fn frames(&self) -> &[ButtonState] {
+        &self.frames
+    }
+
+    /// Number of frames in the log
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Adds a frame to the end of the log
+    pub fn push(&mut self, buttons: ButtonState) {
+        self.frames.push(buttons);
+    }
+
+    /// Turns the log into bytes for saving, which is `GBIL` followed by one button bitmask per frame
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(self.frames.iter().map(|buttons| buttons.bits()));
+
+        bytes
+    }
+
+    /// Reads a log saved by `to_bytes`
+    ///
+    /// ### Return Variants
+    /// - `Some(log)` if `bytes` starts with `GBIL`
+    /// - `None` if it doesn't, so it can't be an input log
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let frames = bytes.strip_prefix(MAGIC)?;
+
+        Some(Self {
+            frames: frames.iter().map(|&bits| ButtonState::from_bits(bits)).collect(),
+        })
+    }
+}
+
+impl FromIterator<ButtonState> for InputLog {
+    fn from_iter<I: IntoIterator<Item = ButtonState>>(iter: I) -> Self {
+        Self {
+            frames: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::joypad::ButtonState;
+
+    use super::InputLog;
+
+    #[test]
+    fn bytes_round_trip() {
+        let log: InputLog = [0x00, 0x81, 0xFF].into_iter().map(ButtonState::from_bits).collect();
+        let bytes = log.to_bytes();
+
+        assert_eq!(bytes, b"GBIL\x00\x81\xFF");
+        assert_eq!(InputLog::from_bytes(&bytes), Some(log));
+        assert_eq!(InputLog::from_bytes(b"GBI"), None);
+    }
+}
@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+
+use crate::bits::bit_set;
+
+/// The two groups of buttons `JOYP` can select between
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ButtonGroup {
+    /// Selected when bit 4 of `JOYP` is low
+    Direction,
+    /// Selected when bit 5 of `JOYP` is low
+    Action,
+}
+
+/// Buttons on the Game Boy
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl Button {
+    /// Every button, in the order of their bits in the joypad state
+    pub const ALL: [Button; 8] = [
+        Button::Right,
+        Button::Left,
+        Button::Up,
+        Button::Down,
+        Button::A,
+        Button::B,
+        Button::Select,
+        Button::Start,
+    ];
+
+    /// Bit of this button in the joypad state
+    ///
+    /// The low nibble is the d-pad, the high nibble is the action buttons
+    fn mask(self) -> u8 {
+        match self {
+            Button::Right => 1 << 0,
+            Button::Left => 1 << 1,
+            Button::Up => 1 << 2,
+            Button::Down => 1 << 3,
+            Button::A => 1 << 4,
+            Button::B => 1 << 5,
+            Button::Select => 1 << 6,
+            Button::Start => 1 << 7,
+        }
+    }
+
+    fn is_direction(self) -> bool {
+        self.mask() & 0x0F > 0
+    }
+
+    /// Which group this button is read from
+    pub fn group(self) -> ButtonGroup {
+        if self.is_direction() {
+            ButtonGroup::Direction
+        } else {
+            ButtonGroup::Action
+        }
+    }
+
+    /// Bit of `JOYP` (0-3) this button pulls low while its group is selected
+    pub fn joyp_bit(self) -> u8 {
+        self.mask().trailing_zeros() as u8 & 3
+    }
+
+    /// Gets the button that pulls `JOYP` bit `bit` low when `group` is selected
+    ///
+    /// ### Return Variants
+    /// - `Some(button)` if `bit` is 0-3
+    /// - `None` if it's any other bit
+    pub fn from_joyp_bit(group: ButtonGroup, bit: u8) -> Option<Self> {
+        if bit >= 4 {
+            return None;
+        }
+
+        let index = match group {
+            ButtonGroup::Direction => bit,
+            ButtonGroup::Action => bit + 4,
+        };
+
+        Some(Button::ALL[index as usize])
+    }
+}
+
+/// Whether each button is held, with one bit per button in the same order as `Button::ALL`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ButtonState(u8);
+
+impl ButtonState {
+    /// Nothing held
+    pub const NONE: Self = Self(0);
+
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Checks if `button` is held
+    pub fn is_pressed(self, button: Button) -> bool {
+        self.0 & button.mask() > 0
+    }
+
+    /// Gets every held button, in the order of `Button::ALL`
+    pub fn pressed(self) -> impl Iterator<Item = Button> {
+        Button::ALL.into_iter().filter(move |&button| self.is_pressed(button))
+    }
+}
+
+impl From<u8> for ButtonState {
+    fn from(bits: u8) -> Self {
+        Self(bits)
+    }
+}
+
+impl From<ButtonState> for u8 {
+    fn from(state: ButtonState) -> Self {
+        state.0
+    }
+}
+
+impl From<Button> for ButtonState {
+    fn from(button: Button) -> Self {
+        Self(button.mask())
+    }
+}
+
+impl FromIterator<Button> for ButtonState {
+    fn from_iter<I: IntoIterator<Item = Button>>(iter: I) -> Self {
+        Self(iter.into_iter().fold(0, |bits, button| bits | button.mask()))
+    }
+}
+
+/// Joypad input, mapped to `JOYP` ($FF00)
+#[derive(Clone, Copy, Debug)]
+pub struct Joypad {
+    /// Held buttons, bits are set while a button is held
+    pressed: u8,
+    /// P14 and P15 as last written to by the game
+    select: u8,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Self {
+            pressed: 0,
+            select: 0x30,
+        }
+    }
+
+    /// Sets whether `button` is being held
+    ///
+    /// Returns `true` if this is a new press of a button in a selected group,
+    /// meaning the joypad interrupt should be requested
+    pub fn set_button(&mut self, button: Button, pressed: bool) -> bool {
+        let mask = button.mask();
+        let was_pressed = self.pressed & mask > 0;
+
+        if pressed {
+            self.pressed |= mask;
+        } else {
+            self.pressed &= !mask;
+        }
+
+        let selected = if button.is_direction() {
+            !bit_set(self.select, 4)
+        } else {
+            !bit_set(self.select, 5)
+        };
+
+        pressed && !was_pressed && selected
+    }
+
+    /// Holds exactly the buttons in `state`, releasing everything else
+    ///
+    /// Returns `true` if any of them is a new press of a button in a selected group, the same as `set_button`
+    pub fn set_state(&mut self, state: ButtonState) -> bool {
+        let new = state.0 & !self.pressed;
+        self.pressed = state.0;
+
+        let mut selected = 0;
+
+        if !bit_set(self.select, 4) {
+            selected |= 0x0F;
+        }
+
+        if !bit_set(self.select, 5) {
+            selected |= 0xF0;
+        }
+
+        new & selected > 0
+    }
+
+    /// Checks if `button` is being held
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.pressed & button.mask() > 0
+    }
+
+    /// Gets every button that's being held
+    pub fn state(&self) -> ButtonState {
+        ButtonState(self.pressed)
+    }
+
+    /// Reads the value of `JOYP`
+    ///
+    /// Bits 6 and 7 are unused and always read `1`, and the low nibble is active low.
+    /// With both groups selected, a bit reads `0` if the button from either group is held,
+    /// and with neither selected the low nibble is all `1`s
+    pub fn read(&self) -> u8 {
+        let mut buttons = 0x0F;
+
+        if !bit_set(self.select, 4) {
+            // P14 low, d-pad selected
+            buttons &= !self.pressed & 0x0F;
+        }
+
+        if !bit_set(self.select, 5) {
+            // P15 low, action buttons selected
+            buttons &= !(self.pressed >> 4) & 0x0F;
+        }
+
+        0xC0 | self.select | buttons
+    }
+
+    /// Writes to `JOYP`. Only the selection bits (4 and 5) are writable
+    pub fn write(&mut self, value: u8) {
+        self.select = value & 0x30;
+    }
+}
+
+impl Default for Joypad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Frontend key bindings for the joypad
+///
+/// `K` is whatever key type the frontend's windowing library uses.
+/// Each key can only be bound to one button, so binding a key that's already in use moves it to the new button.
+/// There are no default bindings since the library can't know the frontend's keys,
+/// so a new or default keymap starts empty and the frontend binds every button itself
+#[derive(Clone, Debug)]
+pub struct KeyMap<K> {
+    bindings: HashMap<Button, K>,
+}
+
+impl<K: Copy + PartialEq> KeyMap<K> {
+    /// Creates a keymap with nothing bound
+    pub fn new() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// Binds `key` to `button`, replacing the previous binding of `button`
+    ///
+    /// If `key` was already bound to a different button, that button is unbound and returned
+    pub fn bind(&mut self, button: Button, key: K) -> Option<Button> {
+        let previous = self
+            .bindings
+            .iter()
+            .find(|&(&b, &k)| b != button && k == key)
+            .map(|(&b, _)| b);
+
+        if let Some(previous) = previous {
+            self.bindings.remove(&previous);
+        }
+
+        self.bindings.insert(button, key);
+        previous
+    }
+
+    /// Removes the binding for `button`, returning the key it was bound to
+    pub fn unbind(&mut self, button: Button) -> Option<K> {
+        self.bindings.remove(&button)
+    }
+
+    /// Gets the key bound to `button`
+    pub fn key(&self, button: Button) -> Option<K> {
+        self.bindings.get(&button).copied()
+    }
+
+    /// Checks if the key bound to `button` is held, according to `is_key_down`
+    ///
+    /// Unbound buttons are never held
+    pub fn is_down(&self, button: Button, is_key_down: impl Fn(K) -> bool) -> bool {
+        self.key(button).is_some_and(is_key_down)
+    }
+}
+
+impl<K: Copy + PartialEq> Default for KeyMap<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Button, ButtonGroup, ButtonState, Joypad, KeyMap};
+
+    #[test]
+    fn joyp_bits() {
+        for button in Button::ALL {
+            assert_eq!(Button::from_joyp_bit(button.group(), button.joyp_bit()), Some(button));
+        }
+
+        assert_eq!(Button::Start.joyp_bit(), 3);
+        assert_eq!(Button::from_joyp_bit(ButtonGroup::Direction, 2), Some(Button::Up));
+        assert_eq!(Button::from_joyp_bit(ButtonGroup::Action, 4), None);
+        assert_eq!(Button::from_joyp_bit(ButtonGroup::Action, 0xFF), None);
+    }
+
+    #[test]
+    fn set_state() {
+        let mut joypad = Joypad::new();
+        let state: ButtonState = [Button::A, Button::Down].into_iter().collect();
+        assert_eq!(u8::from(state), 0b0001_1000);
+
+        // nothing is selected, so there's no interrupt
+        assert!(!joypad.set_state(state));
+        assert_eq!(joypad.state(), state);
+
+        // a is already held, only new presses count
+        joypad.write(0x10);
+        assert!(!joypad.set_state(state));
+        assert!(joypad.set_state(Button::B.into()));
+        assert!(!joypad.is_pressed(Button::A));
+    }
+
+    #[test]
+    fn both_groups_selected() {
+        let mut joypad = Joypad::new();
+        joypad.set_button(Button::Right, true);
+        joypad.set_button(Button::B, true);
+
+        // right and b share a line with a and left, so both groups together pull bits 0 and 1 low
+        joypad.write(0x00);
+        assert_eq!(joypad.read(), 0xC0 | 0b1100);
+
+        joypad.write(0x10);
+        assert_eq!(joypad.read(), 0xD0 | 0b1101);
+        joypad.write(0x20);
+        assert_eq!(joypad.read(), 0xE0 | 0b1110);
+
+        joypad.write(0x30);
+        assert_eq!(joypad.read(), 0xFF);
+    }
+
+    #[test]
+    fn keymap_rebind() {
+        let mut keymap = KeyMap::new();
+
+        assert_eq!(keymap.bind(Button::A, 'x'), None);
+        assert_eq!(keymap.bind(Button::B, 'z'), None);
+
+        // 'x' moves from A to Start
+        assert_eq!(keymap.bind(Button::Start, 'x'), Some(Button::A));
+        assert_eq!(keymap.key(Button::A), None);
+        assert_eq!(keymap.key(Button::Start), Some('x'));
+
+        // rebinding a button to its own key does nothing
+        assert_eq!(keymap.bind(Button::B, 'z'), None);
+        assert_eq!(keymap.key(Button::B), Some('z'));
+    }
+
+    #[test]
+    fn keymap_is_down() {
+        let mut keymap = KeyMap::new();
+        keymap.bind(Button::Up, 'w');
+
+        assert!(keymap.is_down(Button::Up, |key| key == 'w'));
+        assert!(!keymap.is_down(Button::Up, |key| key == 's'));
+        assert!(!keymap.is_down(Button::Down, |_| true));
+
+        // nothing is bound by default
+        let keymap = KeyMap::<char>::default();
+        assert!(Button::ALL.iter().all(|&button| keymap.key(button).is_none()));
+    }
+}
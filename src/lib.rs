@@ -1,20 +1,62 @@
+mod bits;
 mod cpu;
+mod error;
 mod gameboy;
+mod handle;
+mod header;
+mod input_log;
+mod joypad;
+mod limiter;
 mod memory;
 mod ppu;
+mod rewind;
+#[cfg(feature = "sgb")]
+mod sgb;
 
-pub use gameboy::{Gbc, MBC_ADDR};
-pub use memory::{mbc::MbcSelector, mbc::RamSize, mbc::RomSize, Mmu};
-pub use cpu::{CpuStatus, CpuError, Instruction, CpuEvent, CpuReg, CpuFlag, Registers, IoRegs};
-pub use ppu::PpuStatus;
+pub use error::Error;
+pub use gameboy::{Gbc, InstructionHook, RunOutcome, StepInfo, MBC_ADDR};
+pub use handle::{Command, EmulatorHandle};
+pub use memory::{io, mbc::MbcSelector, mbc::RamSize, mbc::RomSize, mbc::{RomBankSource, SeekSource, ROM_BANK_SIZE}, io::{NrRegisters, WaveRam}, AccessKind, Cheat, CheatError, DiagnosticCallback, Hexdump, InvalidAccess, Interrupt, InterruptFlags, MemoryInit, Mmu, ResetKind};
+pub use cpu::{branch_cycles, disassemble, disassemble_range, opcode_cycles, CpuState, CpuStatus, CpuError, Instruction, CpuEvent, CpuReg, CpuFlag, Registers, IoRegs, CLOCK_HZ};
+pub use ppu::{
+    CompatPalette, PpuMode, PpuStatus, RenderMode, ScanlineState, BG_MAP_PIXELS, SPRITE_LIMIT, DOTS_PER_LINE, FRAME_RATE, MAX_TRANSFER_DOTS, MIN_TRANSFER_DOTS, OAM_SCAN_DOTS,
+    SCREEN_HEIGHT, SCREEN_WIDTH, TICKS_PER_FRAME, VBLANK_DOTS,
+};
+pub use header::Header;
+pub use input_log::InputLog;
+pub use rewind::Rewind;
+pub use joypad::{Button, ButtonGroup, ButtonState, Joypad, KeyMap};
+#[cfg(feature = "sgb")]
+pub use sgb::{SgbBorder, SgbCallback, SgbCommand, SgbPacket, SgbReceiver, SGB_HEIGHT, SGB_WIDTH, TRANSFER_LEN};
 
-pub fn get_mbc(rom: &[u8]) -> MbcSelector {
-    let rom_size = RomSize::from_byte(rom[0x0148]);
-    let ram_size = RamSize::from_byte(rom[0x0149]);
+/// Picks the MBC for `rom` from its header
+///
+/// ### Return Variants
+/// - `Ok(mbc)` if the MBC and sizes in the header are supported, and the ROM fits in them
+/// - `Err(Error::RomTooShort)` if the ROM ends before the header does
+/// - `Err(Error::RomTooLarge)` if the ROM is bigger than the header says
+/// - `Err(Error::UnsupportedMbc)`, `Err(Error::UnsupportedRomSize)` or `Err(Error::UnsupportedRamSize)` if the header has something that can't be emulated
+pub fn get_mbc(rom: &[u8]) -> Result<MbcSelector, Error> {
+    if rom.len() <= header::HEADER_END {
+        return Err(Error::RomTooShort(rom.len()));
+    }
+
+    let rom_size = RomSize::from_byte(rom[0x0148])?;
+    let ram_size = RamSize::from_byte(rom[0x0149])?;
     
-    match rom[MBC_ADDR] {
-        0x00 => MbcSelector::NoMbc,
-        0x01 => MbcSelector::Mbc1(rom_size, ram_size),
-        _ => panic!("Unsupported MBC"),
+    let mbc = match rom[MBC_ADDR] {
+        // 0x08 and 0x09 are rom + ram, which NoMbc always has room for
+        0x00 | 0x08 | 0x09 => MbcSelector::NoMbc,
+        0x01..=0x03 => MbcSelector::Mbc1(rom_size, ram_size),
+        0x05 | 0x06 => MbcSelector::Mbc2(rom_size),
+        kind => return Err(Error::UnsupportedMbc(kind)),
+    };
+
+    mbc.check_sizes(rom[0x0148], rom[0x0149])?;
+
+    if rom.len() > mbc.rom_capacity() {
+        return Err(Error::RomTooLarge(rom.len()));
     }
+
+    Ok(mbc)
 }
\ No newline at end of file
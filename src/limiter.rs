@@ -0,0 +1,123 @@
+use std::{
+    collections::VecDeque,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How far back `FpsCounter` looks
+const FPS_WINDOW: Duration = Duration::from_secs(1);
+
+/// Keeps frames from finishing faster than a set rate, see `Gbc::set_frame_limit`
+#[derive(Clone, Debug)]
+pub(crate) struct FrameLimiter {
+    period: Duration,
+    /// When the next frame is allowed to finish, `None` until the first one does
+    next: Option<Instant>,
+}
+
+impl FrameLimiter {
+    /// ### Panic Conditions
+    /// - `rate` isn't a positive, finite number of frames per second
+    pub(crate) fn new(rate: f64) -> Self {
+        assert!(rate.is_finite() && rate > 0.0, "Invalid frame limit: {rate}");
+
+        Self {
+            period: Duration::from_secs_f64(1.0 / rate),
+            next: None,
+        }
+    }
+
+    /// Sleeps until the next frame is due
+    ///
+    /// Deadlines are kept a fixed period apart instead of counting from when the frame finished, so time spent
+    /// sleeping too long is made up on the next frame. Falling more than a frame behind starts over from now,
+    /// rather than rushing through frames to catch up
+    pub(crate) fn wait(&mut self) {
+        let now = Instant::now();
+
+        let next = match self.next {
+            Some(next) if next > now => {
+                thread::sleep(next - now);
+                next + self.period
+            }
+            Some(next) if now - next <= self.period => next + self.period,
+            _ => now + self.period,
+        };
+
+        self.next = Some(next);
+    }
+}
+
+/// Measures how many frames are finishing per second of real time, see `Gbc::fps`
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FpsCounter {
+    /// When each frame in the last `FPS_WINDOW` finished, oldest first
+    frames: VecDeque<Instant>,
+}
+
+impl FpsCounter {
+    /// Notes that a frame finished at `now`, forgetting ones that are too old to count
+    pub(crate) fn record(&mut self, now: Instant) {
+        while self.frames.front().is_some_and(|&frame| now - frame > FPS_WINDOW) {
+            self.frames.pop_front();
+        }
+
+        self.frames.push_back(now);
+    }
+
+    /// Frames per second over the frames in the window
+    ///
+    /// This is `0.0` until at least 2 frames have finished, since there's nothing to measure between
+    pub(crate) fn fps(&self) -> f32 {
+        let (Some(first), Some(last)) = (self.frames.front(), self.frames.back()) else {
+            return 0.0;
+        };
+
+        let elapsed = (*last - *first).as_secs_f32();
+
+        if elapsed == 0.0 {
+            return 0.0;
+        }
+
+        (self.frames.len() - 1) as f32 / elapsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{FpsCounter, FrameLimiter};
+
+    #[test]
+    fn fps_window() {
+        let mut counter = FpsCounter::default();
+        let start = Instant::now();
+        assert_eq!(counter.fps(), 0.0);
+
+        // 10 frames 50ms apart is 20 per second
+        for frame in 0..10 {
+            counter.record(start + Duration::from_millis(50) * frame);
+        }
+
+        assert!((counter.fps() - 20.0).abs() < 0.01);
+
+        // after a long pause only the frames since then count
+        counter.record(start + Duration::from_secs(5));
+        counter.record(start + Duration::from_millis(5100));
+        assert!((counter.fps() - 10.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn paces_frames() {
+        let mut limiter = FrameLimiter::new(200.0);
+        let start = Instant::now();
+
+        // the first frame goes right away, then each one waits 5ms
+        for _ in 0..5 {
+            limiter.wait();
+        }
+
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+}
@@ -1,52 +1,41 @@
 //! TODO:
 //!     Abstract over checking IO registers
 
+use std::collections::VecDeque;
+
+use crate::joypad::{Button, ButtonState, Joypad};
+#[cfg(feature = "sgb")]
+use crate::sgb::SgbReceiver;
+
+pub use self::cheat::{Cheat, CheatError};
+pub use self::dump::Hexdump;
+pub use self::interrupt::{Interrupt, InterruptFlags};
+pub use self::io::*;
+pub use self::init::{MemoryInit, ResetKind};
+pub use self::palette::PaletteRam;
+pub use self::strict::{AccessKind, DiagnosticCallback, InvalidAccess};
+
 use self::{
     bank::{VramBank, WramBank},
     init::init_io,
-    mbc::{init_mbc, Mbc, MbcSelector},
+    mbc::{init_mbc, Mbc, MbcSelector, RomBankSource},
 };
 
 mod bank;
+mod cheat;
+mod dump;
 mod init;
+mod interrupt;
+mod palette;
+mod strict;
+pub mod io;
 pub mod mbc;
 
 /// Object memory
 pub const OAM: u16 = 0xFE00;
 pub const OAM_END: u16 = 0xFE9F;
-// Joypad input
-pub const JOYP: u16 = 0xFF00;
-/// Internal timer
-pub const DIV: u16 = 0xFF04;
-/// User facing timer
-pub const TIMA: u16 = 0xFF05;
-/// Timer modulo (TIMA resets to this)
-pub const TMA: u16 = 0xFF06;
-/// Timer control
-pub const TAC: u16 = 0xFF07;
-/// Interrupt flag
-pub const IF: u16 = 0xFF0F;
-/// LCD control
-pub const LCDC: u16 = 0xFF40;
-/// LCD status
-pub const STAT: u16 = 0xFF41;
-/// Vertical scroll
-pub const SCY: u16 = 0xFF42;
-/// Horizontal scroll
-pub const SCX: u16 = 0xFF43;
-/// Current scanline
-pub const LY: u16 = 0xFF44;
-/// OAM DMA source and start
-/// Writing a byte to this address copies $XX00-$XX9f into $FE00-$FE9F where XX is the byte
-pub const DMA: u16 = 0xFF46;
-/// DMG palette
-pub const BGP: u16 = 0xFF47;
-/// WRAM bank select
-pub const SVBK: u16 = 0xFF70;
 /// High RAM
 pub const HRAM: u16 = 0xFF80;
-/// Granular interrupt enable
-pub const IE: u16 = 0xFFFF;
 
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub(crate) enum MmuAddr {
@@ -60,9 +49,17 @@ pub(crate) enum MmuAddr {
     Ie,
 }
 
+// M-cycles to shift out a byte over serial with the internal clock, 8 bits at 8192Hz
+const SERIAL_CYCLES: u16 = 1024;
+// most sent bytes to hold on to before dropping the oldest
+const SERIAL_BUFFER_SIZE: usize = 0x1000;
+// tile data is 0x8000-0x97FF, the rest of vram is tile maps
+const TILE_DATA_END: u16 = 0x1800;
+
 /// Memory management unit
 ///
 /// The main interfaces of this structure are `Mmu::get()` and `Mmu::set()`
+#[derive(Clone)]
 pub struct Mmu {
     // 0000 - 7FFF
     // A000 - BFFF
@@ -82,6 +79,25 @@ pub struct Mmu {
     hram: [Option<u8>; 0x7F], // high ram, physically located within the cpu, can be used during DMA transfers
     // FFFF
     ie: u8, // interrupt enable register
+    pub joypad: Joypad,
+    /// CGB background palettes
+    pub bg_palettes: PaletteRam,
+    /// CGB object palettes
+    pub obj_palettes: PaletteRam,
+    cheats: Vec<Cheat>,
+    /// Set when tile data (0x8000-0x97FF in either bank) is written, see `Mmu::vram_dirty`
+    vram_dirty: bool,
+    /// M-cycles left in the current serial transfer, if there is one using the internal clock
+    serial_cycles: Option<u16>,
+    /// Bytes sent over serial that haven't been read by `Mmu::read_serial` yet
+    serial_out: VecDeque<u8>,
+    /// Super Game Boy packets sent through `JOYP`
+    #[cfg(feature = "sgb")]
+    pub sgb: SgbReceiver,
+    /// Catch accesses that are almost certainly bugs, see `Mmu::set_strict`
+    strict: bool,
+    /// Where invalid accesses go in strict mode, instead of stopping the CPU
+    diagnostics: Option<DiagnosticCallback>,
 }
 
 impl Mmu {
@@ -94,9 +110,36 @@ impl Mmu {
             io: init_io(),
             hram: [None; 0x7F],
             ie: 0,
+            joypad: Joypad::new(),
+            bg_palettes: PaletteRam::new(),
+            obj_palettes: PaletteRam::new(),
+            cheats: Vec::new(),
+            vram_dirty: true,
+            serial_cycles: None,
+            serial_out: VecDeque::new(),
+            #[cfg(feature = "sgb")]
+            sgb: SgbReceiver::new(),
+            strict: false,
+            diagnostics: None,
         }
     }
 
+    /// Makes a memory map with no cartridge in it, for testing the PPU without building a ROM
+    ///
+    /// The cartridge slot is a 32KiB ROM of zeros with no mapper, and WRAM, VRAM and OAM are all cleared, so nothing reads
+    /// as uninitialized. IO registers start at their power on values. Fill in tiles and objects with `poke` or `splice`,
+    /// and registers the PPU reads from memory, like `SCX` and `SCY`, with `set`.
+    ///
+    /// `LCDC` and the DMG palettes are copied into the `Ppu` when the CPU writes them, so without a CPU they need to be set
+    /// on it directly with `Ppu::set_lcdc`, `Ppu::set_palette` and `Ppu::set_obj_palette`
+    pub fn for_testing() -> Self {
+        let mut memory = Self::new(MbcSelector::NoMbc);
+        memory.load_rom(&[0; 0x8000]);
+        memory.init_memory(MemoryInit::Zero);
+
+        memory
+    }
+
     /// Translates a global memory address to an internally usable enum variant
     pub(crate) fn translate(addr: u16) -> MmuAddr {
         if addr < 0x8000 {
@@ -157,7 +200,15 @@ impl Mmu {
     /// - `None` if the selected cell is uninitialized
     pub fn load(&self, addr: u16) -> Option<u8> {
         match Self::translate(addr) {
-            MmuAddr::Mbc(a) => self.mbc.load(a),
+            MmuAddr::Mbc(a) => {
+                let value = self.mbc.load(a);
+
+                if addr < 0x8000 && !self.cheats.is_empty() {
+                    return self.patch_rom(addr, value);
+                }
+
+                value
+            }
             MmuAddr::Vram(a) => self.vram.load(a),
             MmuAddr::Wram(a) => self.wram.load(a),
             MmuAddr::Oam(a) => self.oam[a as usize],
@@ -167,7 +218,19 @@ impl Mmu {
                 Some(nibble | nibble >> 4)
             }
             MmuAddr::Io(a) => {
-                self.io[a as usize]
+                match addr {
+                    _ if io::is_unmapped(addr) => Some(0xFF),
+                    JOYP => Some(self.joypad.read()),
+                    BCPS => Some(self.bg_palettes.read_spec()),
+                    BCPD => Some(self.bg_palettes.read_data()),
+                    OCPS => Some(self.obj_palettes.read_spec()),
+                    OCPD => Some(self.obj_palettes.read_data()),
+                    // these start out cleared instead of random
+                    UNDOC_FF72..=UNDOC_FF75 => Some(self.io[a as usize].unwrap_or(0)),
+                    // there's no APU yet, so every channel is always silent
+                    PCM12 | PCM34 => Some(0),
+                    _ => self.io[a as usize],
+                }
             }
             MmuAddr::Hram(a) => self.hram[a as usize],
             MmuAddr::Ie => Some(self.ie),
@@ -178,24 +241,132 @@ impl Mmu {
         self.mbc.load_rom(data);
     }
 
+    /// Loads ROM banks from `source` only when they're switched to, instead of all at once like `load_rom`
+    ///
+    /// Up to `cache_banks` of the most recently used banks are kept in memory, and the rest are read again from `source`
+    /// when they're needed. If reading a bank fails, it reads as uninitialized until it's tried again
+    pub fn load_rom_streaming(&mut self, source: impl RomBankSource + 'static, cache_banks: usize) {
+        self.mbc.load_rom_streaming(Box::new(source), cache_banks);
+    }
+
+    /// Copies out the cartridge RAM, in the format used by `.sav` files
+    ///
+    /// That's every RAM bank back to back with nothing else around it, which is what other emulators use too, so saves
+    /// can be moved between them. MBC2 saves one byte per 4 bit cell. None of the supported MBCs have a clock, so there's
+    /// never an RTC footer, and `load_ram` ignores one on a save from somewhere else
+    pub fn save_ram(&self) -> Vec<u8> {
+        self.mbc.save_ram()
+    }
+
+    /// Restores the cartridge RAM from the contents of a `.sav` file
+    pub fn load_ram(&mut self, data: &[u8]) {
+        self.mbc.load_ram(data);
+    }
+
+    /// Turns strict mode on or off, for finding bugs in games and ROM hacks
+    ///
+    /// Normally, accesses like reading disabled cartridge RAM are handled the way hardware does, which is often quietly
+    /// giving `0xFF`. In strict mode, the CPU checks each of its accesses before making them, and any that `InvalidAccess`
+    /// lists are reported. They go to the callback from `set_diagnostics` if there is one, and emulation carries on.
+    /// Without a callback, the instruction still finishes the way it would on hardware, then the CPU stops with
+    /// `CpuError::InvalidAccess`.
+    ///
+    /// Only the CPU is checked, so DMA, the PPU and direct calls like `load` and `set` never report anything
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Sets a function to be called with each invalid access caught in strict mode, or stops calling it if `callback` is `None`
+    ///
+    /// This does nothing unless strict mode is on
+    pub fn set_diagnostics(&mut self, callback: Option<DiagnosticCallback>) {
+        self.diagnostics = callback;
+    }
+
+    /// Checks if a `kind` access to `addr` right now would be one of the ones strict mode catches, see `InvalidAccess`
+    ///
+    /// This only looks at the address and the cartridge's RAM enable, and works the same whether strict mode is on or not
+    pub fn is_invalid_access(&self, addr: u16, kind: AccessKind) -> bool {
+        match Self::translate(addr) {
+            MmuAddr::Mbc(a) if a >= 0xA000 => !self.mbc.ram_accessible(),
+            MmuAddr::Prohibited => true,
+            MmuAddr::Oam(_) | MmuAddr::Io(_) | MmuAddr::Ie => kind == AccessKind::Execute,
+            _ => false,
+        }
+    }
+
+    /// Hands an invalid access to the diagnostics callback
+    ///
+    /// ### Return Variants
+    /// - `Ok(())` if there's a callback, which has been called
+    /// - `Err(access)` if there isn't one, so the CPU should stop
+    pub(crate) fn report_invalid_access(&self, access: InvalidAccess) -> Result<(), InvalidAccess> {
+        match self.diagnostics {
+            Some(ref callback) => {
+                callback(&access);
+                Ok(())
+            }
+            None => Err(access),
+        }
+    }
+
     /// Sets the cell at address `addr` to the value stored in `value`
     ///
     /// ### Side Effects
     /// This method may have internal side effects, as listed below:
+    /// - If `addr` == `0xFF00`, the selected joypad button group will be changed, and with the `sgb` feature it may finish an SGB packet
+    /// - If `addr` == `0xFF69` or `0xFF6B`, the palette spec register will auto increment if it's set to
+    /// - If `addr` == `0xFF02` and bit 7 of `value` is set, a serial transfer will be started
     /// - If `addr` == `0xFF70`, the selected WRAM bank will be changed using the new value
     pub fn set(&mut self, addr: u16, value: u8) {
         match Self::translate(addr) {
             MmuAddr::Mbc(a) => self.mbc.set(a, value),
-            MmuAddr::Vram(a) => self.vram.set(a, value),
+            MmuAddr::Vram(a) => {
+                if a < TILE_DATA_END {
+                    self.vram_dirty = true;
+                }
+
+                self.vram.set(a, value)
+            }
             MmuAddr::Wram(a) => self.wram.set(a, value),
             MmuAddr::Oam(a) => self.oam[a as usize] = Some(value),
             MmuAddr::Prohibited => {}
             MmuAddr::Io(a) => {
+                match addr {
+                    _ if io::is_unmapped(addr) => return,
+                    JOYP => {
+                        #[cfg(feature = "sgb")]
+                        self.sgb.write(value);
+
+                        return self.joypad.write(value);
+                    }
+                    BCPS => return self.bg_palettes.write_spec(value),
+                    BCPD => return self.bg_palettes.write_data(value),
+                    OCPS => return self.obj_palettes.write_spec(value),
+                    OCPD => return self.obj_palettes.write_data(value),
+                    UNDOC_FF75 => return self.io[a as usize] = Some(value & 0b0111_0000),
+                    PCM12 | PCM34 => return,
+                    _ => {}
+                }
+
+                if addr == SC {
+                    self.start_serial(value);
+                }
+
                 if addr == SVBK {
                     // WRAM Bank Select
                     self.wram.select(value);
                 }
 
+                if addr == VBK {
+                    // VRAM Bank Select, only bit 0 is used
+                    self.vram.select(value & 1);
+                }
+
                 self.io[a as usize] = Some(value);
             }
             MmuAddr::Hram(a) => self.hram[a as usize] = Some(value),
@@ -203,14 +374,116 @@ impl Mmu {
         }
     }
 
+    /// Reads the byte at `addr` the way a debugger would, without going through anything a game's read would
+    ///
+    /// `load` is what the CPU sees, so cheats patch ROM reads and disabled cartridge RAM reads as `0xFF`. This reads what's
+    /// actually in the mapped bank instead: ROM without cheats applied, and cartridge RAM whether it's enabled or not.
+    /// Everything else reads the same as `load`. Uninitialized cells read as `0`, like `load_block`
+    pub fn peek(&self, addr: u16) -> u8 {
+        let value = match Self::translate(addr) {
+            MmuAddr::Mbc(a) if addr < 0x8000 => self.mbc.load(a),
+            MmuAddr::Mbc(a) => self.mbc.peek_ram(a),
+            _ => self.load(addr),
+        };
+
+        value.unwrap_or(0)
+    }
+
+    /// Writes `value` straight into whatever is backing `addr`, without any of the side effects of `set`
+    ///
+    /// `set` is a write from the game, so writing to ROM talks to the MBC and can switch banks, and IO writes can start
+    /// serial transfers or switch WRAM and VRAM banks. This is for debuggers and cheat tools editing memory instead:
+    /// - ROM and cartridge RAM are written in whichever banks are mapped, even if RAM is disabled
+    /// - IO registers just store `value`, so `SVBK` and `VBK` change without switching banks, and `SC` starts nothing
+    /// - `JOYP` only changes which button group is selected, and palette data is written without auto incrementing
+    /// - Writes to 0xFEA0-0xFEFF are ignored, since there's nothing there
+    ///
+    /// VRAM writes to tile data still set `vram_dirty`, since cached tiles are out of date either way
+    pub fn poke(&mut self, addr: u16, value: u8) {
+        match Self::translate(addr) {
+            MmuAddr::Mbc(a) if addr < 0x8000 => self.mbc.write_rom(a, value),
+            MmuAddr::Mbc(a) => self.mbc.poke_ram(a, value),
+            MmuAddr::Vram(a) => {
+                if a < TILE_DATA_END {
+                    self.vram_dirty = true;
+                }
+
+                self.vram.set(a, value)
+            }
+            MmuAddr::Wram(a) => self.wram.set(a, value),
+            MmuAddr::Oam(a) => self.oam[a as usize] = Some(value),
+            MmuAddr::Prohibited => {}
+            MmuAddr::Io(a) => match addr {
+                JOYP => self.joypad.write(value),
+                BCPS => self.bg_palettes.write_spec(value),
+                OCPS => self.obj_palettes.write_spec(value),
+                BCPD | OCPD => {
+                    let palettes = if addr == BCPD { &mut self.bg_palettes } else { &mut self.obj_palettes };
+                    // putting the spec back undoes the auto increment
+                    let spec = palettes.read_spec();
+                    palettes.write_data(value);
+                    palettes.write_spec(spec);
+                }
+                _ => self.io[a as usize] = Some(value),
+            },
+            MmuAddr::Hram(a) => self.hram[a as usize] = Some(value),
+            MmuAddr::Ie => self.ie = value,
+        }
+    }
+
     /// Splices a set of `values` into memory, starting at `start`
     pub fn splice(&mut self, start: u16, values: &[u8]) {
         for rel in 0..values.len() as u16 {
             let abs = rel.wrapping_add(start);
-            self.set(abs, values[rel as usize]);
+
+            // rom writes would go to the mbc registers, so they're put in directly instead
+            if abs < 0x8000 {
+                self.mbc.write_rom(abs, values[rel as usize]);
+            } else {
+                self.set(abs, values[rel as usize]);
+            }
         }
     }
 
+    /// Borrows the VRAM bank that's currently selected, for frontends that read tile data all at once
+    ///
+    /// These are the raw bytes from 0x8000-0x9FFF, not decoded tiles, with `None` for cells that were never written.
+    /// Reading them directly skips the address decoding `load` goes through for every byte
+    pub fn vram(&self) -> &[Option<u8>] {
+        self.vram_bank(self.vram.selected())
+    }
+
+    /// Same as `Mmu::vram`, but for either bank, whichever is selected
+    ///
+    /// ### Panic Conditions
+    /// - `bank` isn't `0` or `1`
+    pub fn vram_bank(&self, bank: u8) -> &[Option<u8>] {
+        assert!(bank <= 1, "Invalid VRAM bank: {bank}");
+
+        self.vram.bank(bank)
+    }
+
+    /// Borrows OAM, the raw bytes from 0xFE00-0xFE9F, 4 per object
+    ///
+    /// Uninitialized cells are `None`, the same as `Mmu::vram`
+    pub fn oam(&self) -> &[Option<u8>] {
+        &self.oam
+    }
+
+    /// Checks if tile data (0x8000-0x97FF) has been written since the last `clear_vram_dirty`, for renderers that cache
+    /// decoded tiles
+    ///
+    /// This is one flag for all of tile data in both banks, not one per tile. It starts out set, and filling or clearing
+    /// VRAM with `init_memory` or a cold reset sets it too. Writes that store the same value a cell already had still count
+    pub fn vram_dirty(&self) -> bool {
+        self.vram_dirty
+    }
+
+    /// Clears the flag from `vram_dirty`, usually right after the tiles have been decoded
+    pub fn clear_vram_dirty(&mut self) {
+        self.vram_dirty = false;
+    }
+
     /// Returns a block of memory
     ///
     /// `start` and `end` are inclusive
@@ -220,31 +493,213 @@ impl Mmu {
         (start..=end).map(|i| self.load(i).unwrap_or(0)).collect()
     }
 
-    /// Reads the serial value from SB if SC.7 is set
+    /// Adds a cheat, which stays active until it's removed with `Mmu::remove_cheat`
+    pub fn add_cheat(&mut self, cheat: Cheat) {
+        self.cheats.push(cheat);
+    }
+
+    /// Removes every copy of `cheat`, returning whether there were any
+    pub fn remove_cheat(&mut self, cheat: Cheat) -> bool {
+        let len = self.cheats.len();
+        self.cheats.retain(|&c| c != cheat);
+
+        self.cheats.len() != len
+    }
+
+    /// Gets the active cheats
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    /// Applies Game Genie codes to a byte read from ROM
+    fn patch_rom(&self, addr: u16, value: Option<u8>) -> Option<u8> {
+        self.cheats.iter().fold(value, |value, &cheat| match cheat {
+            Cheat::GameGenie {
+                addr: cheat_addr,
+                value: new,
+                compare,
+            } if cheat_addr == addr && (compare.is_none() || compare == value) => Some(new),
+            _ => value,
+        })
+    }
+
+    /// Writes the values of all GameShark codes, meant to be called once per frame
+    pub fn apply_cheats(&mut self) {
+        for i in 0..self.cheats.len() {
+            let Cheat::GameShark { bank, addr, value } = self.cheats[i] else {
+                continue;
+            };
+
+            // the code picks its own WRAM bank, which shouldn't change the game's selection
+            let switch_bank = (0xD000..0xE000).contains(&addr) && (1..=7).contains(&bank);
+            let selected = self.wram.selected();
+
+            if switch_bank {
+                self.wram.select(bank);
+            }
+
+            self.set(addr, value);
+
+            if switch_bank {
+                self.wram.select(selected);
+            }
+        }
+    }
+
+    /// Copies the bytes from `start` to `end` (inclusive), for debugging
+    ///
+    /// The range can cross regions, and banked regions are read from whichever bank is currently selected.
+    /// Reading has no side effects, and uninitialized cells are `0`. An empty range (`start > end`) gives nothing
+    pub fn dump(&self, start: u16, end: u16) -> Vec<u8> {
+        self.load_block(start, end)
+    }
+
+    /// Same as `Mmu::dump`, but keeps track of uninitialized cells and can be printed as a hexdump
+    pub fn hexdump(&self, start: u16, end: u16) -> Hexdump {
+        Hexdump::new(start, (start..=end).map(|addr| self.load(addr)).collect())
+    }
+
+    /// Requests `interrupt` by setting its bit in IF
+    pub fn request_interrupt(&mut self, interrupt: Interrupt) {
+        let if_reg = self.load(IF).unwrap_or(0);
+        self.set(IF, if_reg | interrupt.bit());
+    }
+
+    /// Reads all of the APU registers at once
+    pub fn nr_registers(&self) -> NrRegisters {
+        NrRegisters::read(|addr| self.load(addr))
+    }
+
+    /// Copies out wave RAM, uninitialized bytes read as `0`
+    pub fn wave_ram(&self) -> WaveRam {
+        let mut data = [0; 16];
+        data.copy_from_slice(&self.load_block(WAVE_RAM, WAVE_RAM_END));
+
+        WaveRam(data)
+    }
+
+    /// Gets the interrupts that are both requested (IF) and enabled (IE)
+    pub fn pending_interrupts(&self) -> InterruptFlags {
+        let if_reg = self.load(IF).unwrap_or(0);
+
+        InterruptFlags::from_bits(self.ie & if_reg)
+    }
+
+    /// Fills WRAM, VRAM and OAM according to `init`, replacing whatever was there
     ///
-    /// Returns 0xFF if SC.7 is not set, or either SB or SC are uninitialized
+    /// Every cell ends up initialized, so this also stops loads from those areas failing when uninitialized reads aren't allowed
+    pub fn init_memory(&mut self, init: MemoryInit) {
+        let mut next = init.source();
+
+        self.wram.fill(&mut next);
+        self.vram.fill(&mut next);
+        self.vram_dirty = true;
+
+        for cell in self.oam.iter_mut() {
+            *cell = Some(next());
+        }
+    }
+
+    /// Puts memory back how it is at power on, see `ResetKind` for what's kept
+    ///
+    /// Held buttons, cheats and the cartridge stay as they are. After a cold reset WRAM, VRAM and OAM are uninitialized
+    /// again, so call `init_memory` to fill them
+    pub fn reset(&mut self, kind: ResetKind) {
+        self.mbc.reset();
+        self.io = init_io();
+        self.ie = 0;
+        self.joypad.write(0x30);
+        self.serial_cycles = None;
+        self.serial_out.clear();
+        self.vram.select(0);
+        self.wram.select(1);
+
+        #[cfg(feature = "sgb")]
+        {
+            self.sgb = SgbReceiver::new();
+        }
+
+        if kind == ResetKind::Cold {
+            *self.vram = VramBank::new();
+            self.vram_dirty = true;
+            *self.wram = WramBank::new();
+            self.oam = [None; 0xA0];
+            self.hram = [None; 0x7F];
+            self.bg_palettes = PaletteRam::new();
+            self.obj_palettes = PaletteRam::new();
+        }
+    }
+
+    /// Sets whether `button` is being held, requesting the joypad interrupt on a new press
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        if self.joypad.set_button(button, pressed) {
+            self.request_interrupt(Interrupt::Joypad);
+        }
+    }
+
+    /// Holds exactly the buttons in `state`, requesting the joypad interrupt if any of them is a new press
+    pub fn set_buttons(&mut self, state: ButtonState) {
+        if self.joypad.set_state(state) {
+            self.request_interrupt(Interrupt::Joypad);
+        }
+    }
+
+    /// Takes the oldest byte sent over serial that hasn't been read yet
     ///
-    /// Mutable so it can reset SC.7 to signal that the byte was sent
+    /// Returns 0xFF if nothing has been sent
     pub fn read_serial(&mut self) -> u8 {
-        if let Some(sc) = self.load(0xFF02) {
-            if sc & (1 << 7) > 0 {
-                let out = self.load(0xFF01).unwrap_or(0xFF);
-                self.set(0xFF01, 0xFF);
-                self.set(0xFF02, sc & !(1 << 7));
+        self.serial_out.pop_front().unwrap_or(0xFF)
+    }
 
-                out
-            } else {
-                0xFF
-            }
-        } else {
-            0xFF
+    /// Starts a serial transfer if SC.7 is being set
+    ///
+    /// There's never a link partner, so only transfers using the internal clock (SC.0) go anywhere.
+    /// The byte in SB is sent out right away, and the transfer finishes after `SERIAL_CYCLES`.
+    /// With the external clock the transfer waits forever, the same as real hardware with nothing plugged in
+    fn start_serial(&mut self, sc: u8) {
+        if sc & 0x81 != 0x81 || self.serial_cycles.is_some() {
+            return;
+        }
+
+        // don't keep bytes forever if the frontend never reads them
+        if self.serial_out.len() == SERIAL_BUFFER_SIZE {
+            self.serial_out.pop_front();
+        }
+
+        self.serial_out.push_back(self.load(SB).unwrap_or(0xFF));
+        self.serial_cycles = Some(SERIAL_CYCLES);
+    }
+
+    /// Moves the current serial transfer forward by an M-cycle
+    ///
+    /// When it finishes, SB has shifted in all 1s from the empty port, SC.7 is reset, and the serial interrupt is requested
+    pub(crate) fn tick_serial(&mut self) {
+        let Some(cycles) = self.serial_cycles else {
+            return;
+        };
+
+        if cycles > 1 {
+            self.serial_cycles = Some(cycles - 1);
+            return;
         }
+
+        self.serial_cycles = None;
+
+        let sc = self.load(SC).unwrap_or(0);
+
+        self.set(SB, 0xFF);
+        self.set(SC, sc & !(1 << 7));
+        self.request_interrupt(Interrupt::Serial);
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{mbc::MbcSelector, Mmu, MmuAddr};
+    use std::io::Cursor;
+
+    use crate::joypad::Button;
+
+    use super::{mbc::{MbcSelector, RamSize, RomSize, SeekSource}, AccessKind, Cheat, Interrupt, MemoryInit, Mmu, MmuAddr, BCPD, BCPS, IE, IF, SB, SC, SERIAL_CYCLES, SVBK, TMA};
 
     fn init_nombc() -> Mmu {
         Mmu::new(MbcSelector::NoMbc)
@@ -256,6 +711,79 @@ mod tests {
         assert_eq!(Mmu::translate(0xA800), MmuAddr::Mbc(0xA800));
     }
 
+    #[test]
+    fn memory_init() {
+        let wram = |init| {
+            let mut mmu = init_nombc();
+            mmu.init_memory(init);
+
+            assert!(mmu.load(0x8000).is_some());
+            assert!(mmu.load(0xFE9F).is_some());
+
+            mmu.load_block(0xC000, 0xC0FF)
+        };
+
+        assert_eq!(wram(MemoryInit::Zero), vec![0; 0x100]);
+        assert_eq!(wram(MemoryInit::Pattern(0xA5)), vec![0xA5; 0x100]);
+
+        // the same seed always gives the same memory
+        let random = wram(MemoryInit::Random(1234));
+        assert_eq!(random, wram(MemoryInit::Random(1234)));
+        assert_ne!(random, wram(MemoryInit::Random(4321)));
+        assert!(random.iter().any(|&byte| byte != random[0]));
+    }
+
+    #[test]
+    fn nombc_flat_rom() {
+        let mut mmu = init_nombc();
+        let rom: Vec<u8> = (0..0x8000).map(|i: usize| (i ^ (i >> 8)) as u8).collect();
+        mmu.load_rom(&rom);
+
+        for (addr, &byte) in rom.iter().enumerate() {
+            assert_eq!(mmu.load(addr as u16), Some(byte), "{addr:#06x}");
+        }
+
+        // writing to where an mbc1 would pick a bank does nothing
+        mmu.set(0x2000, 0x02);
+        assert_eq!(mmu.load(0x2000), Some(rom[0x2000]));
+        assert_eq!(mmu.load(0x4000), Some(rom[0x4000]));
+    }
+
+    #[test]
+    fn mbc2_ram_nibbles() {
+        let mut mmu = Mmu::new(MbcSelector::Mbc2(RomSize::Two));
+
+        // bit 8 clear is ram enable
+        mmu.set(0x0000, 0x0A);
+        mmu.set(0xA000, 0xAB);
+        assert_eq!(mmu.load(0xA000), Some(0xFB));
+
+        // the ram repeats every 512 bytes
+        assert_eq!(mmu.load(0xA200), Some(0xFB));
+
+        // bit 8 set is rom bank select, so this doesn't disable ram
+        mmu.set(0x0100, 0x00);
+        assert_eq!(mmu.load(0xA000), Some(0xFB));
+
+        mmu.set(0x0000, 0x00);
+        assert_eq!(mmu.load(0xA000), Some(0xFF));
+    }
+
+    #[test]
+    fn invalid_accesses() {
+        let mut mmu = Mmu::new(MbcSelector::Mbc1(RomSize::Two, RamSize::Two));
+
+        assert!(mmu.is_invalid_access(0xA000, AccessKind::Read));
+        mmu.set(0x0000, 0x0A);
+        assert!(!mmu.is_invalid_access(0xA000, AccessKind::Write));
+
+        assert!(mmu.is_invalid_access(0xFEA0, AccessKind::Read));
+        assert!(!mmu.is_invalid_access(0xFF00, AccessKind::Read));
+        assert!(mmu.is_invalid_access(0xFF00, AccessKind::Execute));
+        assert!(!mmu.is_invalid_access(0xFF80, AccessKind::Execute));
+        assert!(!mmu.is_invalid_access(0xC000, AccessKind::Execute));
+    }
+
     #[test]
     fn translate_vram() {
         assert_eq!(Mmu::translate(0x9000), MmuAddr::Vram(0x1000));
@@ -272,6 +800,95 @@ mod tests {
         assert_eq!(Mmu::translate(0xFE48), MmuAddr::Oam(0x0048));
     }
 
+    #[test]
+    fn raw_slices() {
+        let mut memory = init_nombc();
+
+        memory.set(0x8001, 0x12);
+        memory.vram.select(1);
+        memory.set(0x8001, 0x34);
+        memory.set(0xFE03, 0x56);
+
+        assert_eq!(memory.vram_bank(0)[..2], [None, Some(0x12)]);
+        assert_eq!(memory.vram_bank(1)[1], Some(0x34));
+        assert_eq!(memory.vram()[1], Some(0x34));
+        assert_eq!(memory.vram().len(), 0x2000);
+
+        assert_eq!(memory.oam().len(), 0xA0);
+        assert_eq!(memory.oam()[3], Some(0x56));
+    }
+
+    #[test]
+    fn vram_dirty() {
+        let mut memory = init_nombc();
+        assert!(memory.vram_dirty());
+
+        memory.clear_vram_dirty();
+        // tile maps aren't tile data
+        memory.set(0x9800, 0x01);
+        assert!(!memory.vram_dirty());
+
+        memory.set(0x97FF, 0x01);
+        assert!(memory.vram_dirty());
+
+        memory.clear_vram_dirty();
+        memory.vram.select(1);
+        memory.set(0x8000, 0x01);
+        assert!(memory.vram_dirty());
+    }
+
+    #[test]
+    fn unmapped_io() {
+        let mut mmu = init_nombc();
+
+        let cases = [
+            (0xFF03, 0xFF),
+            (0xFF08, 0xFF),
+            (0xFF0E, 0xFF),
+            (0xFF15, 0xFF),
+            (0xFF1F, 0xFF),
+            (0xFF27, 0xFF),
+            (0xFF2F, 0xFF),
+            (0xFF4C, 0xFF),
+            (0xFF4E, 0xFF),
+            (0xFF50, 0xFF),
+            (0xFF57, 0xFF),
+            (0xFF67, 0xFF),
+            (0xFF6D, 0xFF),
+            (0xFF71, 0xFF),
+            (0xFF7F, 0xFF),
+        ];
+
+        for (addr, expected) in cases {
+            mmu.set(addr, 0x00);
+            assert_eq!(mmu.load(addr), Some(expected), "{addr:#06X}");
+        }
+
+        // real registers next to the gaps still hold what's written
+        mmu.set(TMA, 0x12);
+        assert_eq!(mmu.load(TMA), Some(0x12));
+    }
+
+    #[test]
+    fn mbc1_streaming() {
+        // 8 banks, each one filled with its own number
+        let rom: Vec<u8> = (0..8).flat_map(|bank| [bank as u8; 0x4000]).collect();
+        let mut mmu = Mmu::new(MbcSelector::Mbc1(RomSize::Two, RamSize::Zero));
+        mmu.load_rom_streaming(SeekSource::new(Cursor::new(rom)).unwrap(), 2);
+
+        assert_eq!(mmu.load(0x0000), Some(0));
+        assert_eq!(mmu.load(0x4000), Some(1));
+
+        for bank in 1..8 {
+            mmu.set(0x2000, bank);
+            assert_eq!(mmu.load(0x7FFF), Some(bank), "bank {bank}");
+        }
+
+        // a clone reads the same banks
+        let clone = mmu.clone();
+        assert_eq!(clone.load(0x5000), Some(7));
+    }
+
     #[test]
     fn translate_io() {
         assert_eq!(Mmu::translate(0xFF38), MmuAddr::Io(0x0038));
@@ -296,7 +913,7 @@ mod tests {
     fn set_get() {
         let mut memory = init_nombc();
         let addresses: &[u16] = &[
-            0x5000, 0xA800, 0x9000, 0xC800, 0xD800, 0xFE48, 0xFF38, 0xFFA8, 0xFFFF,
+            0xA800, 0x9000, 0xC800, 0xD800, 0xFE48, 0xFF38, 0xFFA8, 0xFFFF,
         ];
 
         for (i, e) in addresses.iter().enumerate() {
@@ -343,4 +960,178 @@ mod tests {
 
         assert_eq!(memory.load(0xFEC8), Some(0xCC));
     }
+
+    #[test]
+    fn joypad() {
+        let mut memory = init_nombc();
+
+        memory.set_button(Button::Down, true);
+        memory.set_button(Button::Start, true);
+
+        // select the d-pad
+        memory.set(0xFF00, 0x20);
+        assert_eq!(memory.load(0xFF00), Some(0xE7));
+
+        // select the action buttons
+        memory.set(0xFF00, 0x10);
+        assert_eq!(memory.load(0xFF00), Some(0xD7));
+
+        memory.set_button(Button::Start, false);
+        assert_eq!(memory.load(0xFF00), Some(0xDF));
+    }
+
+    #[test]
+    fn joypad_interrupt() {
+        let mut memory = init_nombc();
+        memory.set(0xFF0F, 0);
+
+        // nothing is selected, so no interrupt
+        memory.set_button(Button::A, true);
+        assert_eq!(memory.load(0xFF0F), Some(0));
+
+        memory.set(0xFF00, 0x10);
+        memory.set_button(Button::B, true);
+        assert_eq!(memory.load(0xFF0F), Some(1 << 4));
+    }
+
+    #[test]
+    fn game_genie() {
+        let mut memory = init_nombc();
+        memory.load_rom(&[0x12; 0x8000]);
+
+        memory.add_cheat(Cheat::GameGenie { addr: 0x4000, value: 0x34, compare: None });
+        memory.add_cheat(Cheat::GameGenie { addr: 0x4001, value: 0x56, compare: Some(0x00) });
+
+        assert_eq!(memory.load(0x4000), Some(0x34));
+        // the compare value doesn't match, so the read isn't patched
+        assert_eq!(memory.load(0x4001), Some(0x12));
+
+        assert!(memory.remove_cheat(Cheat::GameGenie { addr: 0x4000, value: 0x34, compare: None }));
+        assert_eq!(memory.load(0x4000), Some(0x12));
+    }
+
+    #[test]
+    fn game_shark() {
+        let mut memory = init_nombc();
+        memory.set(SVBK, 1);
+
+        memory.add_cheat("01AB00C1".parse().unwrap());
+        memory.add_cheat("02CD00D0".parse().unwrap());
+        memory.apply_cheats();
+
+        assert_eq!(memory.load(0xC100), Some(0xAB));
+
+        // written into bank 2 without changing the selected bank
+        assert_eq!(memory.load(0xD000), None);
+        memory.set(SVBK, 2);
+        assert_eq!(memory.load(0xD000), Some(0xCD));
+    }
+
+    #[test]
+    fn dump() {
+        let mut memory = init_nombc();
+        memory.set(SVBK, 1);
+        memory.splice(0xDFFE, &[1, 2, 3, 4]);
+        memory.set(SVBK, 2);
+        memory.set(0xDFFF, 5);
+
+        // crosses from WRAM into echo RAM, using bank 2
+        assert_eq!(memory.dump(0xDFFE, 0xE001), vec![0, 5, 3, 4]);
+        assert_eq!(memory.hexdump(0xDFFE, 0xDFFF).data(), &[None, Some(5)]);
+        assert!(memory.dump(0xC001, 0xC000).is_empty());
+    }
+
+    #[test]
+    fn serial_internal_clock() {
+        let mut memory = init_nombc();
+        memory.set(IF, 0);
+        memory.set(SB, 0x42);
+        memory.set(SC, 0x81);
+
+        for _ in 0..SERIAL_CYCLES {
+            assert_eq!(memory.load(SC), Some(0x81));
+            memory.tick_serial();
+        }
+
+        // nothing is connected, so only 1s were shifted in
+        assert_eq!(memory.load(SB), Some(0xFF));
+        assert_eq!(memory.load(SC), Some(0x01));
+        assert_eq!(memory.load(IF), Some(1 << 3));
+
+        assert_eq!(memory.read_serial(), 0x42);
+        assert_eq!(memory.read_serial(), 0xFF);
+    }
+
+    #[test]
+    fn serial_external_clock() {
+        let mut memory = init_nombc();
+        memory.set(IF, 0);
+        memory.set(SB, 0x42);
+        memory.set(SC, 0x80);
+
+        for _ in 0..SERIAL_CYCLES * 2 {
+            memory.tick_serial();
+        }
+
+        // waits for a clock that never comes
+        assert_eq!(memory.load(SB), Some(0x42));
+        assert_eq!(memory.load(SC), Some(0x80));
+        assert_eq!(memory.load(IF), Some(0));
+        assert_eq!(memory.read_serial(), 0xFF);
+    }
+
+    #[test]
+    fn pending_interrupts() {
+        let mut memory = init_nombc();
+        memory.set(IF, 0);
+        memory.set(IE, 0b0_0101);
+
+        memory.request_interrupt(Interrupt::Timer);
+        memory.request_interrupt(Interrupt::Serial);
+
+        // serial is requested but not enabled
+        let pending = memory.pending_interrupts();
+        assert_eq!(pending.bits(), 0b0_0100);
+        assert_eq!(pending.highest_priority(), Some(Interrupt::Timer));
+    }
+
+    #[test]
+    fn peek_poke() {
+        let mut memory = Mmu::new(MbcSelector::Mbc1(RomSize::Two, RamSize::Two));
+        memory.splice(0x4000, &[0x11]);
+
+        // ram is disabled, so only peek and poke can see it
+        memory.poke(0xA000, 0x42);
+        assert_eq!(memory.load(0xA000), Some(0xFF));
+        assert_eq!(memory.peek(0xA000), 0x42);
+
+        // doesn't switch rom banks
+        memory.poke(0x2000, 0x02);
+        assert_eq!(memory.load(0x4000), Some(0x11));
+
+        memory.poke(0x4000, 0x22);
+        assert_eq!(memory.peek(0x4000), 0x22);
+
+        // doesn't switch wram banks or start a transfer
+        memory.set(0xD000, 0x33);
+        memory.poke(SVBK, 0x02);
+        assert_eq!(memory.load(SVBK), Some(0x02));
+        assert_eq!(memory.load(0xD000), Some(0x33));
+
+        memory.set(IF, 0);
+        memory.poke(SC, 0x81);
+
+        for _ in 0..SERIAL_CYCLES * 2 {
+            memory.tick_serial();
+        }
+
+        assert_eq!(memory.load(SC), Some(0x81));
+        assert_eq!(memory.load(IF), Some(0));
+
+        // doesn't auto increment
+        memory.set(BCPS, 0x80);
+        memory.poke(BCPD, 0x7F);
+        assert_eq!(memory.load(BCPS), Some(0xC0));
+        assert_eq!(memory.load(BCPD), Some(0x7F));
+    }
 }
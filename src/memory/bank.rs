@@ -47,6 +47,26 @@ impl VramBank {
         }
     }
 
+    /// Sets every cell in both banks to values from `next`
+    pub fn fill(&mut self, mut next: impl FnMut() -> u8) {
+        for cell in self.memory.iter_mut().flatten() {
+            *cell = Some(next());
+        }
+    }
+
+    /// Gets every cell of bank `bank`
+    ///
+    /// ### Panic Conditions
+    /// This method will panic if `bank` is not `0` or `1`
+    pub fn bank(&self, bank: u8) -> &[Option<u8>; 0x2000] {
+        &self.memory[bank as usize]
+    }
+
+    /// Gets the bank that's currently selected
+    pub fn selected(&self) -> u8 {
+        self.selected
+    }
+
     /// Selects the bank to be used when performing `Self::get()` and `Self::set()` operations
     ///
     /// ### Panic Conditions
@@ -104,6 +124,20 @@ impl WramBank {
         }
     }
 
+    /// Sets every cell in every bank to values from `next`
+    pub fn fill(&mut self, mut next: impl FnMut() -> u8) {
+        let banks = self.memory.iter_mut().flatten();
+
+        for cell in self.main.iter_mut().chain(banks) {
+            *cell = Some(next());
+        }
+    }
+
+    /// Gets the currently selected bank
+    pub fn selected(&self) -> u8 {
+        self.selected
+    }
+
     /// Selects the bank to be used when performing `Self::get()` and `Self::set()` operations
     ///
     /// ### Panic Conditions
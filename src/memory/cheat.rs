@@ -0,0 +1,132 @@
+use std::{fmt, str::FromStr};
+
+/// A cheat code, parsed from its usual text format with `str::parse`
+///
+/// - Game Genie codes look like `ABC-DEF` or `ABC-DEF-GHI`, and patch reads from ROM
+/// - GameShark codes look like `01VVLLHH`, and write to RAM once per frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cheat {
+    /// Reads from `addr` return `value` instead, as long as the original byte matches `compare` (if there is one)
+    GameGenie {
+        addr: u16,
+        value: u8,
+        compare: Option<u8>,
+    },
+    /// `value` is written to `addr` every frame, using WRAM bank `bank` if `addr` is in $D000-$DFFF
+    GameShark { bank: u8, addr: u16, value: u8 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheatError {
+    /// The code had this many digits, which doesn't match any format
+    InvalidLength(usize),
+    /// The code had a character that isn't a hex digit
+    InvalidDigit(char),
+    /// A Game Genie code pointed outside of ROM
+    InvalidAddress(u16),
+}
+
+impl fmt::Display for CheatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLength(len) => write!(f, "Cheat code has {len} digits, expected 6, 8 or 9"),
+            Self::InvalidDigit(c) => write!(f, "Cheat code contains a non-hex character: {c:?}"),
+            Self::InvalidAddress(addr) => write!(f, "Game Genie code points outside of ROM: {addr:#06X}"),
+        }
+    }
+}
+
+impl FromStr for Cheat {
+    type Err = CheatError;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let digits = code
+            .chars()
+            .filter(|&c| c != '-')
+            .map(|c| c.to_digit(16).map(|d| d as u8).ok_or(CheatError::InvalidDigit(c)))
+            .collect::<Result<Vec<u8>, _>>()?;
+
+        let byte = |i: usize| digits[i] << 4 | digits[i + 1];
+
+        match digits.len() {
+            6 | 9 => {
+                // ABC-DEF-GHI: AB is the new value, and FCDE is the address with F inverted
+                let value = byte(0);
+                let addr = ((digits[5] ^ 0xF) as u16) << 12
+                    | (digits[2] as u16) << 8
+                    | (digits[3] as u16) << 4
+                    | digits[4] as u16;
+
+                if addr >= 0x8000 {
+                    return Err(CheatError::InvalidAddress(addr));
+                }
+
+                // GI is the compare value, rotated and scrambled. H isn't used
+                let compare = if digits.len() == 9 {
+                    Some((digits[6] << 4 | digits[8]).rotate_right(2) ^ 0xBA)
+                } else {
+                    None
+                };
+
+                Ok(Cheat::GameGenie {
+                    addr,
+                    value,
+                    compare,
+                })
+            }
+            8 => {
+                // BBVVLLHH: RAM bank, value, then the address in little endian
+                let bank = byte(0);
+                let value = byte(2);
+                let addr = u16::from_le_bytes([byte(4), byte(6)]);
+
+                Ok(Cheat::GameShark { bank, addr, value })
+            }
+            len => Err(CheatError::InvalidLength(len)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cheat, CheatError};
+
+    #[test]
+    fn game_genie() {
+        assert_eq!(
+            "00A-17B-C49".parse(),
+            Ok(Cheat::GameGenie {
+                addr: 0x4A17,
+                value: 0x00,
+                compare: Some(0xC8),
+            })
+        );
+
+        assert_eq!(
+            "3EF-02F".parse(),
+            Ok(Cheat::GameGenie {
+                addr: 0x0F02,
+                value: 0x3E,
+                compare: None,
+            })
+        );
+
+        // 7 ^ F = 8, which puts the address past the end of ROM
+        assert_eq!("000-007".parse::<Cheat>(), Err(CheatError::InvalidAddress(0x8000)));
+    }
+
+    #[test]
+    fn game_shark() {
+        assert_eq!(
+            "010238CD".parse(),
+            Ok(Cheat::GameShark {
+                bank: 0x01,
+                addr: 0xCD38,
+                value: 0x02,
+            })
+        );
+
+        assert_eq!("0102G8CD".parse::<Cheat>(), Err(CheatError::InvalidDigit('G')));
+        assert_eq!("0102".parse::<Cheat>(), Err(CheatError::InvalidLength(4)));
+    }
+}
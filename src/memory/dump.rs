@@ -0,0 +1,80 @@
+use std::fmt;
+
+const BYTES_PER_LINE: usize = 16;
+
+/// A copy of a range of memory, which displays as a hexdump
+///
+/// Each line has the address, 16 bytes, and their ASCII. Uninitialized cells are shown as `--`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hexdump {
+    start: u16,
+    data: Vec<Option<u8>>,
+}
+
+impl Hexdump {
+    pub(crate) fn new(start: u16, data: Vec<Option<u8>>) -> Self {
+        Self { start, data }
+    }
+
+    /// Address of the first byte
+    pub fn start(&self) -> u16 {
+        self.start
+    }
+
+    /// The dumped bytes, `None` where memory was uninitialized
+    pub fn data(&self) -> &[Option<u8>] {
+        &self.data
+    }
+}
+
+impl fmt::Display for Hexdump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (line, bytes) in self.data.chunks(BYTES_PER_LINE).enumerate() {
+            let addr = self.start.wrapping_add((line * BYTES_PER_LINE) as u16);
+            write!(f, "{addr:04X}:")?;
+
+            for byte in bytes {
+                match byte {
+                    Some(byte) => write!(f, " {byte:02X}")?,
+                    None => write!(f, " --")?,
+                }
+            }
+
+            // pad short lines so the ascii lines up
+            for _ in bytes.len()..BYTES_PER_LINE {
+                write!(f, "   ")?;
+            }
+
+            let ascii: String = bytes
+                .iter()
+                .map(|byte| match byte {
+                    Some(byte) if byte.is_ascii_graphic() || *byte == b' ' => *byte as char,
+                    _ => '.',
+                })
+                .collect();
+
+            writeln!(f, "  |{ascii}|")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Hexdump;
+
+    #[test]
+    fn display() {
+        let mut data: Vec<_> = b"Hello, world!\n".iter().copied().map(Some).collect();
+        data.extend([None, Some(0xFF), Some(0x00)]);
+
+        let dump = Hexdump::new(0xC000, data);
+
+        assert_eq!(
+            dump.to_string(),
+            "C000: 48 65 6C 6C 6F 2C 20 77 6F 72 6C 64 21 0A -- FF  |Hello, world!...|\n\
+             C010: 00                                               |.|\n"
+        );
+    }
+}
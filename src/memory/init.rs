@@ -1,3 +1,58 @@
+/// What to fill RAM with at power on, see `Mmu::init_memory`
+///
+/// Real hardware comes up with whatever was left in RAM, which a few games use as a source of randomness
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MemoryInit {
+    /// Every cell is 0
+    #[default]
+    Zero,
+    /// Pseudo-random values from a seed, so runs with the same seed are identical
+    Random(u64),
+    /// Every cell is the same byte
+    Pattern(u8),
+}
+
+impl MemoryInit {
+    /// Makes a function that gives the value of each cell to fill, in order
+    pub(crate) fn source(self) -> impl FnMut() -> u8 {
+        // xorshift gets stuck on 0, so that seed gets swapped for something else
+        let mut state = match self {
+            MemoryInit::Random(0) => 0x9E37_79B9_7F4A_7C15,
+            MemoryInit::Random(seed) => seed,
+            _ => 0,
+        };
+
+        move || match self {
+            MemoryInit::Zero => 0,
+            MemoryInit::Pattern(byte) => byte,
+            MemoryInit::Random(_) => {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+
+                (state >> 32) as u8
+            }
+        }
+    }
+}
+
+/// How much of the system `Gbc::reset` puts back to how it was at power on
+///
+/// The cartridge is never touched beyond its banking registers, so cartridge RAM survives both kinds
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResetKind {
+    /// Turning the system off and on again
+    ///
+    /// Clears the CPU registers, PPU, timers, IO registers, WRAM, VRAM, OAM, HRAM and CGB palette RAM.
+    /// WRAM, VRAM and OAM are filled again the same way they were when the system was made
+    #[default]
+    Cold,
+    /// Pulling the reset line while the system stays powered
+    ///
+    /// Clears the CPU registers, PPU, timers and IO registers, but WRAM, VRAM, OAM, HRAM and CGB palette RAM keep whatever was in them
+    Warm,
+}
+
 /// Returns a new initialized IO memory segment
 ///
 /// Some cells are not meant to be initialized at boot, and are set to None
@@ -12,8 +67,8 @@ pub fn init_io() -> [Option<u8>; 0x80] {
         0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00, // FF28
         0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // FF30
         0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // FF38
-        0x00, 0x00, 0x00, 0x00, 0x91, 0x00, 0x00, 0xFC, // FF40
-        0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFE, // FF48
+        0x91, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFC, // FF40
+        0x00, 0x00, 0x00, 0x00, 0xFF, 0x7E, 0xFF, 0xFE, // FF48
         0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // FF50
         0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // FF58
         0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // FF60
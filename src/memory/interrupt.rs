@@ -0,0 +1,114 @@
+use std::fmt;
+
+/// Interrupt sources, in order of priority
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Interrupt {
+    VBlank,
+    Stat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Interrupt {
+    /// Every interrupt, from highest to lowest priority
+    pub const ALL: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::Stat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
+    /// Bit of this interrupt in IE and IF
+    pub fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    /// Address of this interrupt's handler
+    pub fn vector(self) -> u16 {
+        0x40 + 0x08 * self as u16
+    }
+}
+
+impl fmt::Display for Interrupt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// A set of interrupts, in the same layout as IE and IF
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InterruptFlags(u8);
+
+impl InterruptFlags {
+    /// Creates a set from the low 5 bits of `bits`, the rest are ignored
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits & 0x1F)
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn contains(self, interrupt: Interrupt) -> bool {
+        self.0 & interrupt.bit() > 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Iterates over the interrupts in the set, from highest to lowest priority
+    pub fn iter(self) -> impl Iterator<Item = Interrupt> {
+        Interrupt::ALL.into_iter().filter(move |&i| self.contains(i))
+    }
+
+    /// Gets the interrupt that would be serviced first
+    pub fn highest_priority(self) -> Option<Interrupt> {
+        self.iter().next()
+    }
+}
+
+impl fmt::Display for InterruptFlags {
+    /// Lists the interrupts in priority order, like `VBlank, Timer`, or `None` if the set is empty
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "None");
+        }
+
+        for (i, interrupt) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+
+            write!(f, "{interrupt}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Interrupt, InterruptFlags};
+
+    #[test]
+    fn flags() {
+        // bits above the joypad are ignored
+        let flags = InterruptFlags::from_bits(0b1110_0101);
+
+        assert_eq!(flags.bits(), 0b0_0101);
+        assert!(flags.contains(Interrupt::Timer));
+        assert!(!flags.contains(Interrupt::Stat));
+        assert_eq!(flags.highest_priority(), Some(Interrupt::VBlank));
+        assert_eq!(flags.to_string(), "VBlank, Timer");
+        assert_eq!(InterruptFlags::default().to_string(), "None");
+    }
+
+    #[test]
+    fn vectors() {
+        assert_eq!(Interrupt::VBlank.vector(), 0x40);
+        assert_eq!(Interrupt::Joypad.vector(), 0x60);
+    }
+}
@@ -0,0 +1,275 @@
+//! Addresses of the IO registers, grouped by what they control
+//!
+//! Everything here is re-exported from `memory`, so `memory::LCDC` and `memory::io::LCDC` are the same
+
+// ----- joypad -----
+
+/// Joypad input
+pub const JOYP: u16 = 0xFF00;
+
+// ----- serial -----
+
+/// Serial transfer data
+pub const SB: u16 = 0xFF01;
+/// Serial transfer control
+pub const SC: u16 = 0xFF02;
+
+// ----- timer -----
+
+/// Internal timer
+pub const DIV: u16 = 0xFF04;
+/// User facing timer
+pub const TIMA: u16 = 0xFF05;
+/// Timer modulo (TIMA resets to this)
+pub const TMA: u16 = 0xFF06;
+/// Timer control
+pub const TAC: u16 = 0xFF07;
+
+// ----- interrupts -----
+
+/// Interrupt flag
+pub const IF: u16 = 0xFF0F;
+/// Granular interrupt enable
+pub const IE: u16 = 0xFFFF;
+
+// ----- apu -----
+
+/// Channel 1 sweep
+pub const NR10: u16 = 0xFF10;
+/// Channel 1 length timer and duty cycle
+pub const NR11: u16 = 0xFF11;
+/// Channel 1 volume and envelope
+pub const NR12: u16 = 0xFF12;
+/// Channel 1 period low
+pub const NR13: u16 = 0xFF13;
+/// Channel 1 period high and control
+pub const NR14: u16 = 0xFF14;
+/// Channel 2 length timer and duty cycle
+pub const NR21: u16 = 0xFF16;
+/// Channel 2 volume and envelope
+pub const NR22: u16 = 0xFF17;
+/// Channel 2 period low
+pub const NR23: u16 = 0xFF18;
+/// Channel 2 period high and control
+pub const NR24: u16 = 0xFF19;
+/// Channel 3 DAC enable
+pub const NR30: u16 = 0xFF1A;
+/// Channel 3 length timer
+pub const NR31: u16 = 0xFF1B;
+/// Channel 3 output level
+pub const NR32: u16 = 0xFF1C;
+/// Channel 3 period low
+pub const NR33: u16 = 0xFF1D;
+/// Channel 3 period high and control
+pub const NR34: u16 = 0xFF1E;
+/// Channel 4 length timer
+pub const NR41: u16 = 0xFF20;
+/// Channel 4 volume and envelope
+pub const NR42: u16 = 0xFF21;
+/// Channel 4 frequency and randomness
+pub const NR43: u16 = 0xFF22;
+/// Channel 4 control
+pub const NR44: u16 = 0xFF23;
+/// Master volume and VIN panning
+pub const NR50: u16 = 0xFF24;
+/// Sound panning
+pub const NR51: u16 = 0xFF25;
+/// Sound on/off
+pub const NR52: u16 = 0xFF26;
+/// Channel 3 samples
+pub const WAVE_RAM: u16 = 0xFF30;
+pub const WAVE_RAM_END: u16 = 0xFF3F;
+
+// ----- ppu -----
+
+/// LCD control
+pub const LCDC: u16 = 0xFF40;
+/// LCD status
+pub const STAT: u16 = 0xFF41;
+/// Vertical scroll
+pub const SCY: u16 = 0xFF42;
+/// Horizontal scroll
+pub const SCX: u16 = 0xFF43;
+/// Current scanline
+pub const LY: u16 = 0xFF44;
+/// Scanline compare
+pub const LYC: u16 = 0xFF45;
+/// OAM DMA source and start
+/// Writing a byte to this address copies $XX00-$XX9f into $FE00-$FE9F where XX is the byte
+pub const DMA: u16 = 0xFF46;
+/// DMG palette
+pub const BGP: u16 = 0xFF47;
+/// DMG object palette 0
+pub const OBP0: u16 = 0xFF48;
+/// DMG object palette 1
+pub const OBP1: u16 = 0xFF49;
+/// Window Y position
+pub const WY: u16 = 0xFF4A;
+/// Window X position, plus 7
+pub const WX: u16 = 0xFF4B;
+/// VRAM bank select
+pub const VBK: u16 = 0xFF4F;
+/// CGB background palette spec
+pub const BCPS: u16 = 0xFF68;
+/// CGB background palette data
+pub const BCPD: u16 = 0xFF69;
+/// CGB object palette spec
+pub const OCPS: u16 = 0xFF6A;
+/// CGB object palette data
+pub const OCPD: u16 = 0xFF6B;
+/// CGB object priority mode, bit 0 picks between OAM order (0) and X coordinate (1)
+pub const OPRI: u16 = 0xFF6C;
+
+// ----- system -----
+
+/// CGB speed switch
+pub const KEY1: u16 = 0xFF4D;
+/// WRAM bank select
+pub const SVBK: u16 = 0xFF70;
+/// CGB scratch register with no known purpose, all 8 bits can be read and written
+pub const UNDOC_FF72: u16 = 0xFF72;
+/// CGB scratch register with no known purpose, all 8 bits can be read and written
+pub const UNDOC_FF73: u16 = 0xFF73;
+/// CGB scratch register with no known purpose, all 8 bits can be read and written
+pub const UNDOC_FF74: u16 = 0xFF74;
+/// CGB scratch register with no known purpose, only bits 4-6 can be read and written
+pub const UNDOC_FF75: u16 = 0xFF75;
+/// Current amplitude of sound channels 1 (low nibble) and 2 (high nibble), read only
+pub const PCM12: u16 = 0xFF76;
+/// Current amplitude of sound channels 3 (low nibble) and 4 (high nibble), read only
+pub const PCM34: u16 = 0xFF77;
+
+/// Gets the bits of the register at `addr` that aren't hooked up to anything, which always read as 1
+///
+/// `JOYP` and `STAT` put theirs in when they're read, since more than just these bits are generated
+pub fn unused_bits(addr: u16) -> u8 {
+    match addr {
+        // bit 1 is the cgb clock speed
+        SC => 0b0111_1100,
+        TAC => 0b1111_1000,
+        IF => 0b1110_0000,
+        KEY1 => 0b0111_1110,
+        VBK => 0b1111_1110,
+        OPRI => 0b1111_1110,
+        SVBK => 0b1111_1000,
+        UNDOC_FF75 => 0b1000_1111,
+        _ => 0,
+    }
+}
+
+/// Checks if there's no register at all at `addr`, in which case reads are always `0xFF` and writes do nothing
+///
+/// `0xFF4C` and `0xFF50` only do anything while the boot ROM is running, so they count too
+pub fn is_unmapped(addr: u16) -> bool {
+    matches!(
+        addr,
+        0xFF03
+            | 0xFF08..=0xFF0E
+            | 0xFF15
+            | 0xFF1F
+            | 0xFF27..=0xFF2F
+            | 0xFF4C
+            | 0xFF4E
+            | 0xFF50
+            | 0xFF57..=0xFF67
+            | 0xFF6D..=0xFF6F
+            | 0xFF71
+            | 0xFF78..=0xFF7F
+    )
+}
+
+/// Snapshot of the APU registers, NR10 through NR52
+///
+/// Unused gaps in the register range are left out
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NrRegisters {
+    pub nr10: u8,
+    pub nr11: u8,
+    pub nr12: u8,
+    pub nr13: u8,
+    pub nr14: u8,
+    pub nr21: u8,
+    pub nr22: u8,
+    pub nr23: u8,
+    pub nr24: u8,
+    pub nr30: u8,
+    pub nr31: u8,
+    pub nr32: u8,
+    pub nr33: u8,
+    pub nr34: u8,
+    pub nr41: u8,
+    pub nr42: u8,
+    pub nr43: u8,
+    pub nr44: u8,
+    pub nr50: u8,
+    pub nr51: u8,
+    pub nr52: u8,
+}
+
+impl NrRegisters {
+    /// Reads every register using `load`, uninitialized registers read as `0xFF`
+    pub(crate) fn read(load: impl Fn(u16) -> Option<u8>) -> Self {
+        let reg = |addr| load(addr).unwrap_or(0xFF);
+
+        Self {
+            nr10: reg(NR10),
+            nr11: reg(NR11),
+            nr12: reg(NR12),
+            nr13: reg(NR13),
+            nr14: reg(NR14),
+            nr21: reg(NR21),
+            nr22: reg(NR22),
+            nr23: reg(NR23),
+            nr24: reg(NR24),
+            nr30: reg(NR30),
+            nr31: reg(NR31),
+            nr32: reg(NR32),
+            nr33: reg(NR33),
+            nr34: reg(NR34),
+            nr41: reg(NR41),
+            nr42: reg(NR42),
+            nr43: reg(NR43),
+            nr44: reg(NR44),
+            nr50: reg(NR50),
+            nr51: reg(NR51),
+            nr52: reg(NR52),
+        }
+    }
+}
+
+/// Copy of wave RAM, 32 4-bit samples for channel 3
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WaveRam(pub [u8; 16]);
+
+impl WaveRam {
+    /// Gets sample `index` (0-31), the high nibble of each byte is played first
+    pub fn sample(&self, index: usize) -> u8 {
+        let byte = self.0[index / 2];
+        let shift = if index & 1 == 0 { 4 } else { 0 };
+
+        (byte >> shift) & 0x0F
+    }
+
+    /// Iterates over all 32 samples in the order they're played
+    pub fn samples(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..32).map(|i| self.sample(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WaveRam;
+
+    #[test]
+    fn wave_samples() {
+        let mut data = [0; 16];
+        data[0] = 0xA5;
+        data[15] = 0x0F;
+
+        let wave = WaveRam(data);
+        let samples: Vec<u8> = wave.samples().collect();
+
+        assert_eq!(&samples[0..3], &[0xA, 0x5, 0x0]);
+        assert_eq!(&samples[30..32], &[0x0, 0xF]);
+    }
+}
@@ -1,16 +1,26 @@
+use std::sync::Arc;
+
+use crate::error::Error;
+
 mod none;
 mod one;
+mod rom;
+mod two;
 
 pub use none::NoMbc;
 pub use one::Mbc1;
+pub use rom::{RomBankSource, RomBanks, SeekSource, ROM_BANK_SIZE};
+pub use two::Mbc2;
 
 /// MBC kinds, used to set which kind the CPU will use
 #[derive(Clone, Copy, Debug)]
 pub enum MbcSelector {
-    /// 16KiB ROM, no RAM
+    /// 32KiB ROM mapped directly with no banking, and 8KiB RAM for the carts that have it
     NoMbc,
     /// Max 2MiB ROM, 32KiB RAM
     Mbc1(RomSize, RamSize),
+    /// Max 256KiB ROM, 512 4-bit cells of built in RAM
+    Mbc2(RomSize),
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -56,9 +66,31 @@ pub enum MbcAddr {
     Ram(u16),
 }
 
+impl MbcSelector {
+    /// Checks that the ROM and RAM sizes can be used with this MBC, where `rom_byte` and `ram_byte` are the sizes from the header
+    pub fn check_sizes(&self, rom_byte: u8, ram_byte: u8) -> Result<(), Error> {
+        match self {
+            MbcSelector::NoMbc => Ok(()),
+            MbcSelector::Mbc1(RomSize::Seven | RomSize::Eight, _) => Err(Error::UnsupportedRomSize(rom_byte)),
+            MbcSelector::Mbc1(_, RamSize::Four | RamSize::Five) => Err(Error::UnsupportedRamSize(ram_byte)),
+            MbcSelector::Mbc1(..) => Ok(()),
+            MbcSelector::Mbc2(RomSize::Zero | RomSize::One | RomSize::Two | RomSize::Three) => Ok(()),
+            MbcSelector::Mbc2(_) => Err(Error::UnsupportedRomSize(rom_byte)),
+        }
+    }
+
+    /// Most bytes of ROM this MBC can hold as configured
+    pub fn rom_capacity(&self) -> usize {
+        match self {
+            MbcSelector::NoMbc => 0x8000,
+            MbcSelector::Mbc1(size, _) | MbcSelector::Mbc2(size) => convert_rom_size(size) * 0x4000,
+        }
+    }
+}
+
 impl RomSize {
-    pub fn from_byte(byte: u8) -> Self {
-        match byte {
+    pub fn from_byte(byte: u8) -> Result<Self, Error> {
+        Ok(match byte {
             0x00 => Self::Zero,
             0x01 => Self::One,
             0x02 => Self::Two,
@@ -68,21 +100,21 @@ impl RomSize {
             0x06 => Self::Six,
             0x07 => Self::Seven,
             0x08 => Self::Eight,
-            _ => panic!("Unsupported ROM size"),
-        }
+            _ => return Err(Error::UnsupportedRomSize(byte)),
+        })
     }
 }
 
 impl RamSize {
-    pub fn from_byte(byte: u8) -> Self {
-        match byte {
+    pub fn from_byte(byte: u8) -> Result<Self, Error> {
+        Ok(match byte {
             0x00 => Self::Zero,
             0x02 => Self::Two,
             0x03 => Self::Three,
             0x04 => Self::Four,
             0x05 => Self::Five,
-            _ => panic!("Unsupported RAM size"),
-        }
+            _ => return Err(Error::UnsupportedRamSize(byte)),
+        })
     }
 }
 
@@ -96,6 +128,40 @@ pub trait Mbc: Send + Sync {
     /// Loads cartridge data into ROM
     fn load_rom(&mut self, data: &[u8]);
 
+    /// Switches ROM over to banks read from `source` when they're needed, keeping up to `cache_banks` of them in memory
+    ///
+    /// MBCs without switchable banks can read everything up front instead
+    fn load_rom_streaming(&mut self, source: Box<dyn RomBankSource>, cache_banks: usize);
+
+    /// Writes `value` straight into whichever ROM bank is mapped at global address `addr`, skipping the mapper
+    ///
+    /// Games can't do this, it's for putting code into ROM from tests and debuggers
+    ///
+    /// ### Panic Conditions
+    /// - This should panic if `addr` is not in ROM
+    fn write_rom(&mut self, addr: u16, value: u8);
+
+    /// Gets the byte at global address `addr` in whichever RAM bank is mapped, even if RAM is disabled
+    ///
+    /// ### Return Variants
+    /// - `Some(value)` if the cell is initialized
+    /// - `None` if it's uninitialized, or the cartridge has no RAM there
+    ///
+    /// ### Panic Conditions
+    /// - This should panic if `addr` is not in cartridge RAM
+    fn peek_ram(&self, addr: u16) -> Option<u8>;
+
+    /// Writes `value` into whichever RAM bank is mapped at global address `addr`, even if RAM is disabled
+    ///
+    /// Writes to RAM the cartridge doesn't have are ignored
+    ///
+    /// ### Panic Conditions
+    /// - This should panic if `addr` is not in cartridge RAM
+    fn poke_ram(&mut self, addr: u16, value: u8);
+
+    /// Checks if the game can use cartridge RAM right now, which means it's enabled and there's RAM in the mapped bank
+    fn ram_accessible(&self) -> bool;
+
     /// Translates a global memory address into an internal MBC address of either the ROM or RAM section
     ///
     /// Should return either `MbcAddr::Rom(n)` or `MbcAddr::Ram(n)`, where `n` is the address relative to the start of the section
@@ -103,12 +169,34 @@ pub trait Mbc: Send + Sync {
     /// ### Panic Conditions
     /// - This should panic if `addr` is not within the bounds of the MBC
     fn translate(&self, addr: u16) -> MbcAddr;
+
+    /// Copies out all of the cartridge RAM, like for a save file
+    ///
+    /// Banks are one after another, and uninitialized cells are saved as `0xFF`
+    fn save_ram(&self) -> Vec<u8>;
+
+    /// Fills the cartridge RAM from data produced by `Self::save_ram`
+    ///
+    /// Anything past the end of the RAM is ignored
+    fn load_ram(&mut self, data: &[u8]);
+
+    /// Puts the banking registers back how they are at power on, keeping everything in ROM and RAM
+    fn reset(&mut self);
+
+    /// Clones the MBC into a new box, so `Mmu` can be cloned
+    fn box_clone(&self) -> Box<dyn Mbc>;
+}
+
+impl Clone for Box<dyn Mbc> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
 }
 
 pub fn init_mbc(kind: MbcSelector) -> Box<dyn Mbc> {
     match kind {
         MbcSelector::NoMbc => Box::new(NoMbc {
-            rom: Box::new([None; 0x8000]),
+            rom: Arc::new([None; 0x8000]),
             ram: Box::new([None; 0x2000]),
         }),
         MbcSelector::Mbc1(rom_size, ram_size) => {
@@ -128,11 +216,10 @@ pub fn init_mbc(kind: MbcSelector) -> Box<dyn Mbc> {
                 size => convert_ram_size(&size),
             };
 
-            let rom = vec![Box::new([None; 0x4000]); rom_banks];
             let ram = vec![Box::new([None; 0x2000]); ram_banks];
 
             Box::new(Mbc1 {
-                rom: rom.into_boxed_slice(),
+                rom: RomBanks::new(rom_banks),
                 ram: ram.into_boxed_slice(),
                 rom_bank: 1,
                 ram_bank: 0,
@@ -140,6 +227,24 @@ pub fn init_mbc(kind: MbcSelector) -> Box<dyn Mbc> {
                 ram_enabled: false,
             })
         }
+        MbcSelector::Mbc2(rom_size) => {
+            let rom_banks = match rom_size {
+                RomSize::Zero | RomSize::One | RomSize::Two | RomSize::Three => {
+                    convert_rom_size(&rom_size)
+                }
+                size => {
+                    let banks = convert_rom_size(&size);
+                    panic!("Invalid ROM size for MBC2 ({banks} banks)");
+                }
+            };
+
+            Box::new(Mbc2 {
+                rom: RomBanks::new(rom_banks),
+                ram: Box::new([None; 0x200]),
+                rom_bank: 1,
+                ram_enabled: false,
+            })
+        }
     }
 }
 
@@ -1,8 +1,11 @@
-use super::{Mbc, MbcAddr};
+use std::sync::Arc;
+
+use super::{Mbc, MbcAddr, RomBankSource, ROM_BANK_SIZE};
 
 #[derive(Clone)]
 pub struct NoMbc {
-    pub(crate) rom: Box<[Option<u8>; 0x8000]>,
+    /// Shared between clones like `RomBanks`, so snapshots don't copy it
+    pub(crate) rom: Arc<[Option<u8>; 0x8000]>,
     pub(crate) ram: Box<[Option<u8>; 0x2000]>,
 }
 
@@ -21,12 +24,39 @@ impl Mbc for NoMbc {
         let addr = self.translate(addr);
 
         match addr {
-            MbcAddr::Rom0(a) => self.rom[a as usize] = Some(value),
+            // there's no mapper to talk to, so rom writes go nowhere
+            MbcAddr::Rom0(_) => {}
             MbcAddr::RomX(_) => unreachable!(),
             MbcAddr::Ram(a) => self.ram[a as usize] = Some(value),
         }
     }
 
+    fn write_rom(&mut self, addr: u16, value: u8) {
+        match self.translate(addr) {
+            MbcAddr::Rom0(a) => Arc::make_mut(&mut self.rom)[a as usize] = Some(value),
+            _ => panic!("Invalid ROM write (address out of bounds): {addr:#06x}"),
+        }
+    }
+
+    fn peek_ram(&self, addr: u16) -> Option<u8> {
+        match self.translate(addr) {
+            MbcAddr::Ram(a) => self.ram[a as usize],
+            _ => panic!("Invalid RAM read (address out of bounds): {addr:#06x}"),
+        }
+    }
+
+    fn poke_ram(&mut self, addr: u16, value: u8) {
+        match self.translate(addr) {
+            MbcAddr::Ram(a) => self.ram[a as usize] = Some(value),
+            _ => panic!("Invalid RAM write (address out of bounds): {addr:#06x}"),
+        }
+    }
+
+    // without a mapper, RAM is always there
+    fn ram_accessible(&self) -> bool {
+        true
+    }
+
     fn translate(&self, addr: u16) -> MbcAddr {
         match addr {
             0x0000..=0x7FFF => MbcAddr::Rom0(addr),
@@ -35,16 +65,49 @@ impl Mbc for NoMbc {
         }
     }
 
+    fn save_ram(&self) -> Vec<u8> {
+        self.ram.iter().map(|cell| cell.unwrap_or(0xFF)).collect()
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        for (cell, &byte) in self.ram.iter_mut().zip(data) {
+            *cell = Some(byte);
+        }
+    }
+
+    // no registers to reset
+    fn reset(&mut self) {}
+
+    fn box_clone(&self) -> Box<dyn Mbc> {
+        Box::new(self.clone())
+    }
+
     fn load_rom(&mut self, data: &[u8]) {
-        match self.translate((data.len() - 1) as u16) {
-            MbcAddr::Rom0(_) => {
-                for addr in 0..data.len() {
-                    self.set(addr as u16, data[addr]);
+        if data.len() > self.rom.len() {
+            panic!("He ROM too big for he got damn MBC");
+        }
+
+        // the whole rom is mapped straight into 0x0000 - 0x7FFF
+        for (cell, &byte) in Arc::make_mut(&mut self.rom).iter_mut().zip(data) {
+            *cell = Some(byte);
+        }
+    }
+
+    // there's only 32KiB, so it's all read in right away
+    fn load_rom_streaming(&mut self, mut source: Box<dyn RomBankSource>, _cache_banks: usize) {
+        let mut bank = [0; ROM_BANK_SIZE];
+        let banks = source.bank_count().min(2);
+
+        let rom = Arc::make_mut(&mut self.rom);
+        rom.fill(None);
+
+        for number in 0..banks {
+            // a bank that can't be read stays uninitialized, the same as for the other MBCs
+            if source.read_bank(number, &mut bank).is_ok() {
+                for (cell, &byte) in rom[number * ROM_BANK_SIZE..].iter_mut().zip(bank.iter()) {
+                    *cell = Some(byte);
                 }
             }
-            MbcAddr::RomX(_) => unreachable!(),
-            // the translate method should have panicked if addr was outside of the entire MBCk
-            MbcAddr::Ram(_) => panic!("He ROM too big for he got damn MBC"),
-        };
+        }
     }
 }
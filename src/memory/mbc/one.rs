@@ -1,9 +1,9 @@
-use super::{Mbc, MbcAddr};
+use super::{Mbc, MbcAddr, RomBankSource, RomBanks};
 
 #[derive(Clone)]
 pub struct Mbc1 {
     /// Cartridge ROM, up to 128 banks, each 16384 bytes
-    pub rom: Box<[Box<[Option<u8>; 0x4000]>]>,
+    pub rom: RomBanks,
     /// Cartridge RAM, up to 4 banks, each 8192 bytes
     pub ram: Box<[Box<[Option<u8>; 0x2000]>]>,
     pub rom_bank: u8,
@@ -17,8 +17,8 @@ impl Mbc for Mbc1 {
         let addr = self.translate(addr);
 
         match addr {
-            MbcAddr::Rom0(a) => self.rom[0][a as usize],
-            MbcAddr::RomX(a) => self.rom[self.rom_bank as usize][a as usize],
+            MbcAddr::Rom0(a) => self.rom.get(0, a),
+            MbcAddr::RomX(a) => self.rom.get(self.rom_bank as usize, a),
             MbcAddr::Ram(a) => {
                 if self.ram_enabled {
                     self.ram[self.ram_bank as usize][a as usize]
@@ -91,31 +91,71 @@ impl Mbc for Mbc1 {
         }
     }
 
-    fn load_rom(&mut self, data: &[u8]) {
-        let mut bank = 0;
-        let mut i = 0;
-        let len = data.len();
-
-        while i < len {
-            // panic if the bank number is larger than the amount of banks in this mbc
-            if bank >= self.rom.len() {
-                panic!("ROM is of insufficient size as configured");
-            }
+    fn write_rom(&mut self, addr: u16, value: u8) {
+        match self.translate(addr) {
+            MbcAddr::Rom0(a) => self.rom.write(0, a, value),
+            MbcAddr::RomX(a) => self.rom.write(self.rom_bank as usize, a, value),
+            MbcAddr::Ram(_) => panic!("Invalid ROM write (address out of bounds): {addr:#06x}"),
+        }
+    }
 
-            // the number of bytes to move into the current bank
-            let offset = if len - i >= 0x4000 { 0x4000 } else { len - i };
+    fn peek_ram(&self, addr: u16) -> Option<u8> {
+        match self.translate(addr) {
+            MbcAddr::Ram(a) => self.ram.get(self.ram_bank as usize)?[a as usize],
+            _ => panic!("Invalid RAM read (address out of bounds): {addr:#06x}"),
+        }
+    }
 
-            // move all bytes that belong in this bank into this bank
-            for e in i..i + offset {
-                self.rom[bank][e - i] = Some(data[e]);
+    fn poke_ram(&mut self, addr: u16, value: u8) {
+        match self.translate(addr) {
+            MbcAddr::Ram(a) => {
+                if let Some(bank) = self.ram.get_mut(self.ram_bank as usize) {
+                    bank[a as usize] = Some(value);
+                }
             }
+            _ => panic!("Invalid RAM write (address out of bounds): {addr:#06x}"),
+        }
+    }
+
+    fn ram_accessible(&self) -> bool {
+        self.ram_enabled && (self.ram_bank as usize) < self.ram.len()
+    }
 
-            // move onto the next bank, and add `offset` to `i` cause we're past that now
-            bank += 1;
-            i += offset;
+    fn save_ram(&self) -> Vec<u8> {
+        self.ram
+            .iter()
+            .flat_map(|bank| bank.iter())
+            .map(|cell| cell.unwrap_or(0xFF))
+            .collect()
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        let cells = self.ram.iter_mut().flat_map(|bank| bank.iter_mut());
+
+        for (cell, &byte) in cells.zip(data) {
+            *cell = Some(byte);
         }
     }
 
+    fn reset(&mut self) {
+        self.rom_bank = 1;
+        self.ram_bank = 0;
+        self.ram_banking = false;
+        self.ram_enabled = false;
+    }
+
+    fn box_clone(&self) -> Box<dyn Mbc> {
+        Box::new(self.clone())
+    }
+
+    fn load_rom(&mut self, data: &[u8]) {
+        self.rom.load(data);
+    }
+
+    fn load_rom_streaming(&mut self, source: Box<dyn RomBankSource>, cache_banks: usize) {
+        self.rom = RomBanks::streamed(source, cache_banks);
+    }
+
     fn translate(&self, addr: u16) -> MbcAddr {
         match addr {
             0x0000..=0x3FFF => MbcAddr::Rom0(addr),
@@ -0,0 +1,291 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, Read, Seek, SeekFrom},
+    sync::{Arc, Mutex},
+};
+
+/// Bytes in a single switchable ROM bank
+pub const ROM_BANK_SIZE: usize = 0x4000;
+
+/// Somewhere ROM banks can be read from one at a time, for ROMs that shouldn't be kept in memory all at once
+///
+/// See `Mmu::load_rom_streaming`. `SeekSource` covers anything that can be read and seeked, like a `File`
+pub trait RomBankSource: Send {
+    /// Number of 16KiB banks in the ROM
+    fn bank_count(&self) -> usize;
+
+    /// Reads bank number `bank` into `out`
+    ///
+    /// A bank that's cut short by the end of the ROM should have the rest of `out` filled with `0xFF`
+    fn read_bank(&mut self, bank: usize, out: &mut [u8; ROM_BANK_SIZE]) -> io::Result<()>;
+}
+
+/// Reads ROM banks from anything that can be read and seeked, like a `File` or a `Cursor`
+pub struct SeekSource<R> {
+    reader: R,
+    banks: usize,
+}
+
+impl<R: Read + Seek> SeekSource<R> {
+    /// Wraps `reader`, finding how many banks it has from its length
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))? as usize;
+
+        Ok(Self {
+            reader,
+            banks: len.div_ceil(ROM_BANK_SIZE),
+        })
+    }
+}
+
+impl<R: Read + Seek + Send> RomBankSource for SeekSource<R> {
+    fn bank_count(&self) -> usize {
+        self.banks
+    }
+
+    fn read_bank(&mut self, bank: usize, out: &mut [u8; ROM_BANK_SIZE]) -> io::Result<()> {
+        self.reader.seek(SeekFrom::Start((bank * ROM_BANK_SIZE) as u64))?;
+        out.fill(0xFF);
+
+        let mut filled = 0;
+
+        while filled < ROM_BANK_SIZE {
+            match self.reader.read(&mut out[filled..])? {
+                0 => break,
+                read => filled += read,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Banks that have been read from a `RomBankSource`, with the least recently used one dropped first
+struct BankCache {
+    source: Box<dyn RomBankSource>,
+    capacity: usize,
+    /// Least recently used at the front
+    banks: VecDeque<(usize, Box<[u8; ROM_BANK_SIZE]>)>,
+}
+
+impl BankCache {
+    fn load(&mut self, bank: usize, addr: u16) -> Option<u8> {
+        // most reads hit the bank that was just used, so look from the back
+        if let Some(index) = self.banks.iter().rposition(|&(number, _)| number == bank) {
+            let entry = self.banks.remove(index)?;
+            self.banks.push_back(entry);
+        } else {
+            let mut data = Box::new([0; ROM_BANK_SIZE]);
+            self.source.read_bank(bank, &mut data).ok()?;
+
+            if self.banks.len() >= self.capacity {
+                self.banks.pop_front();
+            }
+
+            self.banks.push_back((bank, data));
+        }
+
+        self.banks.back().map(|(_, data)| data[addr as usize])
+    }
+}
+
+#[derive(Clone)]
+enum Banks {
+    /// Every bank is in memory, starting out uninitialized
+    ///
+    /// Clones share the banks, so rewind snapshots don't copy the whole ROM. A bank only gets its own copy when it's written to
+    Loaded(Box<[Arc<[Option<u8>; ROM_BANK_SIZE]>]>),
+    /// Banks are read from a source when they're needed
+    ///
+    /// Clones share the cache, since the ROM itself never changes. Writes from `RomBanks::write` are kept apart so they
+    /// don't get lost when a bank is dropped from the cache
+    Streamed {
+        count: usize,
+        cache: Arc<Mutex<BankCache>>,
+        patches: HashMap<(usize, u16), u8>,
+    },
+}
+
+/// Cartridge ROM for the MBCs with switchable banks, either all in memory or streamed in as needed
+#[derive(Clone)]
+pub struct RomBanks(Banks);
+
+impl RomBanks {
+    /// Makes `count` banks of uninitialized ROM, to be filled by `load`
+    pub fn new(count: usize) -> Self {
+        let empty = Arc::new([None; ROM_BANK_SIZE]);
+        Self(Banks::Loaded(vec![empty; count].into_boxed_slice()))
+    }
+
+    /// Reads banks from `source` only when they're needed, keeping up to `cache_banks` of the most recently used ones
+    ///
+    /// A `cache_banks` of 0 is treated as 1
+    pub fn streamed(source: Box<dyn RomBankSource>, cache_banks: usize) -> Self {
+        let count = source.bank_count();
+        let cache = BankCache {
+            source,
+            capacity: cache_banks.max(1),
+            banks: VecDeque::new(),
+        };
+
+        Self(Banks::Streamed {
+            count,
+            cache: Arc::new(Mutex::new(cache)),
+            patches: HashMap::new(),
+        })
+    }
+
+    /// Number of banks
+    pub fn len(&self) -> usize {
+        match self.0 {
+            Banks::Loaded(ref banks) => banks.len(),
+            Banks::Streamed { count, .. } => count,
+        }
+    }
+
+    /// Gets the byte at `addr` (0-0x3FFF) of bank `bank`
+    ///
+    /// ### Return Variants
+    /// - `Some(value)` if the cell is initialized
+    /// - `None` if the cell is uninitialized, the bank doesn't exist, or reading it from a streamed source failed
+    pub fn get(&self, bank: usize, addr: u16) -> Option<u8> {
+        match self.0 {
+            Banks::Loaded(ref banks) => banks.get(bank)?[addr as usize],
+            Banks::Streamed { count, ref cache, ref patches } => {
+                if bank >= count {
+                    return None;
+                }
+
+                if let Some(&value) = patches.get(&(bank, addr)) {
+                    return Some(value);
+                }
+
+                cache.lock().unwrap_or_else(|e| e.into_inner()).load(bank, addr)
+            }
+        }
+    }
+
+    /// Sets the byte at `addr` (0-0x3FFF) of bank `bank`, see `Mbc::write_rom`
+    ///
+    /// ### Panic Conditions
+    /// - `bank` doesn't exist
+    pub fn write(&mut self, bank: usize, addr: u16, value: u8) {
+        match self.0 {
+            Banks::Loaded(ref mut banks) => Arc::make_mut(&mut banks[bank])[addr as usize] = Some(value),
+            Banks::Streamed { count, ref mut patches, .. } => {
+                assert!(bank < count, "Invalid ROM bank: {bank}");
+                patches.insert((bank, addr), value);
+            }
+        }
+    }
+
+    /// Copies `data` into the banks, one after another
+    ///
+    /// A streamed ROM gets replaced with one that's all in memory
+    ///
+    /// ### Panic Conditions
+    /// - `data` doesn't fit in the banks
+    pub fn load(&mut self, data: &[u8]) {
+        if data.len() > self.len() * ROM_BANK_SIZE {
+            panic!("ROM is of insufficient size as configured");
+        }
+
+        if let Banks::Streamed { count, .. } = self.0 {
+            *self = Self::new(count);
+        }
+
+        let Banks::Loaded(ref mut banks) = self.0 else {
+            unreachable!("Streamed banks were just replaced");
+        };
+
+        for (bank, chunk) in banks.iter_mut().zip(data.chunks(ROM_BANK_SIZE)) {
+            for (cell, &byte) in Arc::make_mut(bank).iter_mut().zip(chunk) {
+                *cell = Some(byte);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Cursor},
+        sync::{Arc, Mutex},
+    };
+
+    use super::{Banks, RomBankSource, RomBanks, SeekSource, ROM_BANK_SIZE};
+
+    /// Each bank is filled with its own number, and every read is logged
+    struct CountingSource {
+        banks: usize,
+        reads: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl RomBankSource for CountingSource {
+        fn bank_count(&self) -> usize {
+            self.banks
+        }
+
+        fn read_bank(&mut self, bank: usize, out: &mut [u8; ROM_BANK_SIZE]) -> io::Result<()> {
+            self.reads.lock().unwrap().push(bank);
+            out.fill(bank as u8);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn lru_cache() {
+        let reads = Arc::new(Mutex::new(Vec::new()));
+        let source = CountingSource {
+            banks: 8,
+            reads: Arc::clone(&reads),
+        };
+        let mut rom = RomBanks::streamed(Box::new(source), 2);
+
+        assert_eq!(rom.len(), 8);
+
+        for bank in [0, 1, 0, 2, 0, 1] {
+            assert_eq!(rom.get(bank, 0x1234), Some(bank as u8));
+        }
+
+        // bank 1 was the least recently used when bank 2 came in
+        assert_eq!(*reads.lock().unwrap(), [0, 1, 2, 1]);
+
+        rom.write(3, 0x10, 0xAB);
+        assert_eq!(rom.get(3, 0x10), Some(0xAB));
+        assert_eq!(rom.get(3, 0x11), Some(3));
+        assert_eq!(rom.get(8, 0x00), None);
+    }
+
+    #[test]
+    fn clones_share_banks() {
+        let mut rom = RomBanks::new(4);
+        rom.load(&[0x12; ROM_BANK_SIZE * 4]);
+
+        let mut copy = rom.clone();
+        let (Banks::Loaded(ref original), Banks::Loaded(ref cloned)) = (&rom.0, &copy.0) else {
+            panic!("ROM should be loaded");
+        };
+        assert!(original.iter().zip(cloned.iter()).all(|(a, b)| Arc::ptr_eq(a, b)));
+
+        // writing only gives the written bank its own copy
+        copy.write(1, 0x10, 0xAB);
+        assert_eq!(copy.get(1, 0x10), Some(0xAB));
+        assert_eq!(rom.get(1, 0x10), Some(0x12));
+    }
+
+    #[test]
+    fn seek_source() {
+        let mut data = vec![0x11; ROM_BANK_SIZE];
+        data.extend([0x22; 0x10]);
+
+        let mut source = SeekSource::new(Cursor::new(data)).unwrap();
+        let mut bank = [0; ROM_BANK_SIZE];
+
+        assert_eq!(source.bank_count(), 2);
+
+        source.read_bank(1, &mut bank).unwrap();
+        assert_eq!(&bank[0x0E..0x12], &[0x22, 0x22, 0xFF, 0xFF]);
+    }
+}
@@ -0,0 +1,119 @@
+use super::{Mbc, MbcAddr, RomBankSource, RomBanks};
+
+#[derive(Clone)]
+pub struct Mbc2 {
+    /// Cartridge ROM, up to 16 banks, each 16384 bytes
+    pub rom: RomBanks,
+    /// Built in RAM, 512 cells that only hold 4 bits each
+    pub ram: Box<[Option<u8>; 0x200]>,
+    pub rom_bank: u8,
+    pub ram_enabled: bool,
+}
+
+impl Mbc for Mbc2 {
+    fn load(&self, addr: u16) -> Option<u8> {
+        let addr = self.translate(addr);
+
+        match addr {
+            MbcAddr::Rom0(a) => self.rom.get(0, a),
+            MbcAddr::RomX(a) => self.rom.get(self.rom_bank as usize, a),
+            MbcAddr::Ram(a) => {
+                if self.ram_enabled {
+                    // the upper 4 bits aren't connected, so they read as 1s
+                    self.ram[a as usize].map(|value| value | 0xF0)
+                } else {
+                    Some(0xFF)
+                }
+            }
+        }
+    }
+
+    fn set(&mut self, addr: u16, value: u8) {
+        let addr = self.translate(addr);
+
+        match addr {
+            // bit 8 of the address picks the register, everything past 0x3FFF does nothing
+            MbcAddr::Rom0(a) if a & 0x100 == 0 => self.ram_enabled = value & 0x0F == 0x0A,
+            MbcAddr::Rom0(_) => {
+                let len = self.rom.len() as u8;
+                let bank = match value & 0x0F {
+                    0 => 1,
+                    bank => bank,
+                };
+
+                // banks past the end of the rom wrap around
+                self.rom_bank = bank % len;
+            }
+            MbcAddr::RomX(_) => {}
+            MbcAddr::Ram(a) => {
+                if self.ram_enabled {
+                    self.ram[a as usize] = Some(value & 0x0F);
+                }
+            }
+        }
+    }
+
+    fn write_rom(&mut self, addr: u16, value: u8) {
+        match self.translate(addr) {
+            MbcAddr::Rom0(a) => self.rom.write(0, a, value),
+            MbcAddr::RomX(a) => self.rom.write(self.rom_bank as usize, a, value),
+            MbcAddr::Ram(_) => panic!("Invalid ROM write (address out of bounds): {addr:#06x}"),
+        }
+    }
+
+    // these still only have 4 bits, same as `load` and `set`
+    fn peek_ram(&self, addr: u16) -> Option<u8> {
+        match self.translate(addr) {
+            MbcAddr::Ram(a) => self.ram[a as usize].map(|value| value | 0xF0),
+            _ => panic!("Invalid RAM read (address out of bounds): {addr:#06x}"),
+        }
+    }
+
+    fn poke_ram(&mut self, addr: u16, value: u8) {
+        match self.translate(addr) {
+            MbcAddr::Ram(a) => self.ram[a as usize] = Some(value & 0x0F),
+            _ => panic!("Invalid RAM write (address out of bounds): {addr:#06x}"),
+        }
+    }
+
+    fn ram_accessible(&self) -> bool {
+        self.ram_enabled
+    }
+
+    fn save_ram(&self) -> Vec<u8> {
+        self.ram.iter().map(|cell| cell.unwrap_or(0xFF)).collect()
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        for (cell, &byte) in self.ram.iter_mut().zip(data) {
+            *cell = Some(byte & 0x0F);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.rom_bank = 1;
+        self.ram_enabled = false;
+    }
+
+    fn box_clone(&self) -> Box<dyn Mbc> {
+        Box::new(self.clone())
+    }
+
+    fn load_rom(&mut self, data: &[u8]) {
+        self.rom.load(data);
+    }
+
+    fn load_rom_streaming(&mut self, source: Box<dyn RomBankSource>, cache_banks: usize) {
+        self.rom = RomBanks::streamed(source, cache_banks);
+    }
+
+    fn translate(&self, addr: u16) -> MbcAddr {
+        match addr {
+            0x0000..=0x3FFF => MbcAddr::Rom0(addr),
+            0x4000..=0x7FFF => MbcAddr::RomX(addr - 0x4000),
+            // only 9 bits of the address are used, so the ram repeats through the whole section
+            0xA000..=0xBFFF => MbcAddr::Ram((addr - 0xA000) & 0x1FF),
+            _ => panic!("Invalid memory translation: ${addr:#06x}"),
+        }
+    }
+}
@@ -0,0 +1,77 @@
+/// CGB palette RAM, accessed through a spec register (BCPS/OCPS) and a data register (BCPD/OCPD)
+///
+/// Holds 8 palettes of 4 colors, each color is 2 bytes of little endian RGB555
+#[derive(Clone, Copy, Debug)]
+pub struct PaletteRam {
+    data: [u8; 64],
+    /// Bit 7 is auto increment, bits 0-5 are the address in `data`
+    spec: u8,
+}
+
+impl PaletteRam {
+    pub fn new() -> Self {
+        Self {
+            // palettes start out white, like the cgb leaves them for dmg games
+            data: [0xFF; 64],
+            spec: 0,
+        }
+    }
+
+    /// Reads the spec register, bit 6 is unused and always reads `1`
+    pub fn read_spec(&self) -> u8 {
+        self.spec | 0x40
+    }
+
+    pub fn write_spec(&mut self, value: u8) {
+        self.spec = value & 0xBF;
+    }
+
+    /// Reads the byte selected by the spec register
+    pub fn read_data(&self) -> u8 {
+        self.data[self.address()]
+    }
+
+    /// Writes the byte selected by the spec register, moving to the next one if auto increment is set
+    pub fn write_data(&mut self, value: u8) {
+        self.data[self.address()] = value;
+
+        if self.spec & 0x80 > 0 {
+            let address = (self.address() as u8 + 1) & 0x3F;
+            self.spec = 0x80 | address;
+        }
+    }
+
+    /// Gets color `index` (0-3) of `palette` (0-7) as RGB555
+    pub fn color(&self, palette: u8, index: u8) -> u16 {
+        let address = (palette as usize & 7) * 8 + (index as usize & 3) * 2;
+
+        u16::from_le_bytes([self.data[address], self.data[address + 1]])
+    }
+
+    fn address(&self) -> usize {
+        (self.spec & 0x3F) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PaletteRam;
+
+    #[test]
+    fn auto_increment() {
+        let mut ram = PaletteRam::new();
+
+        // palette 1, color 2, auto increment
+        ram.write_spec(0x80 | 0x0C);
+        ram.write_data(0x1F);
+        ram.write_data(0x7C);
+
+        assert_eq!(ram.read_spec(), 0xC0 | 0x0E);
+        assert_eq!(ram.color(1, 2), 0x7C1F);
+
+        // wraps around from the last byte
+        ram.write_spec(0x80 | 0x3F);
+        ram.write_data(0x00);
+        assert_eq!(ram.read_spec(), 0xC0);
+    }
+}
@@ -0,0 +1,41 @@
+use std::{fmt, sync::Arc};
+
+/// Frontend function that gets each invalid access caught in strict mode, see `Mmu::set_diagnostics`
+///
+/// It's an `Arc` so the memory map can still be cloned for snapshots
+pub type DiagnosticCallback = Arc<dyn Fn(&InvalidAccess) + Send + Sync>;
+
+/// What the CPU was doing with memory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+    /// Fetching an opcode
+    Execute,
+}
+
+/// An access that real hardware handles quietly, but that a game almost certainly didn't mean to make
+///
+/// Strict mode catches these, see `Mmu::set_strict`:
+/// - Reading or writing cartridge RAM while it's disabled, or when there's no RAM in the mapped bank
+/// - Reading or writing the prohibited area ($FEA0-$FEFF)
+/// - Executing from OAM, the prohibited area, IO registers or `IE`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidAccess {
+    pub addr: u16,
+    pub kind: AccessKind,
+    /// Address of the instruction that made the access
+    pub pc: u16,
+}
+
+impl fmt::Display for InvalidAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            AccessKind::Read => "read from",
+            AccessKind::Write => "write to",
+            AccessKind::Execute => "execution from",
+        };
+
+        write!(f, "Invalid {kind} {:#06X} at PC {:#06X}", self.addr, self.pc)
+    }
+}
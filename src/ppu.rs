@@ -1,6 +1,10 @@
 use std::{fmt::Display, ops::Index};
 
-use crate::{memory::{OAM, OAM_END, SCX, SCY}, Mmu};
+use crate::{bits::{bit, bit_set}, cpu::CLOCK_HZ, error::Error, memory::{PaletteRam, OAM, OAM_END, OPRI, SCX, SCY}, Mmu};
+
+pub use self::compat::CompatPalette;
+
+mod compat;
 
 // darkening shades of grey
 const PALETTE: [Color; 4] = [
@@ -16,6 +20,47 @@ const HEIGHT: u8 = 144;
 const TILE_WIDTH: u8 = 8;
 const TILE_HEIGHT: u8 = 8;
 
+/// Width of the screen in pixels
+pub const SCREEN_WIDTH: usize = WIDTH as usize;
+/// Height of the screen in pixels
+pub const SCREEN_HEIGHT: usize = HEIGHT as usize;
+
+// number of lines spent in vblank after the visible ones
+const VBLANK_LINES: u8 = 10;
+
+// number of objects in OAM, which is the most that could ever be on a line
+const OAM_OBJECTS: usize = 40;
+
+/// Most objects real hardware draws on one line, see `Ppu::set_sprite_limit`
+pub const SPRITE_LIMIT: u8 = 10;
+const LAST_LINE: u8 = HEIGHT + VBLANK_LINES - 1;
+
+/// Dots (T-cycles at normal speed) in every line, visible or not
+pub const DOTS_PER_LINE: u16 = 456;
+/// Dots spent picking objects at the start of each visible line
+pub const OAM_SCAN_DOTS: u16 = 80;
+/// Shortest pixel transfer, with nothing to slow it down
+pub const MIN_TRANSFER_DOTS: u16 = 172;
+/// Longest pixel transfer, with every penalty applied
+pub const MAX_TRANSFER_DOTS: u16 = 289;
+// fetching an object stalls the transfer for at least this long
+const OBJECT_FETCH_DOTS: u16 = 6;
+/// Dots spent in vblank, which covers whole lines
+pub const VBLANK_DOTS: u32 = VBLANK_LINES as u32 * DOTS_PER_LINE as u32;
+
+// the ppu runs 4 dots for every M-cycle
+const DOTS_PER_TICK: u16 = 4;
+
+// pixels start coming out once the first tiles are fetched
+const TRANSFER_DELAY: u16 = MIN_TRANSFER_DOTS - WIDTH as u16;
+
+/// Number of PPU ticks (M-cycles) in a full frame, including vblank
+pub const TICKS_PER_FRAME: usize =
+    DOTS_PER_LINE as usize * (HEIGHT + VBLANK_LINES) as usize / DOTS_PER_TICK as usize;
+
+/// Frames per second on real hardware, a bit under 60
+pub const FRAME_RATE: f64 = CLOCK_HZ as f64 / (TICKS_PER_FRAME * DOTS_PER_TICK as usize) as f64;
+
 // number of bytes in a tile row
 const ROW_SIZE: u8 = 2;
 
@@ -27,6 +72,9 @@ const TILE_BYTES: u8 = ROW_SIZE * TILE_HEIGHT;
 // const HEIGHT_IN_TILES: u8 = HEIGHT / TILE_HEIGHT;
 const WIDTH_IN_TILES: u8 = 32;
 
+/// Width and height of the whole background map in pixels, which the screen is a 160x144 window into
+pub const BG_MAP_PIXELS: usize = WIDTH_IN_TILES as usize * TILE_WIDTH as usize;
+
 // base addresses for the different tile data addressing modes
 const UNSIGNED_BASE: u16 = 0x8000;
 const SIGNED_BASE: u16 = 0x9000;
@@ -48,14 +96,14 @@ pub struct Lcdc {
 
 impl From<u8> for Lcdc {
     fn from(value: u8) -> Self {
-        let lcd_enable = (value & 0b1000_0000) > 0;
-        let window_map_area = if (value & 0b0100_0000) == 0 { 0x9800 } else { 0x9c00 };
-        let window_enable = (value & 0b0010_0000) > 0;
-        let bg_addressing = if (value & 0b0001_0000) == 0 { AddressType::Signed } else { AddressType::Unsigned };
-        let bg_map_area = if (value & 0b0000_1000) == 0 { 0x9800 } else { 0x9c00 };
-        let obj_size = if (value & 0b0000_0100) == 0 { 8 } else { 16 };
-        let obj_enable = (value & 0b0000_0010) > 0;
-        let bg_enable = (value & 0b0000_0001) > 0;
+        let lcd_enable = bit_set(value, 7);
+        let window_map_area = if bit_set(value, 6) { 0x9c00 } else { 0x9800 };
+        let window_enable = bit_set(value, 5);
+        let bg_addressing = if bit_set(value, 4) { AddressType::Unsigned } else { AddressType::Signed };
+        let bg_map_area = if bit_set(value, 3) { 0x9c00 } else { 0x9800 };
+        let obj_size = if bit_set(value, 2) { 16 } else { 8 };
+        let obj_enable = bit_set(value, 1);
+        let bg_enable = bit_set(value, 0);
 
         Self {
             lcd_enable,
@@ -70,16 +118,84 @@ impl From<u8> for Lcdc {
     }
 }
 
-#[derive(Debug)]
+impl From<Lcdc> for u8 {
+    fn from(lcdc: Lcdc) -> Self {
+        let mut value = 0;
+
+        value |= (lcdc.lcd_enable as u8) << 7;
+        value |= ((lcdc.window_map_area == 0x9c00) as u8) << 6;
+        value |= (lcdc.window_enable as u8) << 5;
+        value |= (matches!(lcdc.bg_addressing, AddressType::Unsigned) as u8) << 4;
+        value |= ((lcdc.bg_map_area == 0x9c00) as u8) << 3;
+        value |= ((lcdc.obj_size == 16) as u8) << 2;
+        value |= (lcdc.obj_enable as u8) << 1;
+        value |= lcdc.bg_enable as u8;
+
+        value
+    }
+}
+
+/// What the PPU is doing, numbered the same as the mode bits in STAT
+///
+/// Each visible line goes `OamScan` -> `Transfer` -> `HBlank`, taking `DOTS_PER_LINE` dots in total.
+/// HBlank takes whatever is left after the transfer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PpuMode {
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Transfer = 3,
+}
+
+/// When pixels get drawn into `fb`, trading how many mid-frame effects show up for speed
+///
+/// Timing, modes and interrupts are the same in every mode, only drawing moves around
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RenderMode {
+    /// The whole frame is drawn when vblank starts, with the registers and memory as they are then.
+    /// The cheapest, but anything a game changes partway through a frame, like scrolling for a status bar, is lost
+    PerFrame,
+    /// Each line is drawn all at once when it reaches hblank, so changes between lines show up but changes within one don't
+    PerScanline,
+    /// Each pixel is drawn on the dot it comes out of the LCD, so changes partway through a line show up too
+    #[default]
+    PixelFifo,
+}
+
+#[derive(Clone, Debug)]
 pub struct Ppu {
     pub lcdc: Lcdc,
     pub stat: u8,
+    /// `x` is the next pixel to draw on line `y`
     pub coords: PpuCoords,
+    mode: PpuMode,
+    /// Dots into the current line
+    dot: u16,
     pub palette: Palette,
     pub obj_palettes: [Palette; 2],
     pub fb: Vec<u8>,
-    pub objects: [Option<Object>; 10],
+    /// Objects on the current line in OAM order, with only the first `sprite_limit` filled in
+    pub objects: [Option<Object>; OAM_OBJECTS],
     pub status: PpuStatus,
+    /// Whether the game uses CGB features, set from the header by `Gbc::load_rom`
+    ///
+    /// With it on, background tiles come from the VRAM bank in their BG map attributes and objects from the bank in their OAM
+    /// attributes, instead of always bank 0. `bg_palettes` and `obj_palettes` report palette RAM instead of BGP, OBP0 and OBP1.
+    /// Pixels are still colored with the DMG palettes, and which object wins a pixel goes by OPRI, which `Gbc::load_rom`
+    /// sets from the header too
+    pub cgb: bool,
+    /// Whether pixels are drawn into `fb`, see `set_rendering`
+    rendering: bool,
+    /// When pixels are drawn, see `set_render_mode`
+    render_mode: RenderMode,
+    /// Whether any of the enabled STAT interrupt sources were active last M-cycle, see `update_stat_line`
+    stat_line: bool,
+    /// What each visible line was drawn with, only kept while the log is on, see `set_scanline_log`
+    scanline_log: Option<Box<[ScanlineState; HEIGHT as usize]>>,
+    /// Most objects picked per line, `None` for no limit
+    sprite_limit: Option<u8>,
+    /// How long the current line's pixel transfer takes, worked out when it starts, see `transfer_dots`
+    transfer_dots: u16,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -98,23 +214,26 @@ pub struct ObjectAttributes {
     pub y_flip: bool,
     pub x_flip: bool,
     pub dmg_palette: ObpSelector,
-    // // these are for cgb only, so i'll leave them commented for now
-    // pub bank: VramBankSelector,
+    /// VRAM bank the tile data comes from, only used in CGB mode
+    pub bank: u8,
+    // // this is for cgb only, so i'll leave it commented for now
     // pub cgb_palette: CgbPaletteSelector 
 }
 
 impl From<u8> for ObjectAttributes {
     fn from(value: u8) -> Self {
-        let priority = (value & 0b1000_0000) > 0;
-        let y_flip = (value & 0b0100_0000) > 0;
-        let x_flip = (value & 0b0010_0000) > 0;
+        let priority = bit_set(value, 7);
+        let y_flip = bit_set(value, 6);
+        let x_flip = bit_set(value, 5);
         let dmg_palette = value.into();
+        let bank = bit_set(value, 3) as u8;
 
         Self {
             priority,
             y_flip,
             x_flip,
-            dmg_palette
+            dmg_palette,
+            bank,
         }
     }
 }
@@ -127,10 +246,10 @@ pub enum ObpSelector {
 
 impl From<u8> for ObpSelector {
     fn from(value: u8) -> Self {
-        match (value & 0b00010_0000) >> 5 {
-            0 => Self::Obp0,
-            1 => Self::Obp1,
-            _ => unreachable!(),
+        if bit_set(value, 4) {
+            Self::Obp1
+        } else {
+            Self::Obp0
         }
     }
 }
@@ -141,6 +260,15 @@ pub enum AddressType {
     Signed,
 }
 
+/// Registers the PPU used for one line, as recorded by the scanline log, see `Ppu::set_scanline_log`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScanlineState {
+    pub lcdc: u8,
+    pub scx: u8,
+    pub scy: u8,
+    pub bgp: u8,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct PpuCoords {
     pub x: u8,
@@ -150,6 +278,9 @@ pub struct PpuCoords {
 #[derive(Clone, Copy, Debug)]
 pub struct Palette {
     colors: [Color; 4],
+    /// colors for each DMG shade, from lightest to darkest
+    shades: [Color; 4],
+    value: u8,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -160,27 +291,35 @@ pub enum PpuStatus {
 }
 
 impl Palette {
-    fn new() -> Self {
-        let colors = Self::from_bgp(0b00011011);
+    fn new(value: u8) -> Self {
+        let colors = Self::from_bgp(value, &PALETTE);
 
-        Self { colors }
+        Self { colors, shades: PALETTE, value }
     }
 
     fn update(&mut self, bgp: u8) {
-        self.colors = Self::from_bgp(bgp);
+        self.value = bgp;
+        self.colors = Self::from_bgp(bgp, &self.shades);
     }
 
-    fn from_bgp(bgp: u8) -> [Color; 4] {
+    /// Replaces the color of each shade, keeping the current palette value
+    fn set_shades(&mut self, shades: [u32; 4]) {
+        // colors are stored as RGBA
+        self.shades = shades.map(|rgb| Color::from_u32(rgb << 8 | 0xFF));
+        self.colors = Self::from_bgp(self.value, &self.shades);
+    }
+
+    fn from_bgp(bgp: u8, shades: &[Color; 4]) -> [Color; 4] {
         let color0 =  bgp       & 0b11;
         let color1 = (bgp >> 2) & 0b11;
         let color2 = (bgp >> 4) & 0b11;
         let color3 = (bgp >> 6) & 0b11;
 
         [
-            PALETTE[color0 as usize],
-            PALETTE[color1 as usize],
-            PALETTE[color2 as usize],
-            PALETTE[color3 as usize],
+            shades[color0 as usize],
+            shades[color1 as usize],
+            shades[color2 as usize],
+            shades[color3 as usize],
         ]
     }
 }
@@ -204,9 +343,29 @@ impl Color {
         Self { inner, transparent: false }
     }
 
+    /// Converts a CGB color, with 5 bits each of red, green and blue from low to high
+    pub(crate) fn from_rgb555(color: u16) -> Self {
+        // scale up to 8 bits, filling the low bits so 0x1F becomes 0xFF
+        let expand = |c: u16| {
+            let c = (c & 0x1F) as u32;
+            c << 3 | c >> 2
+        };
+
+        let r = expand(color);
+        let g = expand(color >> 5);
+        let b = expand(color >> 10);
+
+        Self::from_u32(r << 24 | g << 16 | b << 8 | 0xFF)
+    }
+
     fn to_be_bytes(self) -> [u8; 4] {
         self.inner.to_be_bytes()
     }
+
+    /// Converts to `0RGB`, the same format as frames from `Gbc::next_frame`
+    pub(crate) fn to_0rgb(self) -> u32 {
+        self.inner >> 8
+    }
 }
 
 impl Display for Color {
@@ -219,164 +378,490 @@ impl Ppu {
     pub fn new() -> Self {
         let lcdc = 0x91.into();
         let stat = 0;
-        let coords = PpuCoords { x: 0, y: 0 };
-        let palette = Palette::new();
-        let obj_palettes = [Palette::new(), Palette::new()];
+        // the boot rom hands over on the last line of vblank, just after LY switches to 0, which is what makes STAT 0x85
+        let coords = PpuCoords { x: 0, y: LAST_LINE };
+        // BGP is 0xFC at power on, see `init_io`
+        let palette = Palette::new(0xFC);
+        let obj_palettes = [Palette::new(0b00011011), Palette::new(0b00011011)];
         let fb = vec![0; 3 * WIDTH as usize * HEIGHT as usize];
-        let objects = [None; 10];
-        let status = PpuStatus::Drawing;
+        let objects = [None; OAM_OBJECTS];
+        let status = PpuStatus::VBlank;
 
         Self {
             lcdc,
             stat,
             coords,
+            mode: PpuMode::VBlank,
+            dot: DOTS_PER_TICK,
             palette,
             obj_palettes,
             fb,
             objects,
             status,
+            cgb: false,
+            rendering: true,
+            render_mode: RenderMode::default(),
+            stat_line: false,
+            scanline_log: None,
+            sprite_limit: Some(SPRITE_LIMIT),
+            transfer_dots: MIN_TRANSFER_DOTS,
         }
     }
-    
-    /// Returns status of PPU (either `Drawing` or `VBlank`)
-    /// 
+
+    /// Turns drawing on or off, for benchmarks and test ROMs that only care about serial output
+    ///
+    /// With it off, modes, LY and STAT still change at exactly the same times, but objects aren't picked and no pixels are drawn,
+    /// so `fb` keeps whatever was in it
+    pub fn set_rendering(&mut self, rendering: bool) {
+        self.rendering = rendering;
+    }
+
+    /// Checks if pixels are being drawn, see `set_rendering`
+    pub fn is_rendering(&self) -> bool {
+        self.rendering
+    }
+
+    /// Picks when pixels are drawn, see `RenderMode`. The default is `RenderMode::PixelFifo`
+    ///
+    /// Switching partway through a frame can leave some of `fb` from the last frame until the next one is drawn
+    pub fn set_render_mode(&mut self, mode: RenderMode) {
+        self.render_mode = mode;
+    }
+
+    /// Gets when pixels are drawn, see `set_render_mode`
+    pub fn render_mode(&self) -> RenderMode {
+        self.render_mode
+    }
+
+    /// Sets the most objects that are drawn on one line, or `None` to draw all of them
+    ///
+    /// Real hardware stops at `SPRITE_LIMIT`, which is the default, and games that put more than that on a line flicker
+    /// as they take turns. Lifting the limit gets rid of the flicker, but it isn't authentic, and games that hide objects
+    /// on purpose by putting extras before them in OAM will show them. Timing doesn't change either way
+    pub fn set_sprite_limit(&mut self, limit: Option<u8>) {
+        self.sprite_limit = limit;
+    }
+
+    /// Gets the most objects that are drawn on one line, see `set_sprite_limit`
+    pub fn sprite_limit(&self) -> Option<u8> {
+        self.sprite_limit
+    }
+
+    /// Turns the scanline log on or off, see `scanline_state`
+    ///
+    /// It's off by default, so nothing is recorded unless a debugger asks for it. Turning it off throws away what was
+    /// recorded, and turning it on starts every line out as `ScanlineState::default()` until it's drawn again
+    pub fn set_scanline_log(&mut self, enabled: bool) {
+        self.scanline_log = enabled.then(|| Box::new([ScanlineState::default(); HEIGHT as usize]));
+    }
+
+    /// Gets the LCDC, SCX, SCY and BGP values line `ly` was last drawn with, for debugging raster effects
+    ///
+    /// These are taken when the line's pixel transfer starts, so a game changing them in an HBlank or STAT interrupt
+    /// shows up on the next line. Lines are recorded whether or not `set_rendering` is on
+    ///
+    /// ### Return Variants
+    /// - `Some(state)` if the log is on and `ly` is a visible line
+    /// - `None` if the log is off, or `ly` is in vblank
+    pub fn scanline_state(&self, ly: u8) -> Option<ScanlineState> {
+        self.scanline_log.as_ref()?.get(ly as usize).copied()
+    }
+
+    /// The current mode
+    pub fn mode(&self) -> PpuMode {
+        self.mode
+    }
+
+    /// Dots into the current line, from `0` to `DOTS_PER_LINE - 1`
+    pub fn dot(&self) -> u16 {
+        self.dot
+    }
+
+    /// How many dots the pixel transfer of the current line takes, or took if it's already over
+    ///
+    /// This is from `MIN_TRANSFER_DOTS` up to `MAX_TRANSFER_DOTS`, set when the transfer starts. HBlank gets the rest of
+    /// the line, so it's `DOTS_PER_LINE - OAM_SCAN_DOTS - transfer_dots()` dots long
+    pub fn transfer_dots(&self) -> u16 {
+        self.transfer_dots
+    }
+
+    /// Gets LY as the CPU reads it
+    ///
+    /// This is the current line, except on the last line of vblank, where it only reads 153 for the first M-cycle and then 0.
+    /// The PPU itself still counts that line as 153, this only changes what the CPU sees
+    pub fn ly(&self) -> u8 {
+        if self.coords.y == LAST_LINE && self.dot >= DOTS_PER_TICK {
+            0
+        } else {
+            self.coords.y
+        }
+    }
+
+    /// Runs the PPU for one M-cycle
+    ///
+    /// `status` is `EnterVBlank` for the one tick where vblank starts
+    ///
     /// TODO:
-    /// - BG Enable (Don't show BG if LCDC.0 is cleared)
     /// - Window
-    /// - Sprites
     pub fn tick(&mut self, memory: &Mmu) {
-        match self.status {
-            PpuStatus::EnterVBlank => {
-                self.coords.x += 1;
-                self.status = PpuStatus::VBlank;
-                return;
-            },
-            PpuStatus::VBlank => {
-                self.coords.x += 1;
+        self.tick_at(memory, CLOCK_HZ);
+    }
 
-                if self.coords.x == WIDTH {
-                    let (new_y, overflowed) = self.coords.y.overflowing_add(1);
-                    self.coords.y = new_y;
+    /// Runs the PPU for one M-cycle of a CPU running at `clock_hz`, see `Cpu::clock_hz`
+    ///
+    /// Dots always go at `CLOCK_HZ`, so in double speed an M-cycle is only 2 dots instead of 4
+    pub(crate) fn tick_at(&mut self, memory: &Mmu, clock_hz: u32) {
+        if self.status == PpuStatus::EnterVBlank {
+            self.status = PpuStatus::VBlank;
+        }
 
-                    if overflowed {
-                        self.status = PpuStatus::Drawing;
+        let dots = DOTS_PER_TICK as u32 * CLOCK_HZ / clock_hz;
+
+        for _ in 0..dots {
+            self.step_dot(memory);
+        }
+    }
+
+    /// Runs the PPU for a single dot
+    fn step_dot(&mut self, memory: &Mmu) {
+        match self.mode {
+            PpuMode::OamScan => {
+                // the objects on this line are picked before any pixels are drawn
+                // games can change OAM mid-line, but that only affects the lines after it
+                if self.dot == 0 && self.rendering && self.render_mode != RenderMode::PerFrame {
+                    self.scan_oam(memory);
+                }
+
+                if self.dot + 1 == OAM_SCAN_DOTS {
+                    self.mode = PpuMode::Transfer;
+                    self.transfer_dots = self.transfer_length(memory);
+                    self.log_scanline(memory);
+                }
+            }
+            PpuMode::Transfer => {
+                let fifo = self.rendering && self.render_mode == RenderMode::PixelFifo;
+
+                if fifo && self.dot - OAM_SCAN_DOTS >= TRANSFER_DELAY && self.coords.x < WIDTH {
+                    self.draw_pixel(memory);
+                }
+
+                if self.dot + 1 == OAM_SCAN_DOTS + self.transfer_dots {
+                    if self.rendering && self.render_mode == RenderMode::PerScanline {
+                        self.draw_line(memory);
                     }
+
+                    self.mode = PpuMode::HBlank;
                 }
+            }
+            PpuMode::HBlank | PpuMode::VBlank => {}
+        }
 
-                return;
-            },
-            _ => {}
+        self.dot += 1;
+
+        if self.dot == DOTS_PER_LINE {
+            self.next_line();
+
+            if self.status == PpuStatus::EnterVBlank && self.rendering && self.render_mode == RenderMode::PerFrame {
+                self.draw_frame(memory);
+            }
+        }
+    }
+
+    /// Works out how long the current line's pixel transfer takes, from SCX and the objects on it
+    ///
+    /// Fine scrolling throws away `SCX % 8` pixels at the start of the line, and each object costs 6 dots to fetch, plus up
+    /// to 5 more if it has to wait for the background tile under its leftmost pixel. That wait is only paid by the first
+    /// object in each tile. Objects are picked the same way `scan_oam` does, but always with the hardware limit, so timing
+    /// doesn't change with `set_rendering` or `set_sprite_limit`
+    fn transfer_length(&self, memory: &Mmu) -> u16 {
+        let fine_scroll = (memory.load(SCX).unwrap_or(0) % 8) as u16;
+        let mut dots = MIN_TRANSFER_DOTS + fine_scroll;
+
+        if !self.lcdc.obj_enable {
+            return dots;
+        }
+
+        let mut xs: Vec<u8> = memory
+            .load_block(OAM, OAM_END)
+            .chunks_exact(4)
+            .map(Object::from)
+            .filter(|obj| (self.coords.y + 16).wrapping_sub(obj.y) < self.lcdc.obj_size)
+            .take(SPRITE_LIMIT as usize)
+            .map(|obj| obj.x)
+            // objects past the right edge are never fetched
+            .filter(|&x| x < WIDTH + 8)
+            .collect();
+
+        // objects are fetched left to right as the transfer reaches them
+        xs.sort_unstable();
+
+        let mut last_tile = None;
+
+        for x in xs {
+            dots += OBJECT_FETCH_DOTS;
+
+            // one hanging all the way off the left edge waits the longest
+            if x == 0 {
+                dots += 5;
+                continue;
+            }
+
+            // position in the line of background pixels, including the ones thrown away by fine scrolling
+            let pixel = x as u16 + fine_scroll;
+            let tile = pixel / 8;
+
+            if last_tile != Some(tile) {
+                // the rest of the tile after the object's leftmost pixel, less the 2 dots that overlap the fetch
+                dots += (7 - pixel % 8).saturating_sub(2);
+                last_tile = Some(tile);
+            }
+        }
+
+        dots.min(MAX_TRANSFER_DOTS)
+    }
+
+    /// Records the registers the current line is being drawn with, if the scanline log is on
+    fn log_scanline(&mut self, memory: &Mmu) {
+        let state = ScanlineState {
+            lcdc: self.lcdc.into(),
+            scx: memory.load(SCX).unwrap_or(0),
+            scy: memory.load(SCY).unwrap_or(0),
+            bgp: self.palette.value,
+        };
+
+        if let Some(ref mut log) = self.scanline_log {
+            log[self.coords.y as usize] = state;
+        }
+    }
+
+    /// Moves on to the start of the next line, entering or leaving vblank if needed
+    fn next_line(&mut self) {
+        self.dot = 0;
+        self.coords.x = 0;
+        self.coords.y += 1;
+
+        if self.coords.y == HEIGHT {
+            self.mode = PpuMode::VBlank;
+            self.status = PpuStatus::EnterVBlank;
+        } else if self.coords.y == HEIGHT + VBLANK_LINES {
+            self.coords.y = 0;
+            self.mode = PpuMode::OamScan;
+            self.status = PpuStatus::Drawing;
+        } else if self.mode != PpuMode::VBlank {
+            self.mode = PpuMode::OamScan;
+        }
+    }
+
+    /// Draws the rest of the line at `coords` into the framebuffer, with the objects from the last `scan_oam`
+    fn draw_line(&mut self, memory: &Mmu) {
+        while self.coords.x < WIDTH {
+            self.draw_pixel(memory);
         }
+    }
+
+    /// Draws every line into the framebuffer, leaving `coords` where they were
+    fn draw_frame(&mut self, memory: &Mmu) {
+        let coords = self.coords;
+
+        for y in 0..HEIGHT {
+            self.coords = PpuCoords { x: 0, y };
+            self.scan_oam(memory);
+            self.draw_line(memory);
+        }
+
+        self.coords = coords;
+    }
+
+    /// Draws the pixel at `coords` into the framebuffer, then moves to the next one
+    fn draw_pixel(&mut self, memory: &Mmu) {
+        let color = self.pixel_color(memory);
+
+        let index = self.coords.x as usize + self.coords.y as usize * WIDTH as usize;
+        self.fb[index*3..index*3+3].copy_from_slice(&color.to_be_bytes()[0..3]);
+        self.coords.x += 1;
+    }
 
-        let address_type = self.lcdc.bg_addressing;
-        let bg_map_area: u16 = self.lcdc.bg_map_area;
+    /// Gets the color of the pixel at `coords`, with the objects from the last `scan_oam`
+    fn pixel_color(&self, memory: &Mmu) -> Color {
 
         let scy = memory.load(SCY).unwrap_or(0);
         let tile_x = ((self.coords.x / TILE_WIDTH).wrapping_add(memory.load(SCX).unwrap_or(0) / TILE_WIDTH)) % WIDTH_IN_TILES;
         let tile_y = (self.coords.y.wrapping_add(scy)) / TILE_HEIGHT;
-        let tilemap_offset = tile_x as usize + tile_y as usize * WIDTH_IN_TILES as usize;
-        let tilemap_addr = bg_map_area + tilemap_offset as u16;
-
-        // the byte in the tilemap points to the tile index
-        let tile_index = memory.load(tilemap_addr).unwrap_or(0);
+        let tilemap_offset = tile_x as u16 + tile_y as u16 * WIDTH_IN_TILES as u16;
 
         // get the y offset within the tile
         let tile_y_offset = (self.coords.y.wrapping_add(scy)) % TILE_HEIGHT;
-        let bg_data_addr = address_type.convert_offset(tile_index);
-        let bg_data_addr = bg_data_addr + tile_y_offset as u16 * ROW_SIZE as u16;
-
-        // get the object to draw, if any
-        let obj = self.objects.iter().find(
-            |obj| obj.map(|obj| (self.coords.x + 8).overflowing_sub(obj.x).0 < 8).unwrap_or(false)
-        ).map(|obj| *obj).flatten();
 
         // get the current line of the bg tile data
         // 2 bytes per sprite row, combined into 8 2-bit palette indexes
-        let bg_tile_line = memory.load_block(bg_data_addr, bg_data_addr + 1);
-
-        let color = if let Some(obj) = obj {
-            if !self.lcdc.obj_enable {
-                self.decode_color(&bg_tile_line)
-            } else {
-                let obj_y_offset = (self.coords.y.wrapping_add(scy)) % self.lcdc.obj_size;
-                // get the address of the current object line
-                let obj_data_addr = (UNSIGNED_BASE + obj.index as u16 * TILE_BYTES as u16) + (obj_y_offset as u16 * ROW_SIZE as u16);
-
-                //get the current line of the object tile data
-                let obj_tile_line = memory.load_block(obj_data_addr, obj_data_addr + 1);
-                let color = self.decode_color(&obj_tile_line);
-
-                if self.coords.y < 2 {
-                    dbg!(obj);
-                    println!("{color}")
-                }
+        let bg_tile_line = self.bg_tile_row(memory, tilemap_offset, tile_y_offset);
+        let bg = self.decode_color(&bg_tile_line);
 
-                // color 0 is transparent for objects, so we should fall back to the background
-                if color.transparent {
-                    self.decode_color(&bg_tile_line)
-                } else {
-                    color
-                }
-            }
+        let obj = if self.lcdc.obj_enable {
+            self.object_pixel(memory)
         } else {
-            self.decode_color(&bg_tile_line)
+            None
         };
 
-        let index = self.coords.x as usize + self.coords.y as usize * WIDTH as usize;
-        self.fb[index*3..index*3+3].copy_from_slice(&color.to_be_bytes()[0..3]);
-        self.coords.x += 1;
+        match obj {
+            // objects with priority set only show up over background color 0
+            Some((color, behind_bg)) if !behind_bg || bg.transparent => color,
+            _ => bg,
+        }
+    }
 
-        self.status = PpuStatus::Drawing;
+    /// Gets row `row` of the tile at `tilemap_offset` in the background map selected in LCDC, as its 2 bytes of tile data
+    ///
+    /// The map is always read from VRAM bank 0, since bank 1 has the tile attributes in the same place. In CGB mode bit 3 of
+    /// those attributes picks the bank the tile data comes from, otherwise it's bank 0. The bank selected with `VBK` is only
+    /// for the CPU, so it never matters here
+    fn bg_tile_row(&self, memory: &Mmu, tilemap_offset: u16, row: u8) -> [u8; 2] {
+        let map_offset = (self.lcdc.bg_map_area - UNSIGNED_BASE + tilemap_offset) as usize;
+        let tile_index = memory.vram_bank(0)[map_offset].unwrap_or(0);
+        let bank = if self.cgb { bit_set(memory.vram_bank(1)[map_offset].unwrap_or(0), 3) as u8 } else { 0 };
+
+        let data_addr = self.lcdc.bg_addressing.convert_offset(tile_index) + row as u16 * ROW_SIZE as u16;
+        let offset = (data_addr - UNSIGNED_BASE) as usize;
+        let vram = memory.vram_bank(bank);
+
+        [vram[offset].unwrap_or(0), vram[offset + 1].unwrap_or(0)]
+    }
 
-        if self.coords.x == WIDTH {
-            self.coords.x = 0;
-            self.coords.y += 1;
+    /// Draws a whole frame into `out` from what's in memory right now, one `0RGB` pixel per element
+    ///
+    /// This doesn't touch `fb` or the PPU's timing. Everything is drawn with the current registers,
+    /// so effects from games changing them partway through a frame don't show up
+    ///
+    /// ### Return Variants
+    /// - `Ok(())` if the frame was drawn
+    /// - `Err(Error::BufferLength)` if `out` isn't `SCREEN_WIDTH * SCREEN_HEIGHT` long, in which case it's left alone
+    pub fn render_into(&mut self, memory: &Mmu, out: &mut [u32]) -> Result<(), Error> {
+        if out.len() != SCREEN_WIDTH * SCREEN_HEIGHT {
+            return Err(Error::BufferLength(out.len()));
+        }
 
-            if self.coords.y >= HEIGHT {
-                self.status = PpuStatus::EnterVBlank;
-                return;
+        let coords = self.coords;
+        let objects = self.objects;
+
+        for (y, row) in out.chunks_exact_mut(SCREEN_WIDTH).enumerate() {
+            self.coords.y = y as u8;
+            self.scan_oam(memory);
+
+            for (x, pixel) in row.iter_mut().enumerate() {
+                self.coords.x = x as u8;
+                *pixel = self.pixel_color(memory).to_0rgb();
             }
+        }
 
-            // find objects on this line
-            // TODO: Update for 8x16
-            self.objects = Default::default();
-            let objects = memory.load_block(OAM, OAM_END);
-            let mut obj_index = 0;
-
-            for index in 0..objects.len() / 4 {
-                let obj_bytes = &objects[index*4..index*4+4];
-                let obj: Object = obj_bytes.into();
-                
-                if (self.coords.y + 16).overflowing_sub(obj.y).0 < 8 {
-                    self.objects[obj_index] = Some(obj);
-                    obj_index += 1;
-
-                    if obj_index == 10 { break; }
-                }
+        self.coords = coords;
+        self.objects = objects;
+
+        Ok(())
+    }
+
+    /// Selects the objects that overlap the current line, up to the sprite limit
+    fn scan_oam(&mut self, memory: &Mmu) {
+        self.objects = [None; OAM_OBJECTS];
+        let limit = self.sprite_limit.map_or(OAM_OBJECTS, usize::from);
+        let objects = memory.load_block(OAM, OAM_END);
+        let mut obj_index = 0;
+
+        for index in 0..objects.len() / 4 {
+            let obj_bytes = &objects[index*4..index*4+4];
+            let obj: Object = obj_bytes.into();
+
+            if (self.coords.y + 16).wrapping_sub(obj.y) < self.lcdc.obj_size {
+                if obj_index == limit { break; }
+
+                self.objects[obj_index] = Some(obj);
+                obj_index += 1;
             }
         }
     }
 
+    /// Gets the color of the object pixel at the current position, and whether it's drawn behind the background
+    ///
+    /// With bit 0 of OPRI set, which is how DMG games are run, the object with the lowest X wins, with ties going to the one earliest in OAM.
+    /// Otherwise it's just the earliest in OAM.
+    /// Color 0 is transparent, so a lower priority object can show through it
+    fn object_pixel(&self, memory: &Mmu) -> Option<(Color, bool)> {
+        let by_x = memory.load(OPRI).unwrap_or(0x01) & 1 > 0;
+        let mut best: Option<(u8, Color, bool)> = None;
+
+        let covering = self
+            .objects
+            .iter()
+            .flatten()
+            .filter(|obj| (self.coords.x + 8).wrapping_sub(obj.x) < TILE_WIDTH);
+
+        for obj in covering {
+            let color = self.decode_object_color(memory, obj);
+
+            if color.transparent {
+                continue;
+            }
+
+            // objects are in OAM order, so only a strictly lower x can take over
+            if best.is_none_or(|(x, _, _)| by_x && obj.x < x) {
+                best = Some((obj.x, color, obj.attributes.priority));
+            }
+        }
+
+        best.map(|(_, color, behind_bg)| (color, behind_bg))
+    }
+
+    /// Get the color value of `obj` for the current pixel
+    fn decode_object_color(&self, memory: &Mmu, obj: &Object) -> Color {
+        // position of the current pixel within the object
+        let mut row = (self.coords.y + 16).wrapping_sub(obj.y);
+        let mut col = (self.coords.x + 8).wrapping_sub(obj.x);
+
+        if obj.attributes.y_flip {
+            row = self.lcdc.obj_size - 1 - row;
+        }
+
+        if obj.attributes.x_flip {
+            col = TILE_WIDTH - 1 - col;
+        }
+
+        // 8x16 objects ignore the lowest bit of the index, the top tile is always even
+        let tile_index = if self.lcdc.obj_size == 16 { obj.index & 0xFE } else { obj.index };
+
+        // get the address of the current object line, objects always use unsigned addressing
+        let obj_data_addr = (UNSIGNED_BASE + tile_index as u16 * TILE_BYTES as u16) + (row as u16 * ROW_SIZE as u16);
+
+        // get the current line of the object tile data, from either bank in cgb mode
+        let bank = if self.cgb { obj.attributes.bank } else { 0 };
+        let offset = (obj_data_addr - UNSIGNED_BASE) as usize;
+        let vram = memory.vram_bank(bank);
+        let obj_tile_line = [vram[offset].unwrap_or(0), vram[offset + 1].unwrap_or(0)];
+        let color_value = interleave(&obj_tile_line, col);
+        let palette = match obj.attributes.dmg_palette {
+            ObpSelector::Obp0 => &self.obj_palettes[0],
+            ObpSelector::Obp1 => &self.obj_palettes[1],
+        };
+
+        // unlike the background, color 0 of an object is always transparent instead of coming from the palette
+        Color {
+            inner: palette[color_value].inner,
+            transparent: color_value == 0,
+        }
+    }
+
     /// Get the color value for the current pixel given a tile row
     pub fn decode_color(&self, tile_row: &[u8]) -> Color {
         if !self.lcdc.bg_enable {
-            return Color::from_u32(0xFFFFFFFF);
+            // objects always go on top while the background is off
+            return Color {
+                inner: 0xFFFFFFFF,
+                transparent: true,
+            };
         }
 
-        // horizontal offset of the bit within the sprite
         // we're just rendering one pixel here
         // this will be more efficient when we implement the FIFO
-        let x_offset = TILE_WIDTH - 1 - self.coords.x % TILE_WIDTH;
+        let color_value = interleave(tile_row, self.coords.x % TILE_WIDTH);
 
-        // extract relevant bits
-        // we shift the color bytes first so it's less messy to get 0 or 1
-        // first byte in memory has its bits after the second byte, probably cause little endian
-        let low = (tile_row[0] >> x_offset) & 1;
-        let high = (tile_row[1] >> x_offset) & 1;
-
-        // high gets shifted up to fill in the upper bit
-        let color_value = (high << 1) | low;
-        
         Color {
             inner: self.palette[color_value].inner,
             transparent: color_value == 0,
@@ -384,7 +869,10 @@ impl Ppu {
     }
 
     /// Refreshes the VRAM debug window, rendering the current VRAM tile data
-    pub fn debug_show(&mut self, memory: &Mmu, size: [usize; 2], fb: &mut [u8]) {
+    ///
+    /// Tiles are colored with the DMG background palette if `palette` is `None`,
+    /// otherwise with that CGB background palette (0-7) from palette RAM
+    pub fn debug_show(&mut self, memory: &Mmu, size: [usize; 2], palette: Option<u8>, fb: &mut [u8]) {
         // go through VRAM and put each pixel into fb
         const BYTES_PER_TILE_ROW: u8 = ROW_SIZE;
         const START_ADDR: u16 = UNSIGNED_BASE;
@@ -398,17 +886,11 @@ impl Ppu {
                     let tiles = memory.load_block(current_addr, current_addr + 1);
 
                     for col in 0..TILE_WIDTH {
-                        let x_offset = TILE_WIDTH - 1 - col;
-
-                        // extract relevant bits
-                        // we shift the color bytes first so it's less messy to get 0 or 1
-                        // first byte in memory has its bits after the second byte, probably cause little endian
-                        let low = (tiles[0] >> x_offset) & 1;
-                        let high = (tiles[1] >> x_offset) & 1;
-
-                        // high gets shifted up to fill in the upper bit
-                        let color_value = (high << 1) | low;
-                        let color = self.palette[color_value];
+                        let color_value = interleave(&tiles, col);
+                        let color = match palette {
+                            Some(palette) => Color::from_rgb555(memory.bg_palettes.color(palette, color_value)),
+                            None => self.palette[color_value],
+                        };
 
                         let x = tile * TILE_WIDTH as usize + col as usize;
                         let y = row * TILE_HEIGHT as usize + tile_row as usize;
@@ -423,10 +905,130 @@ impl Ppu {
         }
     }
 
+    /// Renders the whole 256x256 background map as palette indexes (0-3), before they go through BGP
+    ///
+    /// Uses the map and tile data areas currently selected in LCDC, and ignores scrolling and whether the background is enabled.
+    /// Pixels are one byte each, row by row
+    pub fn render_bg_indices(&self, memory: &Mmu) -> Vec<u8> {
+        let mut indices = vec![0; BG_MAP_PIXELS * BG_MAP_PIXELS];
+
+        for tile_number in 0..WIDTH_IN_TILES as usize * WIDTH_IN_TILES as usize {
+            let tile_x = tile_number % WIDTH_IN_TILES as usize * TILE_WIDTH as usize;
+            let tile_y = tile_number / WIDTH_IN_TILES as usize * TILE_HEIGHT as usize;
+
+            for row in 0..TILE_HEIGHT {
+                let tile_row = self.bg_tile_row(memory, tile_number as u16, row);
+                let start = tile_x + (tile_y + row as usize) * BG_MAP_PIXELS;
+
+                for col in 0..TILE_WIDTH {
+                    indices[start + col as usize] = interleave(&tile_row, col);
+                }
+            }
+        }
+
+        indices
+    }
+
+    /// Renders the whole 256x256 background map through BGP, 3 bytes per pixel like `fb`
+    ///
+    /// See `render_bg_indices` for what gets drawn
+    pub fn render_bg(&self, memory: &Mmu) -> Vec<u8> {
+        self.render_bg_indices(memory)
+            .into_iter()
+            .flat_map(|index| {
+                let [r, g, b, _] = self.palette[index].to_be_bytes();
+                [r, g, b]
+            })
+            .collect()
+    }
+
+    /// Gets the current background palettes as `0RGB` colors, for palette viewers and the like
+    ///
+    /// In CGB mode these are all 8 palettes from palette RAM. DMG games only have BGP, so only palette 0 is meaningful,
+    /// holding the shade each color index maps to, and the rest are black
+    pub fn bg_palettes(&self, memory: &Mmu) -> [[u32; 4]; 8] {
+        if self.cgb {
+            return Self::cgb_palettes(&memory.bg_palettes);
+        }
+
+        let mut palettes = [[0; 4]; 8];
+        palettes[0] = Self::dmg_palette(&self.palette);
+
+        palettes
+    }
+
+    /// Gets the current object palettes as `0RGB` colors, like `bg_palettes`
+    ///
+    /// DMG games have OBP0 and OBP1 as palettes 0 and 1, and the rest are black. Color 0 is always transparent for objects, even though it has a color here
+    pub fn obj_palettes(&self, memory: &Mmu) -> [[u32; 4]; 8] {
+        if self.cgb {
+            return Self::cgb_palettes(&memory.obj_palettes);
+        }
+
+        let mut palettes = [[0; 4]; 8];
+        palettes[0] = Self::dmg_palette(&self.obj_palettes[0]);
+        palettes[1] = Self::dmg_palette(&self.obj_palettes[1]);
+
+        palettes
+    }
+
+    fn dmg_palette(palette: &Palette) -> [u32; 4] {
+        [0, 1, 2, 3].map(|index| palette[index].to_0rgb())
+    }
+
+    fn cgb_palettes(ram: &PaletteRam) -> [[u32; 4]; 8] {
+        let mut palettes = [[0; 4]; 8];
+
+        for (number, palette) in palettes.iter_mut().enumerate() {
+            for (index, color) in palette.iter_mut().enumerate() {
+                *color = Color::from_rgb555(ram.color(number as u8, index as u8)).to_0rgb();
+            }
+        }
+
+        palettes
+    }
+
     pub fn set_lcdc(&mut self, lcdc: u8) {
         self.lcdc = lcdc.into();
     }
 
+    /// Reads `STAT` as the CPU sees it, with the mode and LY=LYC bits filled in
+    ///
+    /// Bit 7 isn't connected and always reads 1. While the LCD is off, the mode and LY=LYC bits read 0
+    pub fn read_stat(&self, lyc: u8) -> u8 {
+        let interrupts = self.stat & 0b0111_1000;
+
+        if !self.lcdc.lcd_enable {
+            return 0x80 | interrupts;
+        }
+
+        let coincidence = if self.ly() == lyc { bit(2) } else { 0 };
+
+        0x80 | interrupts | coincidence | self.mode as u8
+    }
+
+    /// Updates the STAT interrupt line, which is high while any source enabled in `STAT` is active
+    ///
+    /// All the sources share one line, so the interrupt is only requested when it goes from low to high. LY=LYC is checked
+    /// against LY as the CPU reads it, so an LYC of 0 matches from the second M-cycle of line 153 and stays matched
+    /// through line 0, firing once per frame at line 153 instead of at line 0
+    ///
+    /// ### Return Variants
+    /// - `true` if the line just went high and a STAT interrupt should be requested
+    /// - `false` otherwise
+    pub(crate) fn update_stat_line(&mut self, lyc: u8) -> bool {
+        let line = self.lcdc.lcd_enable
+            && ((bit_set(self.stat, 6) && self.ly() == lyc)
+                || (bit_set(self.stat, 5) && self.mode == PpuMode::OamScan)
+                || (bit_set(self.stat, 4) && self.mode == PpuMode::VBlank)
+                || (bit_set(self.stat, 3) && self.mode == PpuMode::HBlank));
+
+        let rising = line && !self.stat_line;
+        self.stat_line = line;
+
+        rising
+    }
+
     pub fn set_stat(&mut self, stat: u8) {
         self.stat = stat;
     }
@@ -438,10 +1040,30 @@ impl Ppu {
     pub fn set_obj_palette(&mut self, index: usize, obp: u8) {
         self.obj_palettes[index].update(obp);
     }
+
+    /// Sets the colors used for each DMG shade, see `CompatPalette`
+    pub fn set_compat_palette(&mut self, palette: &CompatPalette) {
+        self.palette.set_shades(palette.bg);
+        self.obj_palettes[0].set_shades(palette.obj0);
+        self.obj_palettes[1].set_shades(palette.obj1);
+    }
+}
+
+/// Gets the palette index (0-3) of pixel `col` in a tile row, where 0 is the leftmost pixel
+///
+/// Each row is 2 bytes, the first has the low bit of every pixel and the second has the high bit
+fn interleave(tile_row: &[u8], col: u8) -> u8 {
+    let x_offset = TILE_WIDTH - 1 - col;
+
+    // we shift the color bytes first so it's less messy to get 0 or 1
+    let low = (tile_row[0] >> x_offset) & 1;
+    let high = (tile_row[1] >> x_offset) & 1;
+
+    (high << 1) | low
 }
 
 impl AddressType {
-    fn convert_offset(&self, index: u8) -> u16 {
+    pub(crate) fn convert_offset(&self, index: u8) -> u16 {
         match self {
             AddressType::Unsigned => {
                 let offset = index as u16 * 16;
@@ -468,4 +1090,626 @@ impl From<&[u8]> for Object {
             Self { y: 0, x: 0, index: 0, attributes: 0.into() }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{bits::bit, error::Error, memory::{mbc::MbcSelector, Mmu, BCPD, BCPS, BGP, LCDC, LYC, OPRI, SCX, VBK}};
+
+    use super::{
+        Ppu, PpuMode, RenderMode, PpuStatus, BG_MAP_PIXELS, SPRITE_LIMIT, DOTS_PER_LINE, DOTS_PER_TICK, HEIGHT, MIN_TRANSFER_DOTS, OAM_SCAN_DOTS,
+        SCREEN_HEIGHT, SCREEN_WIDTH, TICKS_PER_FRAME, WIDTH,
+    };
+
+    const TICKS_PER_LINE: usize = (DOTS_PER_LINE / DOTS_PER_TICK) as usize;
+
+    fn init() -> (Ppu, Mmu) {
+        let mut ppu = Ppu::new();
+        let memory = Mmu::for_testing();
+
+        // LCD on, unsigned tile data, objects enabled
+        ppu.set_lcdc(0b1001_0011);
+        // every color index gets a different shade, with color 3 the lightest
+        ppu.set_palette(0b0001_1011);
+
+        // power on is at the end of vblank, so finish it off to start at the top of a frame
+        while ppu.mode() == PpuMode::VBlank {
+            ppu.tick(&memory);
+        }
+
+        (ppu, memory)
+    }
+
+    fn pixel(ppu: &Ppu, x: usize, y: usize) -> &[u8] {
+        let index = x + y * WIDTH as usize;
+        &ppu.fb[index * 3..index * 3 + 3]
+    }
+
+    #[test]
+    fn object_vram_bank() {
+        let frame = |cgb: bool| {
+            let (mut ppu, mut memory) = init();
+            ppu.cgb = cgb;
+
+            // tile 1 is solid color 3 in bank 1, and empty in bank 0
+            memory.set(VBK, 1);
+            memory.splice(0x8010, &[0xFF; 16]);
+            memory.set(VBK, 0);
+            // object 0 covers the top left 8x8 pixels with tile 1, from bank 1
+            memory.splice(0xFE00, &[16, 8, 1, 0b0000_1000]);
+
+            for _ in 0..TICKS_PER_LINE {
+                ppu.tick(&memory);
+            }
+
+            (pixel(&ppu, 0, 0).to_vec(), pixel(&ppu, 8, 0).to_vec())
+        };
+
+        let (object, background) = frame(true);
+        assert_ne!(object, background);
+
+        // outside of cgb mode the bit is ignored, and bank 0 has nothing to draw
+        let (object, background) = frame(false);
+        assert_eq!(object, background);
+    }
+
+    #[test]
+    fn bg_vram_bank() {
+        let frame = |cgb: bool| {
+            let (mut ppu, mut memory) = init();
+            ppu.cgb = cgb;
+
+            // tile 1 is solid color 3 in bank 0, and the top left of the map uses it
+            memory.splice(0x8010, &[0xFF; 16]);
+            memory.set(0x9800, 0x01);
+            // bank 1 has the attributes in the same place, which picks bank 1 for the tile data
+            memory.set(VBK, 1);
+            memory.set(0x9800, 0b0000_1000);
+
+            // VBK stays on bank 1 the whole time
+            for _ in 0..TICKS_PER_LINE {
+                ppu.tick(&memory);
+            }
+
+            (pixel(&ppu, 0, 0).to_vec(), pixel(&ppu, 8, 0).to_vec())
+        };
+
+        // the map and tile data still come from bank 0
+        let (tile, background) = frame(false);
+        assert_ne!(tile, background);
+
+        // in cgb mode the attribute picks bank 1, where tile 1 is empty
+        let (tile, background) = frame(true);
+        assert_eq!(tile, background);
+    }
+
+    #[test]
+    fn oam_snapshot() {
+        let (mut ppu, mut memory) = init();
+
+        // tile 1 is solid color 3
+        memory.splice(0x8010, &[0xFF; 16]);
+        // object 0 covers the top left 8x8 pixels with tile 1
+        memory.splice(0xFE00, &[16, 8, 1, 0]);
+
+        // start OAM scan on line 0, which picks the objects for the line
+        ppu.tick(&memory);
+
+        // move the object offscreen partway through the line
+        memory.set(0xFE00, 0);
+
+        for _ in 1..TICKS_PER_LINE * 2 {
+            ppu.tick(&memory);
+        }
+
+        let object = pixel(&ppu, 0, 0).to_vec();
+        let background = pixel(&ppu, 8, 0).to_vec();
+        assert_ne!(object, background);
+
+        // the rest of line 0 still has the object
+        for x in 1..8 {
+            assert_eq!(pixel(&ppu, x, 0), object);
+        }
+
+        // line 1 was scanned after the object moved
+        assert_eq!(pixel(&ppu, 0, 1), background);
+    }
+
+    #[test]
+    fn object_flip() {
+        let (mut ppu, mut memory) = init();
+
+        // tile 1 has only its top left pixel set
+        memory.splice(0x8010, &[0x80, 0x80]);
+        // object 0 is flipped both ways, so that pixel moves to the bottom right
+        memory.splice(0xFE00, &[16, 8, 1, 0b0110_0000]);
+
+        for _ in 0..TICKS_PER_LINE * 8 {
+            ppu.tick(&memory);
+        }
+
+        let background = pixel(&ppu, 0, 0).to_vec();
+        assert_ne!(pixel(&ppu, 7, 7), background);
+        assert_eq!(pixel(&ppu, 0, 7), background);
+    }
+
+    #[test]
+    fn frame_length() {
+        let (mut ppu, memory) = init();
+
+        for _ in 0..TICKS_PER_LINE * HEIGHT as usize {
+            ppu.tick(&memory);
+        }
+
+        assert_eq!(ppu.status, PpuStatus::EnterVBlank);
+
+        for _ in TICKS_PER_LINE * HEIGHT as usize..TICKS_PER_FRAME {
+            assert_ne!(ppu.status, PpuStatus::Drawing);
+            assert_eq!(ppu.mode(), PpuMode::VBlank);
+            ppu.tick(&memory);
+        }
+
+        // back at the top of the screen for the next frame
+        assert_eq!(ppu.status, PpuStatus::Drawing);
+        assert_eq!(ppu.mode(), PpuMode::OamScan);
+        assert_eq!((ppu.coords.x, ppu.coords.y, ppu.dot()), (0, 0, 0));
+    }
+
+    #[test]
+    fn mode_durations() {
+        let (mut ppu, memory) = init();
+        let mut dots = [0; 4];
+
+        // something no palette will draw
+        ppu.fb.fill(0x12);
+
+        for _ in 0..TICKS_PER_LINE {
+            // every mode lasts a multiple of 4 dots, so ticks never straddle 2 modes
+            dots[ppu.mode() as usize] += DOTS_PER_TICK;
+            ppu.tick(&memory);
+        }
+
+        assert_eq!(dots[PpuMode::OamScan as usize], OAM_SCAN_DOTS);
+        assert_eq!(dots[PpuMode::Transfer as usize], MIN_TRANSFER_DOTS);
+        assert_eq!(dots[PpuMode::HBlank as usize], DOTS_PER_LINE - OAM_SCAN_DOTS - MIN_TRANSFER_DOTS);
+        assert_eq!(dots[PpuMode::VBlank as usize], 0);
+
+        // the whole line was drawn
+        assert_eq!(ppu.coords.y, 1);
+        assert_ne!(pixel(&ppu, WIDTH as usize - 1, 0), &[0x12; 3]);
+        assert_eq!(pixel(&ppu, 0, 1), &[0x12; 3]);
+    }
+
+    #[test]
+    fn transfer_penalties() {
+        let (mut ppu, mut memory) = init();
+
+        // 11 dots for the first in a tile, 6 for another in the same tile, 6 + 1 for the middle of a tile,
+        // 11 for one off the left edge, and nothing for one off the right
+        for (index, x) in [8, 9, 20, 0, 170].into_iter().enumerate() {
+            memory.splice(0xFE00 + index as u16 * 4, &[16, x, 0, 0]);
+        }
+
+        let mut dots = [0; 4];
+
+        for _ in 0..DOTS_PER_LINE {
+            dots[ppu.mode() as usize] += 1;
+            ppu.step_dot(&memory);
+        }
+
+        assert_eq!(ppu.transfer_dots(), MIN_TRANSFER_DOTS + 35);
+        assert_eq!(dots[PpuMode::OamScan as usize], OAM_SCAN_DOTS);
+        assert_eq!(dots[PpuMode::Transfer as usize], ppu.transfer_dots());
+        assert_eq!(dots[PpuMode::HBlank as usize], DOTS_PER_LINE - OAM_SCAN_DOTS - ppu.transfer_dots());
+        assert_eq!(dots.iter().sum::<u16>(), DOTS_PER_LINE);
+        assert_eq!(ppu.coords.y, 1);
+
+        // fine scrolling moves where the objects sit in their tiles too
+        memory.set(SCX, 3);
+        ppu.coords.y = 0;
+
+        for _ in 0..DOTS_PER_LINE {
+            ppu.step_dot(&memory);
+        }
+
+        assert_eq!(ppu.transfer_dots(), MIN_TRANSFER_DOTS + 3 + 8 + 6 + 6 + 11);
+    }
+
+    #[test]
+    fn object_transparency() {
+        let (mut ppu, mut memory) = init();
+
+        ppu.set_palette(0b1110_0100);
+        ppu.set_obj_palette(0, 0b1110_0100);
+        ppu.set_obj_palette(1, 0b0001_1011);
+
+        // tile 0 is solid color 3 for the background
+        memory.splice(0x8000, &[0xFF; 16]);
+        // tile 1 row 0 is color 1 on the left half and color 0 on the right
+        memory.splice(0x8010, &[0xF0, 0x00]);
+
+        // one object with OBP0, one behind the background, and one with OBP1
+        memory.splice(0xFE00, &[16, 8, 1, 0]);
+        memory.splice(0xFE04, &[16, 16, 1, 0b1000_0000]);
+        memory.splice(0xFE08, &[16, 24, 1, 0b0001_0000]);
+
+        for _ in 0..TICKS_PER_LINE {
+            ppu.tick(&memory);
+        }
+
+        let background = pixel(&ppu, 40, 0).to_vec();
+        let obp0 = pixel(&ppu, 0, 0).to_vec();
+        let obp1 = pixel(&ppu, 16, 0).to_vec();
+
+        assert_ne!(obp0, background);
+        assert_ne!(obp1, background);
+        assert_ne!(obp0, obp1);
+
+        for x in 0..4 {
+            assert_eq!(pixel(&ppu, x, 0), obp0);
+            assert_eq!(pixel(&ppu, x + 16, 0), obp1);
+            // the background isn't color 0, so it covers the object
+            assert_eq!(pixel(&ppu, x + 8, 0), background);
+        }
+
+        // color 0 lets the background through
+        for x in [4, 5, 6, 7, 12, 20] {
+            assert_eq!(pixel(&ppu, x, 0), background);
+        }
+    }
+
+    #[test]
+    fn ly_153_reads_0_early() {
+        let (mut ppu, memory) = init();
+
+        for _ in 0..TICKS_PER_LINE * 153 {
+            ppu.tick(&memory);
+        }
+
+        // 153 for the first M-cycle, then 0 for the rest of the line while still in vblank
+        assert_eq!(ppu.ly(), 153);
+        assert_eq!(ppu.read_stat(153) & 0b0100, 0b0100);
+
+        for _ in 0..TICKS_PER_LINE - 1 {
+            ppu.tick(&memory);
+            assert_eq!(ppu.ly(), 0);
+            assert_eq!(ppu.mode(), PpuMode::VBlank);
+        }
+
+        assert_eq!(ppu.read_stat(0) & 0b0100, 0b0100);
+
+        // then the real line 0
+        ppu.tick(&memory);
+        assert_eq!(ppu.ly(), 0);
+        assert_eq!(ppu.mode(), PpuMode::OamScan);
+    }
+
+    #[test]
+    fn lyc_interrupts() {
+        let count = |lyc| {
+            let (mut ppu, memory) = init();
+            ppu.set_stat(bit(6));
+
+            // get past the first line 0, which matches right away for an LYC of 0
+            for _ in 0..TICKS_PER_LINE * 154 {
+                ppu.tick(&memory);
+                ppu.update_stat_line(lyc);
+            }
+
+            let mut lines = Vec::new();
+
+            for _ in 0..TICKS_PER_LINE * 154 * 2 {
+                ppu.tick(&memory);
+
+                if ppu.update_stat_line(lyc) {
+                    lines.push((ppu.coords.y, ppu.dot()));
+                }
+            }
+
+            lines
+        };
+
+        // once per frame, on the M-cycle LY starts reading 0
+        assert_eq!(count(0), [(153, DOTS_PER_TICK); 2]);
+        assert_eq!(count(153), [(153, 0); 2]);
+        assert_eq!(count(10), [(10, 0); 2]);
+    }
+
+    #[test]
+    fn object_priority_mode() {
+        let draw = |opri| {
+            let (mut ppu, mut memory) = init();
+
+            ppu.set_obj_palette(0, 0b1110_0100);
+            ppu.set_obj_palette(1, 0b0001_1011);
+            memory.set(OPRI, opri);
+
+            // tile 1 row 0 is solid color 1
+            memory.splice(0x8010, &[0xFF, 0x00]);
+            // the first object in OAM is to the right of the second, and they overlap from x = 8 to 11
+            memory.splice(0xFE00, &[16, 16, 1, 0]);
+            memory.splice(0xFE04, &[16, 12, 1, 0b0001_0000]);
+
+            for _ in 0..TICKS_PER_LINE {
+                ppu.tick(&memory);
+            }
+
+            let [first, second] = [pixel(&ppu, 15, 0), pixel(&ppu, 4, 0)].map(<[u8]>::to_vec);
+            let overlap = pixel(&ppu, 8, 0).to_vec();
+            assert_ne!(first, second);
+
+            (overlap == first, overlap == second)
+        };
+
+        // dmg style, lowest x wins
+        assert_eq!(draw(0x01), (false, true));
+        // cgb style, first in OAM wins
+        assert_eq!(draw(0x00), (true, false));
+    }
+
+    #[test]
+    fn render_bg_indices() {
+        let (mut ppu, mut memory) = init();
+
+        // tile 1 row 0 is color 0, 1, 2, 3, then color 0 for the rest
+        memory.splice(0x8010, &[0b0101_0000, 0b0011_0000]);
+        // second tile of the second row of the map
+        memory.set(0x9800 + 32 + 1, 1);
+
+        // the palette doesn't change the indexes
+        ppu.set_palette(0);
+
+        let indices = ppu.render_bg_indices(&memory);
+        let start = 8 + 8 * BG_MAP_PIXELS;
+
+        assert_eq!(indices.len(), BG_MAP_PIXELS * BG_MAP_PIXELS);
+        assert_eq!(&indices[start..start + 8], &[0, 1, 2, 3, 0, 0, 0, 0]);
+        assert_eq!(indices.iter().filter(|&&index| index != 0).count(), 3);
+
+        // and every pixel comes out the same color through BGP
+        let rgb = ppu.render_bg(&memory);
+        assert_eq!(rgb.len(), indices.len() * 3);
+        assert!(rgb.chunks(3).all(|pixel| pixel == &rgb[0..3]));
+    }
+
+    #[test]
+    fn rendering_off() {
+        let (mut ppu, mut memory) = init();
+        let (mut drawn, _) = init();
+
+        // every background pixel is color 3
+        memory.splice(0x8000, &[0xFF; 16]);
+        ppu.set_rendering(false);
+        assert!(!ppu.is_rendering());
+
+        for _ in 0..TICKS_PER_FRAME + TICKS_PER_LINE / 3 {
+            ppu.tick(&memory);
+            drawn.tick(&memory);
+
+            assert_eq!((ppu.mode(), ppu.ly(), ppu.dot()), (drawn.mode(), drawn.ly(), drawn.dot()));
+            assert_eq!(ppu.status, drawn.status);
+        }
+
+        assert!(ppu.fb.iter().all(|&byte| byte == 0));
+        assert!(drawn.fb.iter().any(|&byte| byte != 0));
+    }
+
+    #[test]
+    fn render_into() {
+        let (mut ppu, mut memory) = init();
+
+        // tile 1 row 0 is color 0, 1, 2, 3, then color 0 for the rest
+        memory.splice(0x8010, &[0b0101_0000, 0b0011_0000]);
+        memory.set(0x9800, 1);
+        ppu.set_palette(0b1110_0100);
+
+        let mut out = vec![0x123456; SCREEN_WIDTH * SCREEN_HEIGHT];
+        assert!(matches!(ppu.render_into(&memory, &mut out[1..]), Err(Error::BufferLength(len)) if len == out.len() - 1));
+        assert_eq!(out[1], 0x123456);
+
+        ppu.render_into(&memory, &mut out).unwrap();
+        assert_eq!(&out[0..5], &[0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000, 0xFFFFFF]);
+        assert_eq!(out[SCREEN_WIDTH], 0xFFFFFF);
+
+        // nothing moved, and fb wasn't drawn to
+        assert_eq!((ppu.coords.x, ppu.coords.y), (0, 0));
+        assert!(ppu.fb.iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn palette_snapshot() {
+        let (mut ppu, mut memory) = init();
+
+        ppu.set_palette(0b1110_0100);
+        ppu.set_obj_palette(1, 0b0001_1011);
+
+        let bg = ppu.bg_palettes(&memory);
+        assert_eq!(bg[0], [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000]);
+        assert_eq!(bg[1], [0; 4]);
+        assert_eq!(ppu.obj_palettes(&memory)[1], [0x000000, 0x555555, 0xAAAAAA, 0xFFFFFF]);
+
+        // background palette 2 color 1 is pure red
+        memory.set(BCPS, 0x80 | (2 * 8 + 2));
+        memory.set(BCPD, 0x1F);
+        memory.set(BCPD, 0x00);
+        ppu.cgb = true;
+
+        let bg = ppu.bg_palettes(&memory);
+        assert_eq!(bg[2][1], 0xFF0000);
+        // palette RAM starts out white
+        assert_eq!(bg[7], [0xFFFFFF; 4]);
+    }
+
+    #[test]
+    fn debug_show_palette() {
+        let (mut ppu, mut memory) = init();
+
+        // tile 0 row 0 is color 0, 1, 2, 3, then color 0 for the rest
+        memory.splice(0x8000, &[0b0101_0000, 0b0011_0000]);
+
+        // palette 2 gets pure red, green, blue and white
+        memory.set(BCPS, 0x80 | 2 * 8);
+        for color in [0x001F_u16, 0x03E0, 0x7C00, 0x7FFF] {
+            memory.set(BCPD, color as u8);
+            memory.set(BCPD, (color >> 8) as u8);
+        }
+
+        let mut fb = vec![0; 8 * 8 * 3];
+        ppu.debug_show(&memory, [1, 1], Some(2), &mut fb);
+
+        assert_eq!(
+            &fb[0..12],
+            &[0xFF, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+    }
+
+    #[test]
+    fn power_on_registers() {
+        let ppu = Ppu::new();
+        let memory = Mmu::new(MbcSelector::NoMbc);
+
+        // the ppu's own copies have to agree with what the cpu reads back
+        assert_eq!(memory.load(LCDC), Some(0x91));
+        assert!(ppu.lcdc.lcd_enable && ppu.lcdc.bg_enable);
+        assert!(matches!(ppu.lcdc.bg_addressing, super::AddressType::Unsigned));
+
+        assert_eq!(memory.load(BGP), Some(0xFC));
+        assert_eq!(ppu.palette.value, 0xFC);
+
+        // STAT is made up by the ppu when it's read, in vblank with LY matching LYC
+        assert_eq!(ppu.read_stat(memory.load(LYC).unwrap()), 0x85);
+        assert_eq!(ppu.ly(), 0);
+    }
+
+    /// Runs a frame with the background scrolled a tile over partway through line 10, giving the framebuffer
+    fn scrolled_frame(mode: RenderMode) -> Vec<u8> {
+        let (mut ppu, mut memory) = init();
+        ppu.set_render_mode(mode);
+        assert_eq!(ppu.render_mode(), mode);
+
+        // tile 1 is solid color 3, and the map alternates between it and tile 0
+        memory.splice(0x8010, &[0xFF; 16]);
+        for offset in 0..0x400 {
+            memory.set(0x9800 + offset, (offset & 1) as u8);
+        }
+
+        for _ in 0..TICKS_PER_LINE * 10 + TICKS_PER_LINE / 3 {
+            ppu.tick(&memory);
+        }
+
+        memory.set(SCX, 8);
+
+        for _ in TICKS_PER_LINE * 10 + TICKS_PER_LINE / 3..TICKS_PER_FRAME {
+            ppu.tick(&memory);
+        }
+
+        ppu.fb
+    }
+
+    #[test]
+    fn render_modes() {
+        let fifo = scrolled_frame(RenderMode::PixelFifo);
+        let scanline = scrolled_frame(RenderMode::PerScanline);
+        let frame = scrolled_frame(RenderMode::PerFrame);
+        let line = |fb: &[u8], y: usize| fb[y * WIDTH as usize * 3..(y + 1) * WIDTH as usize * 3].to_vec();
+
+        // the lines before the change come out the same either way, and so do the ones after
+        for y in (0..10).chain(11..HEIGHT as usize) {
+            assert_eq!(line(&scanline, y), line(&fifo, y), "line {y}");
+        }
+
+        // only the fifo catches the change partway through line 10
+        assert_ne!(line(&scanline, 10), line(&fifo, 10));
+        assert_eq!(line(&scanline, 10), line(&scanline, 11));
+
+        // drawing everything at vblank uses the new scroll for every line
+        assert_eq!(line(&frame, 0), line(&fifo, HEIGHT as usize - 1));
+        assert_ne!(line(&frame, 0), line(&fifo, 0));
+    }
+
+    #[test]
+    fn scanline_log() {
+        let (mut ppu, mut memory) = init();
+        assert_eq!(ppu.scanline_state(0), None);
+
+        ppu.set_scanline_log(true);
+
+        // the scroll changes in line 9's hblank
+        for _ in 0..TICKS_PER_LINE * 10 - 10 {
+            ppu.tick(&memory);
+        }
+
+        memory.set(SCX, 8);
+
+        for _ in 0..TICKS_PER_LINE * (154 - 9) {
+            ppu.tick(&memory);
+        }
+
+        let state = ppu.scanline_state(9).unwrap();
+        assert_eq!(state.scx, 0);
+        assert_eq!(state.lcdc, 0b1001_0011);
+        assert_eq!(state.bgp, 0b0001_1011);
+
+        assert_eq!(ppu.scanline_state(10).unwrap().scx, 8);
+        assert_eq!(ppu.scanline_state(HEIGHT), None);
+
+        ppu.set_scanline_log(false);
+        assert_eq!(ppu.scanline_state(10), None);
+    }
+
+    #[test]
+    fn sprite_limit() {
+        let drawn = |limit| {
+            let (mut ppu, mut memory) = init();
+            ppu.set_sprite_limit(limit);
+
+            // tile 1 is solid color 3, and 12 objects side by side on line 0 use it
+            memory.splice(0x8010, &[0xFF; 16]);
+            for index in 0..12 {
+                memory.splice(0xFE00 + index * 4, &[16, 8 + index as u8 * 8, 1, 0]);
+            }
+
+            for _ in 0..TICKS_PER_LINE {
+                ppu.tick(&memory);
+            }
+
+            let background = pixel(&ppu, 100, 0).to_vec();
+            (0..12).filter(|&index| pixel(&ppu, index * 8, 0) != background).count()
+        };
+
+        assert_eq!(drawn(Some(SPRITE_LIMIT)), 10);
+        assert_eq!(drawn(Some(0)), 0);
+        assert_eq!(drawn(None), 12);
+    }
+
+    #[test]
+    fn mid_frame_tile_data() {
+        for mode in [RenderMode::PixelFifo, RenderMode::PerScanline] {
+            let (mut ppu, mut memory) = init();
+            ppu.set_render_mode(mode);
+
+            // the map is all tile 0, which is solid color 3 at 0x8000 and color 0 at 0x9000
+            memory.splice(0x8000, &[0xFF; 16]);
+
+            // switch to signed addressing in line 71's hblank
+            for _ in 0..TICKS_PER_LINE * 72 - 10 {
+                ppu.tick(&memory);
+            }
+
+            ppu.set_lcdc(0b1000_0011);
+
+            for _ in TICKS_PER_LINE * 72 - 10..TICKS_PER_FRAME {
+                ppu.tick(&memory);
+            }
+
+            let top = pixel(&ppu, 0, 0).to_vec();
+            let bottom = pixel(&ppu, 0, HEIGHT as usize - 1).to_vec();
+            assert_ne!(top, bottom, "{mode:?}");
+
+            for y in 0..HEIGHT as usize {
+                let expected = if y < 72 { &top } else { &bottom };
+                assert_eq!(pixel(&ppu, 80, y), expected, "{mode:?} line {y}");
+            }
+        }
+    }
+}
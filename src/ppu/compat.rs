@@ -0,0 +1,284 @@
+//! Colorization for DMG games, like the CGB boot ROM does it
+
+use crate::header::Header;
+
+/// The colors used for the 4 DMG shades, for the background and both object palettes
+///
+/// Colors are `0xRRGGBB`, from lightest shade to darkest
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompatPalette {
+    pub bg: [u32; 4],
+    pub obj0: [u32; 4],
+    pub obj1: [u32; 4],
+}
+
+impl CompatPalette {
+    /// What the CGB boot ROM uses for games it doesn't recognize
+    pub const DEFAULT: Self = Self {
+        bg: [0xFFFFFF, 0x7BFF31, 0x0063C5, 0x000000],
+        obj0: [0xFFFFFF, 0xFF8484, 0x943A3A, 0x000000],
+        obj1: [0xFFFFFF, 0xFF8484, 0x943A3A, 0x000000],
+    };
+
+    /// Plain shades of grey, the same for everything
+    pub const GRAYSCALE: Self = Self::uniform([0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000]);
+
+    /// The CGB's own greyscale palette (Left + B during boot)
+    pub const CGB_GRAYSCALE: Self = Self::uniform([0xFFFFFF, 0xA5A5A5, 0x525252, 0x000000]);
+
+    /// Up during boot
+    pub const BROWN: Self = Self::uniform([0xFFFFFF, 0xFFAD63, 0x843100, 0x000000]);
+
+    /// Right + B during boot
+    pub const INVERTED: Self = Self::uniform([0x000000, 0x008484, 0xFFDE00, 0xFFFFFF]);
+
+    /// Uses the same colors for the background and both object palettes
+    pub const fn uniform(colors: [u32; 4]) -> Self {
+        Self {
+            bg: colors,
+            obj0: colors,
+            obj1: colors,
+        }
+    }
+
+    /// Picks the palette a CGB would assign to the game with this header
+    ///
+    /// ### Return Variants
+    /// - `None` if the game supports CGB, since it sets its own colors
+    /// - `Some(palette)` with the palette for the game's title checksum, or `DEFAULT` if there isn't one
+    pub fn from_header(header: &Header) -> Option<Self> {
+        if header.is_cgb() {
+            return None;
+        }
+
+        // only nintendo games get their own palette
+        if !header.is_nintendo() {
+            return Some(Self::DEFAULT);
+        }
+
+        let checksum = header.title_checksum();
+
+        let Some(mut index) = TITLE_CHECKSUMS.iter().position(|&entry| entry == checksum) else {
+            return Some(Self::DEFAULT);
+        };
+
+        // some checksums are shared between games, the 4th letter of the title tells them apart.
+        // each shared checksum has its letters a row of `SHARED_CHECKSUMS` apart
+        if index >= UNIQUE_CHECKSUMS {
+            let letter = (index..PALETTE_PER_CHECKSUM.len())
+                .step_by(SHARED_CHECKSUMS)
+                .find(|&i| FOURTH_LETTERS[i - UNIQUE_CHECKSUMS] == header.title[3]);
+
+            match letter {
+                Some(i) => index = i,
+                None => return Some(Self::DEFAULT),
+            }
+        }
+
+        Some(Self::combination(PALETTE_PER_CHECKSUM[index]))
+    }
+
+    /// Builds palette combination `index` from the boot ROM's table
+    const fn combination(index: u8) -> Self {
+        let [obj0, obj1, bg] = COMBINATIONS[index as usize];
+
+        Self {
+            bg: colors(bg),
+            obj0: colors(obj0),
+            obj1: colors(obj1),
+        }
+    }
+}
+
+/// Takes the 4 colors starting at `offset` in `PALETTE_COLORS`, converted to `0xRRGGBB`
+const fn colors(offset: u8) -> [u32; 4] {
+    let mut out = [0; 4];
+    let mut i = 0;
+
+    while i < 4 {
+        let color = PALETTE_COLORS[offset as usize + i];
+        out[i] = expand(color) << 16 | expand(color >> 5) << 8 | expand(color >> 10);
+        i += 1;
+    }
+
+    out
+}
+
+/// Scales a 5 bit channel up to 8 bits, rounded to the nearest so it matches the palettes above
+const fn expand(channel: u16) -> u32 {
+    let c = (channel & 0x1F) as u32;
+    (c * 0xFF + 15) / 0x1F
+}
+
+// the rest is copied from the CGB boot ROM
+
+/// Checksums that only one game has, which come first in `TITLE_CHECKSUMS`
+const UNIQUE_CHECKSUMS: usize = 65;
+/// Checksums shared between games, which come after the unique ones
+const SHARED_CHECKSUMS: usize = 14;
+
+/// Title checksums of the licensed games that get their own palette
+const TITLE_CHECKSUMS: [u8; UNIQUE_CHECKSUMS + SHARED_CHECKSUMS] = [
+    0x00, 0x88, 0x16, 0x36, 0xD1, 0xDB, 0xF2, 0x3C, 0x8C, 0x92, 0x3D, 0x5C, 0x58, 0xC9, 0x3E, 0x70,
+    0x1D, 0x59, 0x69, 0x19, 0x35, 0xA8, 0x14, 0xAA, 0x75, 0x95, 0x99, 0x34, 0x6F, 0x15, 0xFF, 0x97,
+    0x4B, 0x90, 0x17, 0x10, 0x39, 0xF7, 0xF6, 0xA2, 0x49, 0x4E, 0x43, 0x68, 0xE0, 0x8B, 0xF0, 0xCE,
+    0x0C, 0x29, 0xE8, 0xB7, 0x86, 0x9A, 0x52, 0x01, 0x9D, 0x71, 0x9C, 0xBD, 0x5D, 0x6D, 0x67, 0x3F,
+    0x6B,
+    // shared
+    0xB3, 0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4,
+];
+
+/// 4th letter of the title for each game with a shared checksum, in rows of `SHARED_CHECKSUMS`
+const FOURTH_LETTERS: &[u8; 29] = b"BEFAARBEKEK R-URAR INAILICE R";
+
+/// Palette combination for each checksum, then for each of `FOURTH_LETTERS`
+const PALETTE_PER_CHECKSUM: [u8; UNIQUE_CHECKSUMS + 29] = [
+    0, 4, 5, 35, 34, 3, 31, 15, 10, 5, 19, 36, 7, 37, 30, 44,
+    21, 32, 31, 20, 5, 33, 13, 14, 5, 29, 5, 18, 9, 3, 2, 26,
+    25, 25, 41, 42, 26, 45, 42, 45, 36, 38, 26, 42, 30, 41, 34, 34,
+    5, 42, 6, 5, 33, 25, 42, 42, 40, 2, 16, 25, 42, 42, 5, 0,
+    39,
+    // shared
+    36, 22, 25, 6, 32, 12, 36, 11, 39, 18, 39, 24, 31, 50,
+    17, 46, 6, 27, 0, 47, 41, 41, 0, 0, 19, 34, 23, 18,
+    29,
+];
+
+/// Offsets into `PALETTE_COLORS` for OBJ0, OBJ1 and BG
+///
+/// Most start at a palette, but a few start a color early and take the last color of the palette before
+const COMBINATIONS: [[u8; 3]; 51] = [
+    [4 * 4, 4 * 4, 29 * 4],
+    [18 * 4, 18 * 4, 18 * 4],
+    [20 * 4, 20 * 4, 20 * 4],
+    [24 * 4, 24 * 4, 24 * 4],
+    [9 * 4, 9 * 4, 9 * 4],
+    [0, 0, 0],
+    [27 * 4, 27 * 4, 27 * 4],
+    [5 * 4, 5 * 4, 5 * 4],
+    [12 * 4, 12 * 4, 12 * 4],
+    [26 * 4, 26 * 4, 26 * 4],
+    [16 * 4, 8 * 4, 8 * 4],
+    [4 * 4, 28 * 4, 28 * 4],
+    [4 * 4, 2 * 4, 2 * 4],
+    [3 * 4, 4 * 4, 4 * 4],
+    [4 * 4, 29 * 4, 29 * 4],
+    [28 * 4, 4 * 4, 28 * 4],
+    [2 * 4, 17 * 4, 2 * 4],
+    [16 * 4, 16 * 4, 8 * 4],
+    [4 * 4, 4 * 4, 7 * 4],
+    [4 * 4, 4 * 4, 18 * 4],
+    [4 * 4, 4 * 4, 20 * 4],
+    [19 * 4, 19 * 4, 9 * 4],
+    [4 * 4 - 1, 4 * 4 - 1, 11 * 4],
+    [17 * 4, 17 * 4, 2 * 4],
+    [4 * 4, 4 * 4, 2 * 4],
+    [4 * 4, 4 * 4, 3 * 4],
+    [28 * 4, 28 * 4, 0],
+    [3 * 4, 3 * 4, 0],
+    [0, 0, 4],
+    [18 * 4, 22 * 4, 18 * 4],
+    [20 * 4, 22 * 4, 20 * 4],
+    [24 * 4, 22 * 4, 24 * 4],
+    [16 * 4, 22 * 4, 8 * 4],
+    [17 * 4, 4 * 4, 13 * 4],
+    [28 * 4 - 1, 0, 14 * 4],
+    [28 * 4 - 1, 4 * 4, 15 * 4],
+    [19 * 4, 23 * 4 - 1, 9 * 4],
+    [16 * 4, 28 * 4, 10 * 4],
+    [4 * 4, 23 * 4, 28 * 4],
+    [17 * 4, 22 * 4, 2 * 4],
+    [4 * 4, 0, 2 * 4],
+    [4 * 4, 28 * 4, 3 * 4],
+    [28 * 4, 3 * 4, 0],
+    [3 * 4, 28 * 4, 4 * 4],
+    [21 * 4, 28 * 4, 4 * 4],
+    [3 * 4, 28 * 4, 0],
+    [25 * 4, 3 * 4, 28 * 4],
+    [0, 28 * 4, 8 * 4],
+    [4 * 4, 3 * 4, 28 * 4],
+    [28 * 4, 3 * 4, 6 * 4],
+    [4 * 4, 28 * 4, 29 * 4],
+];
+
+/// Every palette the combinations are made from, 4 CGB style 15 bit colors each
+const PALETTE_COLORS: [u16; 30 * 4] = [
+    0x7FFF, 0x32BF, 0x00D0, 0x0000,
+    0x639F, 0x4279, 0x15B0, 0x04CB,
+    0x7FFF, 0x6E31, 0x454A, 0x0000,
+    0x7FFF, 0x1BEF, 0x0200, 0x0000,
+    0x7FFF, 0x421F, 0x1CF2, 0x0000,
+    0x7FFF, 0x5294, 0x294A, 0x0000,
+    0x7FFF, 0x03FF, 0x012F, 0x0000,
+    0x7FFF, 0x03EF, 0x01D6, 0x0000,
+    0x7FFF, 0x42B5, 0x3DC8, 0x0000,
+    0x7E74, 0x03FF, 0x0180, 0x0000,
+    0x67FF, 0x77AC, 0x1A13, 0x2D6B,
+    0x7ED6, 0x4BFF, 0x2175, 0x0000,
+    0x53FF, 0x4A5F, 0x7E52, 0x0000,
+    0x4FFF, 0x7ED2, 0x3A4C, 0x1CE0,
+    0x03ED, 0x7FFF, 0x255F, 0x0000,
+    0x036A, 0x021F, 0x03FF, 0x7FFF,
+    0x7FFF, 0x01DF, 0x0112, 0x0000,
+    0x231F, 0x035F, 0x00F2, 0x0009,
+    0x7FFF, 0x03EA, 0x011F, 0x0000,
+    0x299F, 0x001A, 0x000C, 0x0000,
+    0x7FFF, 0x027F, 0x001F, 0x0000,
+    0x7FFF, 0x03E0, 0x0206, 0x0120,
+    0x7FFF, 0x7EEB, 0x001F, 0x7C00,
+    0x7FFF, 0x3FFF, 0x7E00, 0x001F,
+    0x7FFF, 0x03FF, 0x001F, 0x0000,
+    0x03FF, 0x001F, 0x000C, 0x0000,
+    0x7FFF, 0x033F, 0x0193, 0x0000,
+    0x0000, 0x4200, 0x037F, 0x7FFF,
+    0x7FFF, 0x7E8C, 0x7C00, 0x0000,
+    0x7FFF, 0x1BEF, 0x6180, 0x0000,
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::header::Header;
+
+    use super::CompatPalette;
+
+    fn header(title: &[u8], old_licensee: u8, cgb_flag: u8) -> Header {
+        let mut rom = vec![0; 0x150];
+        rom[0x134..0x134 + title.len()].copy_from_slice(title);
+        rom[0x143] = cgb_flag;
+        rom[0x14B] = old_licensee;
+
+        Header::from_rom(&rom)
+    }
+
+    #[test]
+    fn from_header() {
+        // third party games always get the default palette
+        assert_eq!(CompatPalette::from_header(&header(b"TETRIS", 0x00, 0)), Some(CompatPalette::DEFAULT));
+        // cgb games pick their own colors
+        assert_eq!(CompatPalette::from_header(&header(b"TETRIS", 0x01, 0x80)), None);
+        // nintendo games nobody's heard of get the default too
+        assert_eq!(CompatPalette::from_header(&header(b"UNKNOWN", 0x01, 0)), Some(CompatPalette::DEFAULT));
+    }
+
+    #[test]
+    fn title_checksums() {
+        let palette = |title: &[u8]| CompatPalette::from_header(&header(title, 0x01, 0)).unwrap();
+
+        // checksum 0xDB
+        assert_eq!(palette(b"TETRIS"), CompatPalette::uniform([0xFFFFFF, 0xFFFF00, 0xFF0000, 0x000000]));
+
+        // KID ICARUS and SOCCER are both 0xBF, and only differ in the 4th letter
+        let kid_icarus = palette(b"KID ICARUS");
+        assert_eq!(kid_icarus.bg, [0xFFFFFF, 0x8C8CDE, 0x52528C, 0x000000]);
+        assert_eq!(kid_icarus.obj0, CompatPalette::DEFAULT.obj0);
+        assert_ne!(palette(b"SOCCER"), kid_icarus);
+        // a checksum that's shared, but not with a letter in the table
+        assert_eq!(palette(b"KIDAICARU2"), CompatPalette::DEFAULT);
+
+        // the palettes picked by buttons during boot are in the same table
+        assert_eq!(CompatPalette::combination(0), CompatPalette::DEFAULT);
+        assert_eq!(CompatPalette::combination(5), CompatPalette::BROWN);
+        assert_eq!(CompatPalette::combination(6), CompatPalette::INVERTED);
+        assert_eq!(CompatPalette::combination(7), CompatPalette::CGB_GRAYSCALE);
+    }
+}
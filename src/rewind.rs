@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+
+use crate::cpu::Cpu;
+
+/// A saved copy of the whole system
+///
+/// Cartridge ROM is shared with the running system instead of copied, see `RomBanks`
+#[derive(Clone)]
+pub(crate) struct Snapshot {
+    pub(crate) cpu: Cpu,
+    pub(crate) frame_end: usize,
+}
+
+/// Ring buffer of recent snapshots, used for rewinding
+///
+/// A snapshot is taken every `interval` frames, and the oldest one is dropped once there are `depth` of them,
+/// so the buffer covers the last `depth * interval` frames.
+/// Each snapshot copies everything that can change, like RAM, VRAM, the registers and the framebuffer, but not the cartridge ROM,
+/// which is shared between them
+pub struct Rewind {
+    states: VecDeque<Snapshot>,
+    depth: usize,
+    interval: usize,
+    /// Frames since the last snapshot
+    frames: usize,
+}
+
+impl Rewind {
+    /// Creates an empty rewind buffer
+    ///
+    /// An `interval` of 0 is treated as 1, taking a snapshot every frame
+    pub fn new(depth: usize, interval: usize) -> Self {
+        Self {
+            states: VecDeque::with_capacity(depth),
+            depth,
+            interval: interval.max(1),
+            frames: 0,
+        }
+    }
+
+    /// Number of snapshots that can be rewound to
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// Counts a finished frame, taking a snapshot if it's been `interval` frames since the last one
+    pub(crate) fn record(&mut self, snapshot: impl FnOnce() -> Snapshot) {
+        self.frames += 1;
+
+        if self.frames < self.interval || self.depth == 0 {
+            return;
+        }
+
+        self.frames = 0;
+
+        if self.states.len() == self.depth {
+            self.states.pop_front();
+        }
+
+        self.states.push_back(snapshot());
+    }
+
+    /// Throws away every snapshot, keeping the depth and interval
+    pub(crate) fn clear(&mut self) {
+        self.states.clear();
+        self.frames = 0;
+    }
+
+    /// Takes out the most recent snapshot
+    pub(crate) fn pop(&mut self) -> Option<Snapshot> {
+        self.frames = 0;
+        self.states.pop_back()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Gbc, MbcSelector, TICKS_PER_FRAME};
+
+    fn init() -> Gbc {
+        let mut rom = vec![0; 0x8000];
+        // jr -2, spins forever
+        rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+
+        let mut gbc = Gbc::new(MbcSelector::NoMbc, false, true);
+        gbc.load_rom(&rom);
+
+        gbc
+    }
+
+    #[test]
+    fn rewind() {
+        let mut gbc = init();
+        gbc.enable_rewind(2, 1);
+
+        for _ in 0..3 {
+            gbc.next_frame().unwrap();
+        }
+
+        // the snapshot from the first frame was pushed out
+        assert_eq!(gbc.rewind_buffer().map(|r| r.len()), Some(2));
+
+        assert!(gbc.rewind());
+        assert!(gbc.rewind());
+        assert!(!gbc.rewind());
+
+        let ticks = gbc.cpu.ticks();
+        assert!((TICKS_PER_FRAME * 2..TICKS_PER_FRAME * 3).contains(&ticks));
+    }
+
+    #[test]
+    fn rewind_interval() {
+        let mut gbc = init();
+        gbc.enable_rewind(10, 4);
+
+        for _ in 0..9 {
+            gbc.next_frame().unwrap();
+        }
+
+        // snapshots after frames 4 and 8
+        assert_eq!(gbc.rewind_buffer().map(|r| r.len()), Some(2));
+    }
+}
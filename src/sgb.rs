@@ -0,0 +1,500 @@
+use std::collections::VecDeque;
+
+use crate::{
+    bits::word_bit_set,
+    ppu::{Color, SCREEN_HEIGHT, SCREEN_WIDTH},
+};
+
+/// Bytes in a single SGB packet
+pub const PACKET_LEN: usize = 16;
+
+/// Width of the full SGB picture, with the border around the screen
+pub const SGB_WIDTH: usize = 256;
+
+/// Height of the full SGB picture, with the border around the screen
+pub const SGB_HEIGHT: usize = 224;
+
+/// Bytes sent through VRAM by each of the `_TRN` commands
+pub const TRANSFER_LEN: usize = 0x1000;
+
+/// Where the top left of the Game Boy screen goes in the full SGB picture
+const SCREEN_X: usize = 48;
+const SCREEN_Y: usize = 40;
+
+/// Bytes in one 4bpp SNES tile
+const BORDER_TILE_SIZE: usize = 32;
+
+/// Bytes of tilemap sent by `PctTrn`, 32x32 entries even though only 28 rows are shown
+const BORDER_MAP_LEN: usize = 0x800;
+
+/// Bytes of palettes sent by `PctTrn` after the tilemap, which are palettes 4 to 7 with 16 colors each
+const BORDER_PALETTES_LEN: usize = 0x80;
+
+/// Frontend function that gets each SGB command, see `Gbc::set_sgb_callback`
+pub type SgbCallback = Box<dyn FnMut(&SgbPacket) + Send>;
+
+/// Super Game Boy commands, from the top 5 bits of the first byte of a packet
+///
+/// Only the ones that matter for palettes and borders get their own variant, everything else is `Other`.
+/// The `_TRN` commands send their actual data through VRAM on the next frame. Only `ChrTrn` and `PctTrn` get theirs read,
+/// for `SgbBorder`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SgbCommand {
+    /// Sets SGB palettes 0 and 1
+    Pal01,
+    /// Sets SGB palettes 2 and 3
+    Pal23,
+    /// Sets SGB palettes 0 and 3
+    Pal03,
+    /// Sets SGB palettes 1 and 2
+    Pal12,
+    /// Applies palettes to rectangles of the screen
+    AttrBlk,
+    /// Applies palettes to whole lines of the screen
+    AttrLin,
+    /// Splits the screen in two along a line, with a palette for each side
+    AttrDiv,
+    /// Applies palettes to individual tiles of the screen
+    AttrChr,
+    /// Picks 4 palettes out of the ones sent by `PalTrn`
+    PalSet,
+    /// Sends 512 palettes through VRAM
+    PalTrn,
+    /// Asks for multiplayer, which is also how games check that they're on an SGB
+    MltReq,
+    /// Sends border tiles through VRAM
+    ChrTrn,
+    /// Sends the border tilemap and palettes through VRAM
+    PctTrn,
+    /// Sends attribute files through VRAM
+    AttrTrn,
+    /// Applies one of the attribute files sent by `AttrTrn`
+    AttrSet,
+    /// Freezes or blanks the screen while the game sets things up
+    MaskEn,
+    /// Any other command, with its code
+    Other(u8),
+}
+
+impl SgbCommand {
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            0x00 => Self::Pal01,
+            0x01 => Self::Pal23,
+            0x02 => Self::Pal03,
+            0x03 => Self::Pal12,
+            0x04 => Self::AttrBlk,
+            0x05 => Self::AttrLin,
+            0x06 => Self::AttrDiv,
+            0x07 => Self::AttrChr,
+            0x0A => Self::PalSet,
+            0x0B => Self::PalTrn,
+            0x11 => Self::MltReq,
+            0x13 => Self::ChrTrn,
+            0x14 => Self::PctTrn,
+            0x15 => Self::AttrTrn,
+            0x16 => Self::AttrSet,
+            0x17 => Self::MaskEn,
+            code => Self::Other(code),
+        }
+    }
+}
+
+/// A full SGB command, made of one or more packets
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SgbPacket {
+    pub command: SgbCommand,
+    /// Every byte of every packet, including the header byte at the start
+    pub data: Vec<u8>,
+}
+
+impl SgbPacket {
+    /// Gets the colors sent by one of the `PALxx` commands, as CGB style 15 bit colors
+    ///
+    /// The first color is color 0 of every palette, then it's colors 1 to 3 of the first palette and colors 1 to 3 of the second
+    ///
+    /// ### Return Variants
+    /// - `Some(colors)` if this is `Pal01`, `Pal23`, `Pal03` or `Pal12`
+    /// - `None` for any other command
+    pub fn palette_colors(&self) -> Option<[u16; 7]> {
+        match self.command {
+            SgbCommand::Pal01 | SgbCommand::Pal23 | SgbCommand::Pal03 | SgbCommand::Pal12 => {}
+            _ => return None,
+        }
+
+        let mut colors = [0; 7];
+
+        for (i, color) in colors.iter_mut().enumerate() {
+            let low = self.data[1 + i * 2] as u16;
+            let high = self.data[2 + i * 2] as u16;
+
+            *color = (high << 8 | low) & 0x7FFF;
+        }
+
+        Some(colors)
+    }
+}
+
+/// Picks SGB packets out of writes to `JOYP`
+///
+/// Each packet starts with a reset pulse, where P14 and P15 are both low. After that, every pulse of P14 alone is a 0 bit and
+/// every pulse of P15 alone is a 1, with both going high again in between. Bytes are sent low bit first, and the 16 bytes of a
+/// packet are followed by a single 0 bit.
+/// The low 3 bits of the first byte are how many packets the command takes, and the rest of the packets don't have a header
+#[derive(Clone, Debug)]
+pub struct SgbReceiver {
+    /// P14 and P15 from the last write
+    select: u8,
+    /// Bits received of the current packet, or `None` if no packet is being sent
+    bit: Option<usize>,
+    packet: [u8; PACKET_LEN],
+    /// Packets received so far for the current command
+    data: Vec<u8>,
+    /// Finished commands that haven't been taken yet
+    received: VecDeque<SgbPacket>,
+    /// The last border transfer command, which still needs its data read from VRAM
+    transfer: Option<SgbPacket>,
+    /// Color 0 from the last `PALxx` command, which shows through the border's transparent pixels
+    backdrop: u16,
+}
+
+impl SgbReceiver {
+    pub fn new() -> Self {
+        Self {
+            select: 0x30,
+            bit: None,
+            packet: [0; PACKET_LEN],
+            data: Vec::new(),
+            received: VecDeque::new(),
+            transfer: None,
+            backdrop: 0x7FFF,
+        }
+    }
+
+    /// Handles a write to `JOYP`
+    pub fn write(&mut self, value: u8) {
+        let select = value & 0x30;
+        let previous = self.select;
+        self.select = select;
+
+        match select {
+            // reset pulse, which starts a packet and throws away any half sent one
+            0x00 => {
+                self.bit = Some(0);
+                self.packet = [0; PACKET_LEN];
+            }
+            0x30 => {}
+            // a bit only counts the first time a line goes low after both were high
+            _ if previous == 0x30 => self.receive_bit(select == 0x10),
+            _ => {}
+        }
+    }
+
+    /// Takes the oldest command that hasn't been taken yet
+    pub fn take(&mut self) -> Option<SgbPacket> {
+        self.received.pop_front()
+    }
+
+    /// Takes the last `ChrTrn` or `PctTrn` command, if there's been one since the last time
+    ///
+    /// These are kept apart from `take`, so the border still gets its data when a frontend takes every command
+    pub fn take_transfer(&mut self) -> Option<SgbPacket> {
+        self.transfer.take()
+    }
+
+    /// Color 0 from the last `PALxx` command, as a CGB style 15 bit color
+    ///
+    /// It starts out white, until a game sends its own palettes
+    pub fn backdrop(&self) -> u16 {
+        self.backdrop
+    }
+
+    fn receive_bit(&mut self, one: bool) {
+        let Some(bit) = self.bit else {
+            // regular joypad polling
+            return;
+        };
+
+        if bit < PACKET_LEN * 8 {
+            self.packet[bit / 8] |= (one as u8) << (bit % 8);
+            self.bit = Some(bit + 1);
+
+            return;
+        }
+
+        self.bit = None;
+
+        // the stop bit has to be a 0, otherwise it wasn't a packet
+        if !one {
+            self.finish_packet();
+        }
+    }
+
+    fn finish_packet(&mut self) {
+        self.data.extend_from_slice(&self.packet);
+
+        let Some(&header) = self.data.first() else {
+            unreachable!("A packet was just added");
+        };
+
+        // a length of 0 still sends the one packet
+        let packets = ((header & 0x07) as usize).max(1);
+
+        if self.data.len() >= packets * PACKET_LEN {
+            let data = std::mem::take(&mut self.data);
+            let packet = SgbPacket {
+                command: SgbCommand::from_code(header >> 3),
+                data,
+            };
+
+            if let Some(colors) = packet.palette_colors() {
+                self.backdrop = colors[0];
+            }
+
+            if matches!(packet.command, SgbCommand::ChrTrn | SgbCommand::PctTrn) {
+                self.transfer = Some(packet.clone());
+            }
+
+            self.received.push_back(packet);
+        }
+    }
+}
+
+impl Default for SgbReceiver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The picture the SNES draws around the Game Boy screen, sent by `ChrTrn` and `PctTrn`
+///
+/// `ChrTrn` sends 128 of the 256 tiles at a time, and `PctTrn` sends the tilemap and the 4 palettes it uses.
+/// Color 0 of every border palette is transparent, showing the Game Boy screen in the middle and the backdrop everywhere else
+#[derive(Clone, Debug)]
+pub struct SgbBorder {
+    tiles: Box<[u8; BORDER_TILE_SIZE * 256]>,
+    /// The tilemap followed by the palettes, or `None` until `PctTrn` has been sent
+    map: Option<Box<[u8; BORDER_MAP_LEN + BORDER_PALETTES_LEN]>>,
+}
+
+impl SgbBorder {
+    pub fn new() -> Self {
+        Self {
+            tiles: Box::new([0; BORDER_TILE_SIZE * 256]),
+            map: None,
+        }
+    }
+
+    /// Checks if a tilemap has been sent, which is when there's a border to draw
+    pub fn is_set(&self) -> bool {
+        self.map.is_some()
+    }
+
+    /// Takes in the data sent through VRAM for `packet`
+    ///
+    /// Any command other than `ChrTrn` and `PctTrn` is ignored
+    pub fn transfer(&mut self, packet: &SgbPacket, data: &[u8; TRANSFER_LEN]) {
+        match packet.command {
+            SgbCommand::ChrTrn => {
+                // bit 0 picks between tiles 0x00-0x7F and 0x80-0xFF
+                let start = (packet.data[1] & 1) as usize * TRANSFER_LEN;
+                self.tiles[start..start + TRANSFER_LEN].copy_from_slice(data);
+            }
+            SgbCommand::PctTrn => {
+                let mut map = Box::new([0; BORDER_MAP_LEN + BORDER_PALETTES_LEN]);
+                map.copy_from_slice(&data[..BORDER_MAP_LEN + BORDER_PALETTES_LEN]);
+                self.map = Some(map);
+            }
+            _ => {}
+        }
+    }
+
+    /// Draws the border with `screen` in the middle, into `out`
+    ///
+    /// `screen` and `out` are `0RGB`, the same as frames from `Gbc::next_frame`. `backdrop` is a CGB style 15 bit color,
+    /// see `SgbReceiver::backdrop`. Without a border, it's just the screen on the backdrop
+    ///
+    /// ### Panic Conditions
+    /// - `screen` isn't `SCREEN_WIDTH * SCREEN_HEIGHT` long
+    /// - `out` isn't `SGB_WIDTH * SGB_HEIGHT` long
+    pub fn render(&self, screen: &[u32], backdrop: u16, out: &mut [u32]) {
+        assert_eq!(screen.len(), SCREEN_WIDTH * SCREEN_HEIGHT, "Screen is the wrong size");
+        assert_eq!(out.len(), SGB_WIDTH * SGB_HEIGHT, "Output is the wrong size");
+
+        out.fill(Color::from_rgb555(backdrop).to_0rgb());
+
+        for (row, line) in screen.chunks_exact(SCREEN_WIDTH).enumerate() {
+            let start = (SCREEN_Y + row) * SGB_WIDTH + SCREEN_X;
+            out[start..start + SCREEN_WIDTH].copy_from_slice(line);
+        }
+
+        let Some(ref map) = self.map else {
+            return;
+        };
+
+        for (y, line) in out.chunks_exact_mut(SGB_WIDTH).enumerate() {
+            for (x, pixel) in line.iter_mut().enumerate() {
+                let entry_addr = (y / 8 * 32 + x / 8) * 2;
+                let entry = u16::from_le_bytes([map[entry_addr], map[entry_addr + 1]]);
+
+                // bits 8 and 9 would be more tiles on the SNES, but the SGB only has 256
+                let tile = (entry & 0xFF) as usize;
+                let palette = ((entry >> 10) & 0x03) as usize;
+                let col = if word_bit_set(entry, 14) { 7 - x % 8 } else { x % 8 };
+                let row = if word_bit_set(entry, 15) { 7 - y % 8 } else { y % 8 };
+
+                let tile_addr = tile * BORDER_TILE_SIZE + row * 2;
+                let planes = [
+                    self.tiles[tile_addr],
+                    self.tiles[tile_addr + 1],
+                    self.tiles[tile_addr + 16],
+                    self.tiles[tile_addr + 17],
+                ];
+                let color = planes
+                    .iter()
+                    .enumerate()
+                    .fold(0, |color, (plane, bits)| color | ((bits >> (7 - col)) & 1) << plane) as usize;
+
+                if color == 0 {
+                    continue;
+                }
+
+                let color_addr = BORDER_MAP_LEN + (palette * 16 + color) * 2;
+                *pixel = Color::from_rgb555(u16::from_le_bytes([map[color_addr], map[color_addr + 1]])).to_0rgb();
+            }
+        }
+    }
+}
+
+impl Default for SgbBorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+    use super::{SgbBorder, SgbCommand, SgbPacket, SgbReceiver, PACKET_LEN, SGB_HEIGHT, SGB_WIDTH, TRANSFER_LEN};
+
+    /// Writes `packet` to `receiver` the same way a game would
+    fn send(receiver: &mut SgbReceiver, packet: &[u8; PACKET_LEN]) {
+        receiver.write(0x00);
+        receiver.write(0x30);
+
+        for byte in packet {
+            for bit in 0..8 {
+                receiver.write(if byte & (1 << bit) > 0 { 0x10 } else { 0x20 });
+                receiver.write(0x30);
+            }
+        }
+
+        // stop bit
+        receiver.write(0x20);
+        receiver.write(0x30);
+    }
+
+    #[test]
+    fn pal01() {
+        let mut receiver = SgbReceiver::new();
+        let mut packet = [0; PACKET_LEN];
+
+        // PAL01, 1 packet
+        packet[0] = 0x00 << 3 | 1;
+        // color 0 is pure red, palette 0 color 1 is pure blue
+        packet[1..5].copy_from_slice(&[0x1F, 0x00, 0x00, 0x7C]);
+
+        // regular joypad polling before the packet is ignored
+        receiver.write(0x20);
+        receiver.write(0x10);
+        receiver.write(0x30);
+
+        send(&mut receiver, &packet);
+
+        let received = receiver.take().unwrap();
+        assert_eq!(received.command, SgbCommand::Pal01);
+        assert_eq!(received.data, packet);
+
+        let colors = received.palette_colors().unwrap();
+        assert_eq!(&colors[0..3], &[0x001F, 0x7C00, 0x0000]);
+
+        assert!(receiver.take().is_none());
+    }
+
+    #[test]
+    fn multi_packet() {
+        let mut receiver = SgbReceiver::new();
+        let mut first = [0; PACKET_LEN];
+        let second = [0xAB; PACKET_LEN];
+
+        // ATTR_BLK, 2 packets
+        first[0] = 0x04 << 3 | 2;
+
+        send(&mut receiver, &first);
+        assert!(receiver.take().is_none());
+
+        send(&mut receiver, &second);
+
+        let received = receiver.take().unwrap();
+        assert_eq!(received.command, SgbCommand::AttrBlk);
+        assert_eq!(received.data.len(), PACKET_LEN * 2);
+        assert_eq!(&received.data[PACKET_LEN..], &second);
+        assert_eq!(received.palette_colors(), None);
+    }
+
+    #[test]
+    fn border() {
+        let mut receiver = SgbReceiver::new();
+        let mut packet = [0; PACKET_LEN];
+
+        // PCT_TRN, 1 packet
+        packet[0] = 0x14 << 3 | 1;
+        send(&mut receiver, &packet);
+
+        let pct_trn = receiver.take_transfer().unwrap();
+        assert_eq!(pct_trn.command, SgbCommand::PctTrn);
+        assert!(receiver.take_transfer().is_none());
+        // it's still there for the frontend too
+        assert_eq!(receiver.take(), Some(pct_trn.clone()));
+
+        // tile 0x81 is all color 1, tile 0x82 only has its leftmost column set
+        let mut tiles = [0; TRANSFER_LEN];
+        for row in 0..8 {
+            tiles[32 + row * 2] = 0xFF;
+            tiles[64 + row * 2] = 0x80;
+        }
+
+        let chr_trn = SgbPacket {
+            command: SgbCommand::ChrTrn,
+            data: vec![0x13 << 3 | 1, 0x01],
+        };
+
+        // tile 0x81 with palette 4, and tile 0x82 flipped sideways
+        let mut map = [0; TRANSFER_LEN];
+        map[0..2].copy_from_slice(&0x0081u16.to_le_bytes());
+        map[2..4].copy_from_slice(&0x4082u16.to_le_bytes());
+        // palette 4 color 1 is pure red
+        map[0x802..0x804].copy_from_slice(&0x001Fu16.to_le_bytes());
+
+        let mut border = SgbBorder::new();
+        border.transfer(&chr_trn, &tiles);
+        assert!(!border.is_set());
+
+        border.transfer(&pct_trn, &map);
+        assert!(border.is_set());
+
+        let screen = vec![0x123456; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let mut out = vec![0; SGB_WIDTH * SGB_HEIGHT];
+        border.render(&screen, receiver.backdrop(), &mut out);
+
+        assert_eq!(out[0], 0xFF0000);
+        assert_eq!(out[7 * SGB_WIDTH + 7], 0xFF0000);
+        // flipped, so the set column is on the right
+        assert_eq!(out[8], 0xFFFFFF);
+        assert_eq!(out[15], 0xFF0000);
+        assert_eq!(out[40 * SGB_WIDTH + 48], 0x123456);
+        assert_eq!(out[183 * SGB_WIDTH + 207], 0x123456);
+        assert_eq!(out[184 * SGB_WIDTH + 207], 0xFFFFFF);
+    }
+}
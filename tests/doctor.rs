@@ -0,0 +1,56 @@
+//! Compares the CPU against a Gameboy Doctor log, one instruction at a time
+//!
+//! Skipped unless both of these are set:
+//! - `GB_DOCTOR_ROM`: path to the ROM to run, like one of blargg's `cpu_instrs` tests
+//! - `GB_DOCTOR_LOG`: path to the reference log for that ROM
+//!
+//! ```sh
+//! GB_DOCTOR_ROM=roms/01-special.gb GB_DOCTOR_LOG=logs/01-special.txt cargo test --test doctor
+//! ```
+
+use gbc::{disassemble, Gbc};
+
+fn env_path(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|path| !path.is_empty())
+}
+
+#[test]
+fn doctor() {
+    let (Some(rom_path), Some(log_path)) = (env_path("GB_DOCTOR_ROM"), env_path("GB_DOCTOR_LOG")) else {
+        eprintln!("GB_DOCTOR_ROM and GB_DOCTOR_LOG aren't set, skipping");
+        return;
+    };
+
+    let rom = std::fs::read(&rom_path).expect("Couldn't read GB_DOCTOR_ROM");
+    let log = std::fs::read_to_string(&log_path).expect("Couldn't read GB_DOCTOR_LOG");
+
+    let mut gbc = Gbc::new_headless(&rom).expect("Couldn't load GB_DOCTOR_ROM");
+    // doctor logs are made with LY stuck at 0x90, so the ppu can't move on without the cpu noticing
+    gbc.cpu.doctor_mode = true;
+
+    let mut previous: Option<(String, String)> = None;
+
+    for (line, expected) in log.lines().enumerate() {
+        let actual = gbc.cpu.trace_line();
+
+        if actual != expected.trim_end() {
+            let (before, instruction) = previous.unwrap_or_default();
+
+            panic!(
+                "Diverged from {log_path} on line {}\n\
+                 after:    {instruction}\n\
+                 from:     {before}\n\
+                 expected: {expected}\n\
+                 actual:   {actual}",
+                line + 1,
+            );
+        }
+
+        let (instruction, _) = disassemble(&gbc.cpu.memory, gbc.cpu.regs.pc);
+        previous = Some((actual, instruction));
+
+        if let (Err(e), _) = gbc.step() {
+            panic!("{e} on line {} of {log_path}", line + 1);
+        }
+    }
+}